@@ -0,0 +1,692 @@
+//! Analog to Digital Converter (ADC)
+//!
+//! 12-bit successive approximation converters, ADC1 and ADC2. Channels 0-15
+//! are the pins below, ADC1 also measures the internal temperature sensor
+//! (channel 16) and the internal reference voltage (channel 17).
+//!
+//! | Channel | 0-7     | 8-9     | 10-15   |
+//! |---------|---------|---------|---------|
+//! | Pin     | PA0-PA7 | PB0-PB1 | PC0-PC5 |
+//!
+//! ```
+//! let mut adc = Adc::new(dp.ADC1, ccdr.peripheral.ADC1, &ccdr.clocks);
+//! let mut pa0 = gpioa.pa0.into_analog();
+//!
+//! adc.set_sample_time(&pa0, SampleTime::T_239P5);
+//! let raw: u16 = adc.read(&mut pa0).unwrap();
+//! let mv = adc.read_millivolts(&mut pa0);
+//! ```
+//!
+//! ADC1 can also convert a sequence of up to 16 channels in one go and
+//! have DMA1 channel 1 store the results, see [`Adc::into_scan`]:
+//!
+//! ```
+//! static mut RESULTS: [u16; 3] = [0; 3];
+//!
+//! let dma1 = dp.DMA1.split(ccdr.peripheral.DMA1);
+//! let mut scan = adc.into_scan(&[0, 1, 8], dma1.ch1, unsafe { &mut RESULTS });
+//!
+//! scan.start();
+//! let results = scan.wait(); // PA0, PA1, PB0
+//! ```
+//!
+//! Paired with ADC2, ADC1 can sample a single channel at twice the rate of
+//! one converter, see [`Adc::into_interleaved`]:
+//!
+//! ```
+//! static mut SAMPLES: [u32; 512] = [0; 512];
+//!
+//! let adc2 = Adc::new(dp.ADC2, ccdr.peripheral.ADC2, &ccdr.clocks);
+//! let mut fast = adc.into_interleaved(adc2, 0, dma1.ch1, unsafe { &mut SAMPLES });
+//!
+//! fast.start();
+//! for sample in fast.wait() {
+//!     // 1024 samples of PA0, in time order
+//! }
+//! ```
+//!
+//! The converter clock is [`CoreClocks::adcclk`], configured through
+//! [`Rcc::adcclk`](crate::rcc::Rcc::adcclk). A conversion takes the sample
+//! time plus 12.5 ADC clock cycles.
+
+use core::convert::Infallible;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::delay::CycleDelay;
+use crate::dma::{Channel as DmaChannel, Direction, WordSize};
+use crate::gpio::{Analog, Pin};
+use crate::hal::adc::{Channel, OneShot};
+use crate::pac::{adc1, ADC1, ADC2};
+use crate::rcc::rec::{self, ResetEnable};
+use crate::rcc::CoreClocks;
+use crate::time::Hertz;
+
+/// Typical internal reference voltage, in millivolts
+pub const VREFINT_MV: u32 = 1200;
+
+/// Factory calibration point of the temperature sensor in system memory:
+/// the sensor voltage in mV in the low half word, the temperature in °C it
+/// was measured at in the high half word
+const TS_CAL_ADDR: usize = 0x1FFF_F720;
+/// Typical sensor voltage at 25°C, in millivolts, from the datasheet
+const V25_MV: i32 = 1430;
+/// Typical sensor slope, in 0.1 mV/°C, the voltage falls as it heats up
+const AVG_SLOPE: i32 = 43;
+
+/// ADC sample time, in ADC clock cycles
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(non_camel_case_types)]
+#[repr(u8)]
+pub enum SampleTime {
+    /// 1.5 cycles
+    T_1P5 = 0b000,
+    /// 7.5 cycles
+    T_7P5 = 0b001,
+    /// 13.5 cycles
+    T_13P5 = 0b010,
+    /// 28.5 cycles
+    T_28P5 = 0b011,
+    /// 41.5 cycles
+    T_41P5 = 0b100,
+    /// 55.5 cycles
+    T_55P5 = 0b101,
+    /// 71.5 cycles
+    T_71P5 = 0b110,
+    /// 239.5 cycles
+    T_239P5 = 0b111,
+}
+
+impl Default for SampleTime {
+    /// 28.5 cycles
+    fn default() -> Self {
+        SampleTime::T_28P5
+    }
+}
+
+/// An ADC peripheral
+pub trait Instance: crate::Sealed {
+    /// The Reset and Enable control block for this ADC
+    type Rec: ResetEnable;
+
+    /// Pointer to the register block
+    fn ptr() -> *const adc1::RegisterBlock;
+}
+
+/// Internal temperature sensor, ADC1 channel 16
+pub struct Temperature;
+
+/// Internal reference voltage, ADC1 channel 17
+pub struct Vref;
+
+macro_rules! adc_pins {
+    ($ADC:ident: $($P:literal, $N:literal => $chan:literal;)+) => {
+        $(
+            impl Channel<$ADC> for Pin<$P, $N, Analog> {
+                type ID = u8;
+
+                fn channel() -> u8 {
+                    $chan
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! adc {
+    ($($ADC:ident: $Rec:ident,)+) => {
+        $(
+            impl crate::Sealed for $ADC {}
+
+            impl Instance for $ADC {
+                type Rec = rec::$Rec;
+
+                #[inline(always)]
+                fn ptr() -> *const adc1::RegisterBlock {
+                    $ADC::ptr()
+                }
+            }
+
+            adc_pins!($ADC:
+                'A', 0 => 0;
+                'A', 1 => 1;
+                'A', 2 => 2;
+                'A', 3 => 3;
+                'A', 4 => 4;
+                'A', 5 => 5;
+                'A', 6 => 6;
+                'A', 7 => 7;
+                'B', 0 => 8;
+                'B', 1 => 9;
+                'C', 0 => 10;
+                'C', 1 => 11;
+                'C', 2 => 12;
+                'C', 3 => 13;
+                'C', 4 => 14;
+                'C', 5 => 15;
+            );
+        )+
+    };
+}
+
+adc! {
+    ADC1: Adc1,
+    ADC2: Adc2,
+}
+
+impl Channel<ADC1> for Temperature {
+    type ID = u8;
+
+    fn channel() -> u8 {
+        16
+    }
+}
+
+impl Channel<ADC1> for Vref {
+    type ID = u8;
+
+    fn channel() -> u8 {
+        17
+    }
+}
+
+/// Analog to Digital Converter
+pub struct Adc<ADC: Instance> {
+    rb: ADC,
+    prec: ADC::Rec,
+    clock: Hertz,
+    // Core clock, for the CycleDelay waits
+    hclk: Hertz,
+    // VDDA in mV, from the last `refresh_vref`
+    vdda_mv: Option<u16>,
+}
+
+impl<ADC: Instance> Adc<ADC> {
+    /// Powers up and calibrates the ADC.
+    ///
+    /// Conversions are started by software, results are right aligned and
+    /// every channel samples for [`SampleTime::default`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ADC clock is above 14MHz, which happens when PCLK2 is
+    /// above 112MHz, see [`Rcc::adcclk`](crate::rcc::Rcc::adcclk).
+    pub fn new(adc: ADC, prec: ADC::Rec, clocks: &CoreClocks) -> Self {
+        assert!(
+            clocks.adcclk().raw() <= 14_000_000,
+            "ADC clock above 14MHz, lower PCLK2"
+        );
+        let prec = prec.enable().reset();
+
+        let mut s = Adc {
+            rb: adc,
+            prec,
+            clock: clocks.adcclk(),
+            hclk: clocks.hclk(),
+            vdda_mv: None,
+        };
+        let rb = s.rb();
+
+        rb.ctlr1.reset();
+        // SWSTART is EXTSEL 0b111, and EXTTRIG must be set for it to work
+        rb.ctlr2
+            .write(|w| unsafe { w.extsel().bits(0b111).exttrig().set_bit() });
+
+        let st = SampleTime::default() as u32;
+        let all = (0..10).fold(0, |bits, ch| bits | st << (3 * ch));
+        rb.samptr1.write(|w| unsafe { w.bits(all & 0x00FF_FFFF) });
+        rb.samptr2.write(|w| unsafe { w.bits(all) });
+
+        s.power_up();
+        s.calibrate();
+        s
+    }
+
+    #[inline(always)]
+    fn rb(&self) -> &adc1::RegisterBlock {
+        // NOTE(unsafe) we own the ADC
+        unsafe { &*ADC::ptr() }
+    }
+
+    fn power_up(&mut self) {
+        self.rb().ctlr2.modify(|_, w| w.adon().set_bit());
+        // tSTAB, 1µs
+        CycleDelay::new(self.hclk).delay_us(1);
+    }
+
+    /// Runs the self-calibration, which the converter needs once after
+    /// power up to reach its specified accuracy.
+    pub fn calibrate(&mut self) {
+        let rb = self.rb();
+        // At least 2 ADC clock cycles powered up before calibrating
+        let cycles = 2 * u64::from(self.hclk.raw() / self.clock.raw() + 1);
+        CycleDelay::new(self.hclk).delay_cycles(cycles);
+
+        rb.ctlr2.modify(|_, w| w.rstcal().set_bit());
+        while rb.ctlr2.read().rstcal().bit_is_set() {}
+
+        rb.ctlr2.modify(|_, w| w.cal().set_bit());
+        while rb.ctlr2.read().cal().bit_is_set() {}
+    }
+
+    /// Sets the sample time of the channel of `pin`. Kept until changed, or
+    /// until the ADC is reset.
+    pub fn set_sample_time<PIN: Channel<ADC, ID = u8>>(
+        &mut self,
+        _pin: &PIN,
+        sample_time: SampleTime,
+    ) {
+        self.set_channel_sample_time(PIN::channel(), sample_time);
+    }
+
+    fn set_channel_sample_time(&mut self, channel: u8, sample_time: SampleTime) {
+        let st = sample_time as u32;
+        let rb = self.rb();
+        if channel < 10 {
+            let offset = 3 * u32::from(channel);
+            rb.samptr2
+                .modify(|r, w| unsafe { w.bits((r.bits() & !(0b111 << offset)) | (st << offset)) });
+        } else {
+            let offset = 3 * u32::from(channel - 10);
+            rb.samptr1
+                .modify(|r, w| unsafe { w.bits((r.bits() & !(0b111 << offset)) | (st << offset)) });
+        }
+    }
+
+    /// Selects `channel` as the only regular conversion
+    fn select_channel(&mut self, channel: u8) {
+        let rb = self.rb();
+        rb.rsqr1.write(|w| unsafe { w.l().bits(0) });
+        rb.rsqr3.write(|w| unsafe { w.sq1().bits(channel) });
+    }
+
+    fn convert(&mut self, channel: u8) -> u16 {
+        self.select_channel(channel);
+        let rb = self.rb();
+        rb.ctlr2
+            .modify(|_, w| w.cont().clear_bit().swstart().set_bit());
+        while rb.statr.read().eoc().bit_is_clear() {}
+        // Reading RDATAR clears EOC
+        rb.rdatar.read().bits() as u16
+    }
+
+    /// Converts `pin` over and over in the background, until
+    /// [`stop_continuous`](Adc::stop_continuous). Read the results with
+    /// [`try_read`](Adc::try_read).
+    pub fn start_continuous<PIN: Channel<ADC, ID = u8>>(&mut self, _pin: &mut PIN) {
+        self.select_channel(PIN::channel());
+        self.rb()
+            .ctlr2
+            .modify(|_, w| w.cont().set_bit().swstart().set_bit());
+    }
+
+    /// Returns the latest continuous conversion result, if there is a new
+    /// one since the last call
+    pub fn try_read(&mut self) -> Option<u16> {
+        let rb = self.rb();
+        if rb.statr.read().eoc().bit_is_set() {
+            Some(rb.rdatar.read().bits() as u16)
+        } else {
+            None
+        }
+    }
+
+    /// Stops continuous conversions after the current one
+    pub fn stop_continuous(&mut self) {
+        self.rb().ctlr2.modify(|_, w| w.cont().clear_bit());
+    }
+
+    /// Powers down the ADC, disables its clock, and releases the
+    /// peripheral and the REC token for a later [`new`](Adc::new)
+    pub fn release(self) -> (ADC, ADC::Rec) {
+        self.rb().ctlr2.modify(|_, w| w.adon().clear_bit());
+        (self.rb, self.prec.disable())
+    }
+}
+
+impl Adc<ADC1> {
+    /// Powers the temperature sensor and the reference voltage up or down
+    pub fn enable_internal_channels(&mut self, enable: bool) {
+        self.rb().ctlr2.modify(|_, w| w.tsvrefe().bit(enable));
+    }
+
+    fn read_internal(&mut self, channel: u8) -> u16 {
+        let was_enabled = self.rb().ctlr2.read().tsvrefe().bit_is_set();
+        if !was_enabled {
+            self.enable_internal_channels(true);
+            // tSTART of the temperature sensor, 10µs
+            CycleDelay::new(self.hclk).delay_us(10);
+        }
+
+        // Both channels need a long sample time, 17.1µs for the sensor
+        self.set_channel_sample_time(channel, SampleTime::T_239P5);
+        let raw = self.convert(channel);
+
+        if !was_enabled {
+            self.enable_internal_channels(false);
+        }
+        raw
+    }
+
+    /// Converts the internal temperature sensor.
+    ///
+    /// The result is the raw sensor voltage, the temperature follows from
+    /// `(V25 - Vsense) / Avg_Slope + 25` with V25 and Avg_Slope from the
+    /// datasheet. Both vary from part to part, so calibrate against a known
+    /// temperature for anything more precise than a few degrees, or use
+    /// [`read_temperature_calibrated`](Adc::read_temperature_calibrated).
+    pub fn read_temp_raw(&mut self) -> u16 {
+        self.read_internal(Temperature::channel())
+    }
+
+    /// Measures the die temperature, in °C.
+    ///
+    /// Uses the factory calibration point at `0x1FFF_F720` in system
+    /// memory: the sensor voltage (low half word, mV) at a temperature
+    /// taken at the factory (high half word, °C, usually about 25°C). Only
+    /// the offset is calibrated, the slope is the typical 4.3 mV/°C of the
+    /// datasheet, so the error grows away from the calibration point. If
+    /// the point is missing or implausible, this falls back to the typical
+    /// 1.43V at 25°C, and is then only good for ±10°C or so.
+    ///
+    /// The sensor voltage is measured against VDDA, which is derived from
+    /// the internal reference voltage, see [`read_vref_mv`](Adc::read_vref_mv).
+    pub fn read_temperature_calibrated(&mut self) -> i32 {
+        let vdda = i32::from(self.read_vref_mv());
+        let mv = i32::from(self.read_temp_raw()) * vdda / 4095;
+        let (ref_mv, ref_temp) = ts_cal().unwrap_or((V25_MV, 25));
+        // Rounds to the nearest degree
+        let delta = (mv - ref_mv) * 10;
+        ref_temp - (delta + delta.signum() * AVG_SLOPE / 2) / AVG_SLOPE
+    }
+
+    /// Converts the internal reference voltage
+    pub fn read_vref_raw(&mut self) -> u16 {
+        self.read_internal(Vref::channel())
+    }
+
+    /// Derives the ADC reference voltage (VDDA), in millivolts, from the
+    /// internal reference voltage and its typical value [`VREFINT_MV`]
+    pub fn read_vref_mv(&mut self) -> u16 {
+        let raw = u32::from(self.read_vref_raw()).max(1);
+        (VREFINT_MV * 4095 / raw) as u16
+    }
+
+    /// Converts `pin` and scales the result to millivolts, against the
+    /// VDDA of the last [`refresh_vref`](Adc::refresh_vref). The first call
+    /// measures it.
+    ///
+    /// VDDA is derived from the internal reference voltage, which is only
+    /// known as its typical value [`VREFINT_MV`] and varies by a few
+    /// percent from part to part, and with temperature. The result is
+    /// therefore only accurate to a few percent, plus the few LSB of offset
+    /// and linearity error of the converter. Calibrate against a known
+    /// voltage for better.
+    pub fn read_millivolts<PIN: Channel<ADC1, ID = u8>>(&mut self, _pin: &mut PIN) -> u16 {
+        let vdda = match self.vdda_mv {
+            Some(mv) => mv,
+            None => self.refresh_vref(),
+        };
+        let raw = self.convert(PIN::channel());
+        (u32::from(raw) * u32::from(vdda) / 4095) as u16
+    }
+
+    /// Measures VDDA again for [`read_millivolts`](Adc::read_millivolts)
+    /// and returns it, in millivolts. Call it when the supply may have
+    /// changed, e.g. on a discharging battery.
+    pub fn refresh_vref(&mut self) -> u16 {
+        let mv = self.read_vref_mv();
+        self.vdda_mv = Some(mv);
+        mv
+    }
+
+    /// Converts `channels` in order on every [`AdcScan::start`], with DMA
+    /// storing the results at the start of `buffer`.
+    ///
+    /// The channel numbers are listed in the [module docs](self); for a pin
+    /// it is `<PIN as Channel<ADC1>>::channel()`. Internal channels must be
+    /// powered with [`enable_internal_channels`](Adc::enable_internal_channels).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is empty, longer than 16 or than `buffer`, or
+    /// holds a channel above 17.
+    pub fn into_scan(
+        self,
+        channels: &[u8],
+        mut dma: DmaChannel<1, 1>,
+        buffer: &'static mut [u16],
+    ) -> AdcScan {
+        let len = channels.len();
+        assert!((1..=16).contains(&len) && len <= buffer.len());
+        assert!(channels.iter().all(|ch| *ch <= 17));
+
+        let rb = self.rb();
+
+        // SQ1-SQ6 in RSQR3, SQ7-SQ12 in RSQR2, SQ13-SQ16 and L in RSQR1
+        let mut rsqr = [0u32; 3];
+        for (i, ch) in channels.iter().enumerate() {
+            rsqr[i / 6] |= u32::from(*ch) << (5 * (i % 6));
+        }
+        rsqr[2] |= (len as u32 - 1) << 20;
+        rb.rsqr3.write(|w| unsafe { w.bits(rsqr[0]) });
+        rb.rsqr2.write(|w| unsafe { w.bits(rsqr[1]) });
+        rb.rsqr1.write(|w| unsafe { w.bits(rsqr[2]) });
+
+        rb.ctlr1.modify(|_, w| w.scan().set_bit());
+        rb.ctlr2.modify(|_, w| w.cont().clear_bit().dma().set_bit());
+
+        dma.stop();
+        dma.set_peripheral_address(&rb.rdatar as *const _ as u32, false);
+        dma.set_memory_address(buffer.as_mut_ptr() as u32, true);
+        dma.set_word_size(WordSize::Bits16);
+        dma.set_direction(Direction::PeripheralToMemory);
+        dma.set_circular(false);
+
+        AdcScan {
+            adc: self,
+            dma,
+            buffer,
+            len,
+        }
+    }
+
+    /// Converts `channel` alternately with this ADC and `adc2`, in fast
+    /// interleaved dual mode (DUALMOD `0b0111`), with DMA storing the
+    /// results in `buffer`.
+    ///
+    /// ADC2 starts converting right away and ADC1 7 ADC clock cycles later,
+    /// both then convert continuously. With the sample time forced to 1.5
+    /// cycles, each conversion takes 14 cycles, so a sample comes every 7
+    /// ADC clock cycles: 2 MSPS at the maximum ADC clock of 14 MHz.
+    ///
+    /// Each word of `buffer` holds one result of both ADCs, ADC2 in the
+    /// upper half and the ADC1 result taken 7 cycles later in the lower
+    /// half. [`AdcInterleaved::wait`] returns them in time order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is above 15, the internal channels only exist on
+    /// ADC1, or if `buffer` is empty or longer than 65535 words.
+    pub fn into_interleaved(
+        mut self,
+        mut adc2: Adc<ADC2>,
+        channel: u8,
+        mut dma: DmaChannel<1, 1>,
+        buffer: &'static mut [u32],
+    ) -> AdcInterleaved {
+        assert!(channel <= 15);
+        assert!((1..=usize::from(u16::MAX)).contains(&buffer.len()));
+
+        // Sampling phases must not overlap, so less than 7 cycles
+        self.set_channel_sample_time(channel, SampleTime::T_1P5);
+        adc2.set_channel_sample_time(channel, SampleTime::T_1P5);
+        self.select_channel(channel);
+        adc2.select_channel(channel);
+
+        let rb = self.rb();
+        rb.ctlr1.modify(|_, w| unsafe { w.dualmod().bits(0b0111) });
+        rb.ctlr2.modify(|_, w| w.dma().set_bit());
+
+        dma.stop();
+        // In dual mode, the 32 bits of RDATAR hold both results
+        dma.set_peripheral_address(&rb.rdatar as *const _ as u32, false);
+        dma.set_memory_address(buffer.as_mut_ptr() as u32, true);
+        dma.set_word_size(WordSize::Bits32);
+        dma.set_direction(Direction::PeripheralToMemory);
+        dma.set_circular(false);
+
+        AdcInterleaved {
+            adc1: self,
+            adc2,
+            dma,
+            buffer,
+        }
+    }
+}
+
+/// The temperature sensor calibration point, as `(mV, °C)`, if it is
+/// plausible. Erased or missing values read as all ones.
+fn ts_cal() -> Option<(i32, i32)> {
+    // NOTE(unsafe) system memory is always readable
+    let cal = unsafe { core::ptr::read_volatile(TS_CAL_ADDR as *const u32) };
+    let mv = (cal & 0xFFFF) as i32;
+    let temp = i32::from((cal >> 16) as u16 as i16);
+    ((1000..=1800).contains(&mv) && (-40..=125).contains(&temp)).then_some((mv, temp))
+}
+
+/// ADC1 converting a channel sequence into a buffer, see [`Adc::into_scan`]
+pub struct AdcScan {
+    adc: Adc<ADC1>,
+    dma: DmaChannel<1, 1>,
+    buffer: &'static mut [u16],
+    len: usize,
+}
+
+impl AdcScan {
+    /// Starts converting the sequence once. The previous results are
+    /// overwritten as the new ones come in.
+    pub fn start(&mut self) {
+        self.dma.stop();
+        self.dma.set_transfer_length(self.len as u16);
+        compiler_fence(Ordering::Release);
+        self.dma.start();
+        self.adc.rb().ctlr2.modify(|_, w| w.swstart().set_bit());
+    }
+
+    /// Whether the whole sequence has been converted and stored
+    pub fn is_complete(&self) -> bool {
+        self.dma.is_complete()
+    }
+
+    /// The results of the last complete sequence, in channel order
+    pub fn results(&self) -> Option<&[u16]> {
+        if self.is_complete() {
+            compiler_fence(Ordering::Acquire);
+            Some(&self.buffer[..self.len])
+        } else {
+            None
+        }
+    }
+
+    /// Waits for the sequence to complete and returns the results
+    pub fn wait(&mut self) -> &[u16] {
+        while !self.is_complete() {}
+        compiler_fence(Ordering::Acquire);
+        &self.buffer[..self.len]
+    }
+
+    /// Stops scanning, powers down ADC1 and disables its clock, and
+    /// releases the peripheral, its REC token, the DMA channel and the
+    /// buffer
+    pub fn release(mut self) -> ((ADC1, rec::Adc1), DmaChannel<1, 1>, &'static mut [u16]) {
+        self.dma.stop();
+        let rb = self.adc.rb();
+        rb.ctlr1.modify(|_, w| w.scan().clear_bit());
+        rb.ctlr2.modify(|_, w| w.dma().clear_bit());
+        compiler_fence(Ordering::Acquire);
+        (self.adc.release(), self.dma, self.buffer)
+    }
+}
+
+/// ADC1 and ADC2 interleaving conversions of one channel into a buffer,
+/// see [`Adc::into_interleaved`]
+pub struct AdcInterleaved {
+    adc1: Adc<ADC1>,
+    adc2: Adc<ADC2>,
+    dma: DmaChannel<1, 1>,
+    buffer: &'static mut [u32],
+}
+
+impl AdcInterleaved {
+    /// Starts filling the buffer once. The previous samples are
+    /// overwritten as the new ones come in.
+    pub fn start(&mut self) {
+        self.dma.stop();
+        self.dma.set_transfer_length(self.buffer.len() as u16);
+        compiler_fence(Ordering::Release);
+        self.dma.start();
+        // CONT on both ADCs, the trigger of ADC1 starts the pair
+        self.adc2.rb().ctlr2.modify(|_, w| w.cont().set_bit());
+        self.adc1
+            .rb()
+            .ctlr2
+            .modify(|_, w| w.cont().set_bit().swstart().set_bit());
+    }
+
+    /// Whether the buffer is full
+    pub fn is_complete(&self) -> bool {
+        self.dma.is_complete()
+    }
+
+    /// Waits for the buffer to fill, stops converting and returns the
+    /// samples in time order
+    pub fn wait(&mut self) -> impl Iterator<Item = u16> + '_ {
+        while !self.is_complete() {}
+        self.stop();
+        compiler_fence(Ordering::Acquire);
+        self.buffer
+            .iter()
+            .flat_map(|word| [(word >> 16) as u16, *word as u16])
+    }
+
+    fn stop(&mut self) {
+        self.adc1.rb().ctlr2.modify(|_, w| w.cont().clear_bit());
+        self.adc2.rb().ctlr2.modify(|_, w| w.cont().clear_bit());
+    }
+
+    /// Stops converting, leaves dual mode, powers down both ADCs and
+    /// disables their clocks, and releases the peripherals, their REC
+    /// tokens, the DMA channel and the buffer
+    pub fn release(
+        mut self,
+    ) -> (
+        (ADC1, rec::Adc1),
+        (ADC2, rec::Adc2),
+        DmaChannel<1, 1>,
+        &'static mut [u32],
+    ) {
+        self.stop();
+        self.dma.stop();
+        let rb = self.adc1.rb();
+        rb.ctlr1.modify(|_, w| unsafe { w.dualmod().bits(0) });
+        rb.ctlr2.modify(|_, w| w.dma().clear_bit());
+        compiler_fence(Ordering::Acquire);
+        (
+            self.adc1.release(),
+            self.adc2.release(),
+            self.dma,
+            self.buffer,
+        )
+    }
+}
+
+impl<ADC: Instance, WORD: From<u16>, PIN: Channel<ADC, ID = u8>> OneShot<ADC, WORD, PIN>
+    for Adc<ADC>
+{
+    type Error = Infallible;
+
+    fn read(&mut self, _pin: &mut PIN) -> nb::Result<WORD, Infallible> {
+        Ok(self.convert(PIN::channel()).into())
+    }
+}
@@ -0,0 +1,283 @@
+//! Alternate Function I/O (AFIO)
+//!
+//! Owns the EXTI line routing (`EXTICR1`-`EXTICR4`) used by
+//! [`ExtiPin`](crate::gpio::ExtiPin) and the peripheral pin remapping in
+//! `PCFR1`/`PCFR2`.
+//!
+//! Remapping only moves the signals; the pins still have to be put into the
+//! matching alternate or input mode. The pins listed on each variant are the
+//! ones the peripheral uses under that mapping.
+//!
+//! **NOTE**: The remap is not checked against the pins handed to the
+//! drivers. Their pin traits accept every mapping of a peripheral, so
+//! `Serial::new(USART1, (pb6, pb7), ..)` builds fine without
+//! `remap_usart1(Usart1Remap::Remap1)`, and then TX and RX stay on PA9 and
+//! PA10. Select the remap that matches the pins before creating the driver.
+
+use crate::gpio::{Debugger, Unconfigured, PA13, PA14};
+use crate::pac::AFIO;
+use crate::rcc::rec::{self, ResetEnable};
+
+/// Extension trait that constrains the `AFIO` peripheral
+pub trait AfioExt {
+    /// Constrains the `AFIO` peripheral, enabling its clock
+    fn constrain(self, prec: rec::Afio) -> Afio;
+}
+
+impl AfioExt for AFIO {
+    fn constrain(self, prec: rec::Afio) -> Afio {
+        prec.enable();
+        Afio {
+            rb: self,
+            sw_cfg: 0,
+        }
+    }
+}
+
+/// Constrained AFIO peripheral
+///
+/// Generated by calling `constrain` on the PAC's AFIO peripheral.
+pub struct Afio {
+    pub(crate) rb: AFIO,
+    // SW_CFG is write-only and reads back as 0, keep the value we wrote
+    sw_cfg: u32,
+}
+
+// PCFR1.SW_CFG
+const SW_CFG_MASK: u32 = 0b111 << 24;
+
+/// USART1 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Usart1Remap {
+    /// TX: PA9, RX: PA10
+    Default,
+    /// TX: PB6, RX: PB7
+    Remap1,
+    /// TX: PB15, RX: PA8
+    Remap2,
+    /// TX: PA6, RX: PA7
+    Remap3,
+}
+
+/// USART2 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Usart2Remap {
+    /// TX: PA2, RX: PA3
+    Default,
+    /// TX: PD5, RX: PD6
+    Remap,
+}
+
+/// USART3 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Usart3Remap {
+    /// TX: PB10, RX: PB11
+    Default,
+    /// TX: PC10, RX: PC11
+    Partial,
+    /// TX: PD8, RX: PD9
+    Full,
+}
+
+/// SPI1 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Spi1Remap {
+    /// NSS: PA4, SCK: PA5, MISO: PA6, MOSI: PA7
+    Default,
+    /// NSS: PA15, SCK: PB3, MISO: PB4, MOSI: PB5
+    Remap,
+}
+
+/// I2C1 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum I2c1Remap {
+    /// SCL: PB6, SDA: PB7
+    Default,
+    /// SCL: PB8, SDA: PB9
+    Remap,
+}
+
+/// TIM1 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Tim1Remap {
+    /// CH1-CH4: PA8-PA11, CH1N-CH3N: PB13-PB15, BKIN: PB12
+    Default,
+    /// CH1-CH4: PA8-PA11, CH1N-CH3N: PA7, PB0, PB1, BKIN: PA6
+    Partial,
+    /// CH1-CH4: PE9, PE11, PE13, PE14, CH1N-CH3N: PE8, PE10, PE12,
+    /// BKIN: PE15
+    Full,
+}
+
+/// TIM2 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Tim2Remap {
+    /// CH1-CH4: PA0, PA1, PA2, PA3
+    Default,
+    /// CH1-CH4: PA15, PB3, PA2, PA3
+    Partial1,
+    /// CH1-CH4: PA0, PA1, PB10, PB11
+    Partial2,
+    /// CH1-CH4: PA15, PB3, PB10, PB11
+    Full,
+}
+
+/// TIM3 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Tim3Remap {
+    /// CH1-CH4: PA6, PA7, PB0, PB1
+    Default,
+    /// CH1-CH4: PB4, PB5, PB0, PB1
+    Partial,
+    /// CH1-CH4: PC6, PC7, PC8, PC9
+    Full,
+}
+
+/// TIM4 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Tim4Remap {
+    /// CH1-CH4: PB6-PB9
+    Default,
+    /// CH1-CH4: PD12-PD15
+    Remap,
+}
+
+/// CAN1 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Can1Remap {
+    /// RX, TX: PA11, PA12
+    Default,
+    /// RX, TX: PB8, PB9
+    Remap2,
+    /// RX, TX: PD0, PD1
+    Remap3,
+}
+
+/// CAN2 pin mapping
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Can2Remap {
+    /// RX, TX: PB12, PB13
+    Default,
+    /// RX, TX: PB5, PB6
+    Remap,
+}
+
+impl Afio {
+    /// Select the USART1 pin mapping
+    pub fn remap_usart1(&mut self, remap: Usart1Remap) {
+        let (rm, rm1) = match remap {
+            Usart1Remap::Default => (0, 0),
+            Usart1Remap::Remap1 => (1, 0),
+            Usart1Remap::Remap2 => (0, 1),
+            Usart1Remap::Remap3 => (1, 1),
+        };
+        self.modify_pcfr1(0b1 << 2, rm << 2);
+        self.rb
+            .pcfr2
+            .modify(|r, w| unsafe { w.bits((r.bits() & !(0b1 << 26)) | (rm1 << 26)) });
+    }
+
+    /// Select the USART2 pin mapping
+    pub fn remap_usart2(&mut self, remap: Usart2Remap) {
+        self.modify_pcfr1(0b1 << 3, (remap as u32) << 3);
+    }
+
+    /// Select the USART3 pin mapping
+    pub fn remap_usart3(&mut self, remap: Usart3Remap) {
+        let bits = match remap {
+            Usart3Remap::Default => 0b00,
+            Usart3Remap::Partial => 0b01,
+            Usart3Remap::Full => 0b11,
+        };
+        self.modify_pcfr1(0b11 << 4, bits << 4);
+    }
+
+    /// Select the SPI1 pin mapping
+    pub fn remap_spi1(&mut self, remap: Spi1Remap) {
+        self.modify_pcfr1(0b1, remap as u32);
+    }
+
+    /// Select the I2C1 pin mapping
+    pub fn remap_i2c1(&mut self, remap: I2c1Remap) {
+        self.modify_pcfr1(0b1 << 1, (remap as u32) << 1);
+    }
+
+    /// Select the TIM1 pin mapping
+    pub fn remap_tim1(&mut self, remap: Tim1Remap) {
+        let bits = match remap {
+            Tim1Remap::Default => 0b00,
+            Tim1Remap::Partial => 0b01,
+            Tim1Remap::Full => 0b11,
+        };
+        self.modify_pcfr1(0b11 << 6, bits << 6);
+    }
+
+    /// Select the TIM2 pin mapping
+    pub fn remap_tim2(&mut self, remap: Tim2Remap) {
+        self.modify_pcfr1(0b11 << 8, (remap as u32) << 8);
+    }
+
+    /// Select the TIM3 pin mapping
+    pub fn remap_tim3(&mut self, remap: Tim3Remap) {
+        let bits = match remap {
+            Tim3Remap::Default => 0b00,
+            Tim3Remap::Partial => 0b10,
+            Tim3Remap::Full => 0b11,
+        };
+        self.modify_pcfr1(0b11 << 10, bits << 10);
+    }
+
+    /// Select the TIM4 pin mapping
+    pub fn remap_tim4(&mut self, remap: Tim4Remap) {
+        self.modify_pcfr1(0b1 << 12, (remap as u32) << 12);
+    }
+
+    /// Select the CAN1 pin mapping
+    pub fn remap_can1(&mut self, remap: Can1Remap) {
+        let bits = match remap {
+            Can1Remap::Default => 0b00,
+            Can1Remap::Remap2 => 0b10,
+            Can1Remap::Remap3 => 0b11,
+        };
+        self.modify_pcfr1(0b11 << 13, bits << 13);
+    }
+
+    /// Select the CAN2 pin mapping
+    pub fn remap_can2(&mut self, remap: Can2Remap) {
+        self.modify_pcfr1(0b1 << 22, (remap as u32) << 22);
+    }
+
+    /// Disables the SWD debug interface and releases its pins for other
+    /// uses.
+    ///
+    /// Debug access is lost until the next reset, flashing then requires
+    /// holding the chip in reset or erasing it through the bootloader.
+    pub fn deactivate_swj(
+        &mut self,
+        pa13: PA13<Debugger>,
+        pa14: PA14<Debugger>,
+    ) -> (PA13<Unconfigured>, PA14<Unconfigured>) {
+        // SW_CFG = 100: SW-DP disabled
+        self.sw_cfg = 0b100 << 24;
+        self.modify_pcfr1(0, 0);
+        (pa13.into_unconfigured(), pa14.into_unconfigured())
+    }
+
+    fn modify_pcfr1(&mut self, mask: u32, bits: u32) {
+        let sw_cfg = self.sw_cfg;
+        self.rb
+            .pcfr1
+            .modify(|r, w| unsafe { w.bits((r.bits() & !(mask | SW_CFG_MASK)) | bits | sw_cfg) });
+    }
+}
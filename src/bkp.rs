@@ -0,0 +1,145 @@
+//! Backup data registers (BKP)
+//!
+//! 42 16-bit registers in the backup domain. They keep their value across
+//! resets and, with VBAT, across power loss, until a tamper event or a
+//! backup domain reset clears them.
+//!
+//! ```
+//! let mut pwr = dp.PWR.constrain(ccdr.peripheral.PWR);
+//! let mut backup = pwr.enable_backup_domain(ccdr.peripheral.BKP);
+//!
+//! let mut bkp = Bkp::new(dp.BKP, &mut backup);
+//! let boots = bkp.read(0).wrapping_add(1);
+//! bkp.write(0, boots);
+//! ```
+
+use vcell::VolatileCell;
+
+use crate::pac;
+use crate::pwr::BackupDomain;
+
+/// Number of backup data registers
+pub const DATA_REGISTERS: usize = 42;
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+struct BKP {
+    _reserved0: u32,
+    DATAR1: [VolatileCell<u32>; 10],
+    OCTLR: VolatileCell<u32>,
+    TPCTLR: VolatileCell<u32>,
+    TPCSR: VolatileCell<u32>,
+    _reserved1: [u32; 2],
+    DATAR11: [VolatileCell<u32>; 32],
+}
+
+// TPCTLR bits
+const TPE: u32 = 1 << 0;
+const TPAL: u32 = 1 << 1;
+// TPCSR bits
+const CTE: u32 = 1 << 0;
+const CTI: u32 = 1 << 1;
+const TPIE: u32 = 1 << 2;
+const TEF: u32 = 1 << 8;
+
+/// Level of the tamper pin (PC13) that clears the data registers
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TamperLevel {
+    /// Tamper on high level
+    High,
+    /// Tamper on low level
+    Low,
+}
+
+/// Backup data registers
+pub struct Bkp {
+    bkp: pac::BKP,
+}
+
+impl Bkp {
+    /// Takes the backup registers. Writes require the backup domain write
+    /// protection to stay lifted.
+    pub fn new(bkp: pac::BKP, _backup: &mut BackupDomain) -> Self {
+        Bkp { bkp }
+    }
+
+    fn regs(&self) -> &BKP {
+        // NOTE(unsafe) we own the BKP
+        unsafe { &*(pac::BKP::ptr() as *const BKP) }
+    }
+
+    fn data(&self, reg: usize) -> &VolatileCell<u32> {
+        let rb = self.regs();
+        match reg {
+            0..=9 => &rb.DATAR1[reg],
+            10..=41 => &rb.DATAR11[reg - 10],
+            _ => panic!("no backup data register {}", reg),
+        }
+    }
+
+    /// Reads backup data register `reg`, from 0
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reg` is not below [`DATA_REGISTERS`].
+    pub fn read(&self, reg: usize) -> u16 {
+        self.data(reg).get() as u16
+    }
+
+    /// Writes backup data register `reg`, from 0
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reg` is not below [`DATA_REGISTERS`].
+    pub fn write(&mut self, reg: usize, value: u16) {
+        self.data(reg).set(u32::from(value));
+    }
+
+    /// Clears the data registers when the tamper pin PC13 reaches `level`.
+    ///
+    /// The pin is then reserved for tamper detection.
+    pub fn enable_tamper(&mut self, level: TamperLevel) {
+        let rb = self.regs();
+        let tpal = match level {
+            TamperLevel::High => 0,
+            TamperLevel::Low => TPAL,
+        };
+        // TPAL must be set before TPE
+        rb.TPCTLR.set(tpal);
+        rb.TPCTLR.set(tpal | TPE);
+    }
+
+    /// Stops tamper detection, releasing PC13
+    pub fn disable_tamper(&mut self) {
+        self.regs().TPCTLR.set(0);
+    }
+
+    /// Starts raising the tamper interrupt on a tamper event
+    pub fn listen_tamper(&mut self) {
+        // TPIE is the only bit of TPCSR that reads back
+        self.regs().TPCSR.set(TPIE);
+    }
+
+    /// Stops raising the tamper interrupt
+    pub fn unlisten_tamper(&mut self) {
+        self.regs().TPCSR.set(0);
+    }
+
+    /// Whether a tamper event cleared the data registers
+    pub fn is_tampered(&self) -> bool {
+        self.regs().TPCSR.get() & TEF != 0
+    }
+
+    /// Clears the tamper event and interrupt flags. Tamper detection stays
+    /// blocked while the flag is set.
+    pub fn clear_tamper(&mut self) {
+        let rb = self.regs();
+        rb.TPCSR.set(rb.TPCSR.get() & TPIE | CTE | CTI);
+    }
+
+    /// Releases the backup registers
+    pub fn release(self) -> pac::BKP {
+        self.bkp
+    }
+}
@@ -0,0 +1,664 @@
+//! Controller Area Network (CAN), bxCAN
+//!
+//! ```
+//! let gpiob = dp.GPIOB.split(ccdr.peripheral.GPIOB);
+//! let rx = gpiob.pb8.into_floating_input();
+//! let tx = gpiob.pb9.into_alternate();
+//! afio.remap_can1(Can1Remap::Remap2);
+//!
+//! let mut can = Can::new(dp.CAN1, (tx, rx), 500_000.Hz(), ccdr.peripheral.CAN1, &ccdr.clocks)?;
+//! can.set_filter(0, Mask32::accept_all());
+//!
+//! let frame = Frame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+//! block!(can.transmit(&frame))?;
+//! let reply = block!(can.receive())?;
+//! ```
+//!
+//! Frames are received in FIFO 0 through the 28 filter banks, which live in
+//! CAN1. CAN2 uses banks 14 to 27 and needs the CAN1 clock for them, which
+//! [`Can::new`] enables for CAN2. Creating CAN1 resets all the banks, do it
+//! before setting the filters of CAN2. A frame only passes if it matches an
+//! enabled filter.
+//!
+//! The bit timing samples at ~87.5% of the bit with a synchronization jump
+//! width of 1 time quantum. The controller recovers from bus-off on its own.
+//!
+//! Frames are queued in three transmit mailboxes. By default they go out in
+//! the order they were queued, [`TxPriority::Identifier`] sends the pending
+//! frame with the highest priority ID first instead. When all mailboxes are
+//! pending, [`transmit`](Can::transmit) aborts a pending frame of lower
+//! priority to make room for the new one and hands it back. A burst of low
+//! priority frames that is waiting for a busy bus can then never hold an
+//! urgent frame back (priority inversion). [`abort`](Can::abort) cancels a
+//! pending frame by hand.
+//!
+//! In time triggered communication mode, see
+//! [`set_time_triggered`](Can::set_time_triggered), received frames carry
+//! the value of a 16-bit counter running at the bit rate, taken at the
+//! sample point of their start of frame bit, see [`Frame::timestamp`].
+
+use vcell::VolatileCell;
+
+pub use embedded_can::{ExtendedId, Id, StandardId};
+
+use crate::gpio::{Alternate, Input, Pin, PushPull};
+use crate::pac::{CAN1, CAN2, RCC};
+use crate::rcc::rec::{self, ResetEnable};
+use crate::rcc::CoreClocks;
+use crate::time::Hertz;
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+struct MailboxRegs {
+    IR: VolatileCell<u32>,
+    DTR: VolatileCell<u32>,
+    DLR: VolatileCell<u32>,
+    DHR: VolatileCell<u32>,
+}
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+struct CAN {
+    MCR: VolatileCell<u32>,
+    MSR: VolatileCell<u32>,
+    TSR: VolatileCell<u32>,
+    RFR: [VolatileCell<u32>; 2],
+    IER: VolatileCell<u32>,
+    ESR: VolatileCell<u32>,
+    BTR: VolatileCell<u32>,
+    _reserved0: [u32; 88],
+    TX: [MailboxRegs; 3],
+    RX: [MailboxRegs; 2],
+    _reserved1: [u32; 12],
+    FMR: VolatileCell<u32>,
+    FM1R: VolatileCell<u32>,
+    _reserved2: u32,
+    FS1R: VolatileCell<u32>,
+    _reserved3: u32,
+    FFA1R: VolatileCell<u32>,
+    _reserved4: u32,
+    FWR: VolatileCell<u32>,
+    _reserved5: [u32; 8],
+    FR: [[VolatileCell<u32>; 2]; 28],
+}
+
+// MCR bits
+const INRQ: u32 = 1 << 0;
+const SLEEP: u32 = 1 << 1;
+const TXFP: u32 = 1 << 2;
+const ABOM: u32 = 1 << 6;
+const TTCM: u32 = 1 << 7;
+// MSR bits
+const INAK: u32 = 1 << 0;
+// TSR bits, TXOK and ABRQ shifted by 8 * mailbox
+const TXOK: u32 = 1 << 1;
+const ABRQ: u32 = 1 << 7;
+const TME_OFFSET: u32 = 26;
+// RFR bits
+const FMP_MASK: u32 = 0b11;
+const FOVR: u32 = 1 << 4;
+const RFOM: u32 = 1 << 5;
+// ESR bits
+const BOFF: u32 = 1 << 2;
+// FMR bits
+const FINIT: u32 = 1 << 0;
+// Mailbox IR bits
+const TXRQ: u32 = 1 << 0;
+const RTR: u32 = 1 << 1;
+const IDE: u32 = 1 << 2;
+
+/// CAN error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// A received frame was dropped, FIFO 0 was full
+    Overrun,
+    /// The controller is bus-off after too many errors, it recovers after
+    /// 128 x 11 recessive bits
+    BusOff,
+    /// The controller did not enter or leave initialization mode, e.g. RX
+    /// is held low without a transceiver or on a shorted bus
+    Timeout,
+}
+
+impl embedded_can::Error for Error {
+    fn kind(&self) -> embedded_can::ErrorKind {
+        match self {
+            Error::Overrun => embedded_can::ErrorKind::Overrun,
+            Error::BusOff | Error::Timeout => embedded_can::ErrorKind::Other,
+        }
+    }
+}
+
+/// Order in which pending frames are sent, MCR.TXFP
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxPriority {
+    /// Highest priority identifier first, as in bus arbitration
+    Identifier,
+    /// In the order of the transmit requests, the default
+    Chronological,
+}
+
+/// A transmit mailbox
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mailbox {
+    Mailbox0 = 0,
+    Mailbox1 = 1,
+    Mailbox2 = 2,
+}
+
+impl Mailbox {
+    const ALL: [Mailbox; 3] = [Mailbox::Mailbox0, Mailbox::Mailbox1, Mailbox::Mailbox2];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// A CAN 2.0 frame
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Frame {
+    id: Id,
+    remote: bool,
+    dlc: u8,
+    data: [u8; 8],
+    timestamp: Option<u16>,
+}
+
+impl embedded_can::Frame for Frame {
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        if data.len() > 8 {
+            return None;
+        }
+        let mut frame = Frame {
+            id: id.into(),
+            remote: false,
+            dlc: data.len() as u8,
+            data: [0; 8],
+            timestamp: None,
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+        Some(frame)
+    }
+
+    fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+        if dlc > 8 {
+            return None;
+        }
+        Some(Frame {
+            id: id.into(),
+            remote: true,
+            dlc: dlc as u8,
+            data: [0; 8],
+            timestamp: None,
+        })
+    }
+
+    fn is_extended(&self) -> bool {
+        matches!(self.id, Id::Extended(_))
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn dlc(&self) -> usize {
+        usize::from(self.dlc)
+    }
+
+    /// The data, empty for remote frames
+    fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..usize::from(self.dlc)]
+        }
+    }
+}
+
+impl Frame {
+    /// Bit time counter value at the start of frame of a received frame,
+    /// `None` unless received in time triggered communication mode
+    pub fn timestamp(&self) -> Option<u16> {
+        self.timestamp
+    }
+
+    /// Reads a mailbox, with the TIME field of DTR as timestamp if `ttcm`
+    fn read(mb: &MailboxRegs, ttcm: bool) -> Frame {
+        let ir = mb.IR.get();
+        let id = if ir & IDE != 0 {
+            Id::Extended(ExtendedId::new(ir >> 3).unwrap())
+        } else {
+            Id::Standard(StandardId::new((ir >> 21) as u16).unwrap())
+        };
+        let mut data = [0; 8];
+        data[..4].copy_from_slice(&mb.DLR.get().to_le_bytes());
+        data[4..].copy_from_slice(&mb.DHR.get().to_le_bytes());
+        let dtr = mb.DTR.get();
+        Frame {
+            id,
+            remote: ir & RTR != 0,
+            // DLC values above 8 mean 8 bytes
+            dlc: (dtr & 0xF).min(8) as u8,
+            data,
+            timestamp: ttcm.then_some((dtr >> 16) as u16),
+        }
+    }
+
+    fn write(&self, mb: &MailboxRegs) {
+        let bytes = self.data;
+        mb.DTR.set(u32::from(self.dlc));
+        mb.DLR
+            .set(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        mb.DHR
+            .set(u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]));
+        mb.IR.set(self.ir() | TXRQ);
+    }
+
+    /// Identifier register value, which is lower for frames that win the
+    /// bus arbitration
+    fn ir(&self) -> u32 {
+        let rtr = if self.remote { RTR } else { 0 };
+        id_bits(self.id) | rtr
+    }
+}
+
+/// Identifier register (TIR, RIR) layout of an ID
+fn id_bits(id: Id) -> u32 {
+    match id {
+        Id::Standard(id) => u32::from(id.as_raw()) << 21,
+        Id::Extended(id) => id.as_raw() << 3 | IDE,
+    }
+}
+
+/// A 32-bit identifier and mask filter: a frame passes if its ID matches
+/// `id` on all the bits set in `mask`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Mask32 {
+    id: u32,
+    mask: u32,
+}
+
+impl Mask32 {
+    /// Passes every frame
+    pub fn accept_all() -> Self {
+        Mask32 { id: 0, mask: 0 }
+    }
+
+    /// Passes standard frames whose ID matches `id` on the bits of `mask`
+    pub fn frames_with_std_id(id: StandardId, mask: StandardId) -> Self {
+        Mask32 {
+            id: id_bits(Id::Standard(id)),
+            mask: id_bits(Id::Standard(mask)) | IDE,
+        }
+    }
+
+    /// Passes extended frames whose ID matches `id` on the bits of `mask`
+    pub fn frames_with_ext_id(id: ExtendedId, mask: ExtendedId) -> Self {
+        Mask32 {
+            id: id_bits(Id::Extended(id)),
+            mask: id_bits(Id::Extended(mask)),
+        }
+    }
+
+    /// Only passes data frames
+    pub fn data_frames_only(mut self) -> Self {
+        self.mask |= RTR;
+        self.id &= !RTR;
+        self
+    }
+
+    /// Only passes remote frames
+    pub fn remote_frames_only(mut self) -> Self {
+        self.mask |= RTR;
+        self.id |= RTR;
+        self
+    }
+}
+
+/// A CAN peripheral
+pub trait Instance: crate::Sealed {
+    /// The Reset and Enable control block for this CAN
+    type Rec: ResetEnable;
+
+    /// Base address of the registers
+    #[doc(hidden)]
+    fn base() -> usize;
+}
+
+/// A pin that can be used as the TX line of `CAN`
+pub trait PinTx<CAN> {}
+
+/// A pin that can be used as the RX line of `CAN`
+pub trait PinRx<CAN> {}
+
+/// A `(TX, RX)` pin pair
+pub trait Pins<CAN> {}
+
+impl<CAN, TX: PinTx<CAN>, RX: PinRx<CAN>> Pins<CAN> for (TX, RX) {}
+
+macro_rules! can {
+    ($($CANX:ident: $Rec:ident => {
+        tx: [$(($TxP:literal, $TxN:literal)),*],
+        rx: [$(($RxP:literal, $RxN:literal)),*],
+    })+) => {
+        $(
+            impl crate::Sealed for $CANX {}
+
+            impl Instance for $CANX {
+                type Rec = rec::$Rec;
+
+                #[inline(always)]
+                fn base() -> usize {
+                    $CANX::ptr() as usize
+                }
+            }
+
+            $(
+                impl PinTx<$CANX> for Pin<$TxP, $TxN, Alternate<PushPull>> {}
+            )*
+            $(
+                impl<MODE> PinRx<$CANX> for Pin<$RxP, $RxN, Input<MODE>> {}
+            )*
+        )+
+    };
+}
+
+// Default pins first, then the remapped ones, see `afio::Can*Remap`
+can! {
+    CAN1: Can1 => {
+        tx: [('A', 12), ('B', 9), ('D', 1)],
+        rx: [('A', 11), ('B', 8), ('D', 0)],
+    }
+    CAN2: Can2 => {
+        tx: [('B', 13), ('B', 6)],
+        rx: [('B', 12), ('B', 5)],
+    }
+}
+
+#[inline(always)]
+fn regs<T: Instance>() -> &'static CAN {
+    unsafe { &*(T::base() as *const CAN) }
+}
+
+/// The filter banks of both controllers
+#[inline(always)]
+fn filters() -> &'static CAN {
+    regs::<CAN1>()
+}
+
+/// Waits up to `limit` polls for MSR.INAK to be `set`
+fn wait_inak(rb: &CAN, set: bool, limit: u32) -> Result<(), Error> {
+    for _ in 0..limit {
+        if (rb.MSR.get() & INAK != 0) == set {
+            return Ok(());
+        }
+    }
+    Err(Error::Timeout)
+}
+
+/// Splits `pclk / bitrate` time quanta into the BTR prescaler and segments,
+/// as `(BRP, TS1, TS2)` register values
+fn bit_timing(pclk: u32, bitrate: u32) -> Option<(u32, u32, u32)> {
+    // 1 + TS1 + TS2 quanta per bit, longest first for the finest sampling
+    (8..=25).rev().find_map(|tq: u32| {
+        let per_quantum = bitrate.checked_mul(tq)?;
+        if pclk % per_quantum != 0 {
+            return None;
+        }
+        let brp = pclk / per_quantum;
+        let ts2 = ((tq + 4) / 8).clamp(1, 8);
+        let ts1 = tq - 1 - ts2;
+        ((1..=1024).contains(&brp) && ts1 <= 16).then(|| (brp - 1, ts1 - 1, ts2 - 1))
+    })
+}
+
+/// CAN controller
+pub struct Can<CAN, PINS> {
+    can: CAN,
+    pins: PINS,
+    // Polls of MSR.INAK before giving up, about 128 bit times
+    inak_limit: u32,
+}
+
+impl<CAN: Instance, PINS: Pins<CAN>> Can<CAN, PINS> {
+    /// Configures the controller for `bitrate` and joins the bus.
+    ///
+    /// Returns [`Error::Timeout`] if the controller does not see 11
+    /// recessive bits within about 128 bit times.
+    ///
+    /// # Panics
+    ///
+    /// Panics if PCLK1 is not a multiple of 8 to 25 times `bitrate`, with
+    /// a prescaler up to 1024.
+    pub fn new(
+        can: CAN,
+        pins: PINS,
+        bitrate: Hertz,
+        prec: CAN::Rec,
+        clocks: &CoreClocks,
+    ) -> Result<Self, Error> {
+        let (brp, ts1, ts2) =
+            bit_timing(clocks.pclk1().raw(), bitrate.raw()).expect("CAN bitrate not satisfied");
+
+        prec.enable().reset();
+        if CAN::base() != CAN1::ptr() as usize {
+            // The filter banks of CAN2 live in CAN1. Only the clock is
+            // enabled, CAN1 is never reset from here.
+            // NOTE(unsafe) atomic read-modify-write of APB1PCENR
+            riscv::interrupt::free(|_| unsafe {
+                (*RCC::ptr()).apb1pcenr.modify(|_, w| w.can1en().set_bit())
+            });
+        }
+        let rb = regs::<CAN>();
+        // Each poll takes at least a core cycle
+        let limit = 128 * (clocks.hclk().raw() / bitrate.raw());
+
+        rb.MCR.set(INRQ | TXFP | ABOM);
+        wait_inak(rb, true, limit)?;
+        rb.BTR.set(ts2 << 20 | ts1 << 16 | brp);
+
+        rb.MCR.set(rb.MCR.get() & !(INRQ | SLEEP));
+        // Waits for 11 recessive bits on the bus
+        wait_inak(rb, false, limit)?;
+
+        Ok(Can {
+            can,
+            pins,
+            inak_limit: limit,
+        })
+    }
+
+    /// Passes frames matching `filter` through filter bank `bank` into
+    /// FIFO 0. Banks 0 to 13 filter CAN1, 14 to 27 CAN2.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bank` is not below 28.
+    pub fn set_filter(&mut self, bank: usize, filter: Mask32) {
+        assert!(bank < 28);
+        let rb = filters();
+        let bit = 1 << bank;
+
+        // NOTE the filter registers are shared by CAN1 and CAN2
+        riscv::interrupt::free(|_| {
+            rb.FMR.set(rb.FMR.get() | FINIT);
+            rb.FWR.set(rb.FWR.get() & !bit);
+            // 32-bit scale, mask mode, FIFO 0
+            rb.FS1R.set(rb.FS1R.get() | bit);
+            rb.FM1R.set(rb.FM1R.get() & !bit);
+            rb.FFA1R.set(rb.FFA1R.get() & !bit);
+            rb.FR[bank][0].set(filter.id);
+            rb.FR[bank][1].set(filter.mask);
+            rb.FWR.set(rb.FWR.get() | bit);
+            rb.FMR.set(rb.FMR.get() & !FINIT);
+        });
+    }
+
+    /// Stops passing frames through filter bank `bank`
+    pub fn disable_filter(&mut self, bank: usize) {
+        assert!(bank < 28);
+        let rb = filters();
+        riscv::interrupt::free(|_| {
+            rb.FWR.set(rb.FWR.get() & !(1 << bank));
+        });
+    }
+
+    /// Selects the order in which pending frames are sent.
+    ///
+    /// The controller briefly leaves the bus to change it, wait for the
+    /// pending frames to go out first. Returns [`Error::Timeout`] if it
+    /// cannot rejoin the bus, as in [`new`](Can::new).
+    pub fn set_tx_priority(&mut self, priority: TxPriority) -> Result<(), Error> {
+        self.modify_mcr(TXFP, priority == TxPriority::Chronological)
+    }
+
+    /// Enables or disables time triggered communication mode (TTCM), in
+    /// which received frames get a [`timestamp`](Frame::timestamp).
+    ///
+    /// The controller briefly leaves the bus to change it, like
+    /// [`set_tx_priority`](Can::set_tx_priority).
+    pub fn set_time_triggered(&mut self, enable: bool) -> Result<(), Error> {
+        self.modify_mcr(TTCM, enable)
+    }
+
+    /// Sets or clears `mask` in MCR, in initialization mode
+    fn modify_mcr(&mut self, mask: u32, set: bool) -> Result<(), Error> {
+        let rb = regs::<CAN>();
+        rb.MCR.set(rb.MCR.get() | INRQ);
+        wait_inak(rb, true, self.inak_limit)?;
+        if set {
+            rb.MCR.set(rb.MCR.get() | mask);
+        } else {
+            rb.MCR.set(rb.MCR.get() & !mask);
+        }
+        rb.MCR.set(rb.MCR.get() & !INRQ);
+        // Waits for 11 recessive bits on the bus
+        wait_inak(rb, false, self.inak_limit)
+    }
+
+    /// Leaves the bus and releases the peripheral and the pins
+    pub fn release(self) -> (CAN, PINS) {
+        let rb = regs::<CAN>();
+        rb.MCR.set(rb.MCR.get() | INRQ);
+        (self.can, self.pins)
+    }
+}
+
+impl<CAN: Instance, PINS> Can<CAN, PINS> {
+    /// Queues `frame` in a free mailbox and returns it.
+    ///
+    /// If all mailboxes are pending, the pending frame with the lowest
+    /// priority is aborted and replaced when `frame` has a higher priority,
+    /// it is then returned along with the mailbox. Otherwise this returns
+    /// `WouldBlock`.
+    pub fn transmit(&mut self, frame: &Frame) -> nb::Result<(Mailbox, Option<Frame>), Error> {
+        match self.transmit_free(frame) {
+            Err(nb::Error::WouldBlock) => {}
+            result => return result.map(|mailbox| (mailbox, None)),
+        }
+
+        let rb = regs::<CAN>();
+        // Lowest priority pending frame
+        let (mailbox, lowest) = Mailbox::ALL
+            .into_iter()
+            .map(|mb| (mb, rb.TX[mb.index()].IR.get() & !TXRQ))
+            .max_by_key(|(_, ir)| *ir)
+            .unwrap();
+        if frame.ir() >= lowest {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let replaced = Frame::read(&rb.TX[mailbox.index()], false);
+        let aborted = self.abort(mailbox);
+        frame.write(&rb.TX[mailbox.index()]);
+        Ok((mailbox, aborted.then_some(replaced)))
+    }
+
+    /// Queues `frame` in a free mailbox, never replaces a pending frame
+    fn transmit_free(&mut self, frame: &Frame) -> nb::Result<Mailbox, Error> {
+        let rb = regs::<CAN>();
+        if rb.ESR.get() & BOFF != 0 {
+            return Err(nb::Error::Other(Error::BusOff));
+        }
+
+        let mailbox = Mailbox::ALL
+            .into_iter()
+            .find(|mb| !self.is_pending(*mb))
+            .ok_or(nb::Error::WouldBlock)?;
+        frame.write(&rb.TX[mailbox.index()]);
+        Ok(mailbox)
+    }
+
+    /// Whether the frame in `mailbox` is still waiting to be sent
+    pub fn is_pending(&self, mailbox: Mailbox) -> bool {
+        regs::<CAN>().TSR.get() & (1 << (TME_OFFSET + mailbox as u32)) == 0
+    }
+
+    /// Cancels the frame pending in `mailbox`, and returns whether it was
+    /// cancelled. Returns `false` if the mailbox was empty or the frame was
+    /// sent before it could be cancelled.
+    pub fn abort(&mut self, mailbox: Mailbox) -> bool {
+        if !self.is_pending(mailbox) {
+            return false;
+        }
+        let rb = regs::<CAN>();
+        let shift = 8 * mailbox as u32;
+        rb.TSR.set(ABRQ << shift);
+        // A frame on the bus is only aborted once it lost arbitration or failed
+        while self.is_pending(mailbox) {}
+        rb.TSR.get() & (TXOK << shift) == 0
+    }
+}
+
+impl<CAN: Instance, PINS> embedded_can::nb::Can for Can<CAN, PINS> {
+    type Frame = Frame;
+    type Error = Error;
+
+    /// See [`Can::transmit`]
+    fn transmit(&mut self, frame: &Frame) -> nb::Result<Option<Frame>, Error> {
+        Can::transmit(self, frame).map(|(_, replaced)| replaced)
+    }
+
+    fn receive(&mut self) -> nb::Result<Frame, Error> {
+        let rb = regs::<CAN>();
+        let rfr = rb.RFR[0].get();
+
+        if rfr & FOVR != 0 {
+            // Flags are cleared by writing 1
+            rb.RFR[0].set(FOVR);
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+        if rfr & FMP_MASK == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let frame = Frame::read(&rb.RX[0], rb.MCR.get() & TTCM != 0);
+
+        // Releases the FIFO output mailbox
+        rb.RFR[0].set(RFOM);
+        Ok(frame)
+    }
+}
+
+impl<CAN: Instance, PINS> embedded_can::blocking::Can for Can<CAN, PINS> {
+    type Frame = Frame;
+    type Error = Error;
+
+    /// Waits for a free mailbox, never replaces a pending frame
+    fn transmit(&mut self, frame: &Frame) -> Result<(), Error> {
+        nb::block!(self.transmit_free(frame)).map(|_| ())
+    }
+
+    fn receive(&mut self) -> Result<Frame, Error> {
+        nb::block!(embedded_can::nb::Can::receive(self))
+    }
+}
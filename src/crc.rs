@@ -0,0 +1,70 @@
+//! CRC calculation unit
+//!
+//! Computes the CRC-32 used by Ethernet (polynomial 0x04C11DB7), one
+//! 32-bit word at a time, most significant bit first and without a final
+//! XOR. The register starts from `0xFFFF_FFFF` after [`reset`](Crc::reset).
+//!
+//! ```
+//! let mut crc = Crc::new(dp.CRC, ccdr.peripheral.CRC);
+//!
+//! crc.reset();
+//! crc.feed(&image);
+//! let checksum = crc.result();
+//! ```
+
+use crate::pac::CRC;
+use crate::rcc::rec::{self, ResetEnable};
+
+// CTLR bits
+const RESET: u32 = 1 << 0;
+
+/// CRC calculation unit
+pub struct Crc {
+    crc: CRC,
+}
+
+impl Crc {
+    /// Enables the CRC clock and resets the CRC register
+    pub fn new(crc: CRC, prec: rec::Crc) -> Self {
+        prec.enable();
+        let mut crc = Crc { crc };
+        crc.reset();
+        crc
+    }
+
+    /// Resets the CRC register to `0xFFFF_FFFF`, the independent data
+    /// register is left as is
+    pub fn reset(&mut self) {
+        self.crc.ctlr.write(|w| unsafe { w.bits(RESET) });
+    }
+
+    /// Adds `data` to the CRC
+    pub fn feed(&mut self, data: &[u32]) {
+        for word in data {
+            self.crc.datar.write(|w| unsafe { w.bits(*word) });
+        }
+    }
+
+    /// Returns the CRC of the words fed since the last reset
+    pub fn result(&self) -> u32 {
+        self.crc.datar.read().bits()
+    }
+
+    /// Stores a byte in the independent data register, unaffected by
+    /// [`reset`](Crc::reset)
+    pub fn set_idr(&mut self, value: u8) {
+        self.crc
+            .idatar
+            .write(|w| unsafe { w.bits(u32::from(value)) });
+    }
+
+    /// Returns the byte in the independent data register
+    pub fn idr(&self) -> u8 {
+        self.crc.idatar.read().bits() as u8
+    }
+
+    /// Releases the CRC unit
+    pub fn release(self) -> CRC {
+        self.crc
+    }
+}
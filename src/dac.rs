@@ -0,0 +1,251 @@
+//! Digital to Analog Converter (DAC)
+//!
+//! Two 12-bit channels, on PA4 and PA5, referenced to VREF+.
+//!
+//! ```
+//! let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
+//! let (mut ch1, mut ch2) = dac(
+//!     dp.DAC,
+//!     (gpioa.pa4.into_analog(), gpioa.pa5.into_analog()),
+//!     ccdr.peripheral.DAC,
+//! );
+//!
+//! ch1.enable();
+//! ch1.set_value(2048); // VREF+ / 2
+//!
+//! // Disables the channels and gives the DAC and the pins back
+//! let (dac, (pa4, pa5)) = dac::release((ch1, ch2));
+//! ```
+//!
+//! Without a trigger a value is output one APB1 cycle after it is written.
+//! With [`enable_trigger`](C1::enable_trigger) it is held until the trigger,
+//! e.g. the update of a timer started with
+//! [`Timer::trigger_on_update`](crate::timer::Timer::trigger_on_update),
+//! which paces a waveform.
+
+use vcell::VolatileCell;
+
+use crate::gpio::{Analog, Pin};
+use crate::pac;
+use crate::rcc::rec::{self, ResetEnable};
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+struct DAC {
+    CTLR: VolatileCell<u32>,
+    SWTR: VolatileCell<u32>,
+    /// Right aligned 12-bit, left aligned 12-bit, right aligned 8-bit data,
+    /// per channel
+    DHR: [[VolatileCell<u32>; 3]; 2],
+    DUAL_DHR: [VolatileCell<u32>; 3],
+    DOR: [VolatileCell<u32>; 2],
+}
+
+#[inline(always)]
+fn regs() -> &'static DAC {
+    unsafe { &*(pac::DAC::ptr() as *const DAC) }
+}
+
+// CTLR bits, per channel
+const EN: u32 = 1 << 0;
+const BOFF: u32 = 1 << 1;
+const TEN: u32 = 1 << 2;
+const TSEL_OFFSET: u32 = 3;
+const TSEL_MASK: u32 = 0b111 << TSEL_OFFSET;
+
+/// Conversion trigger
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Trigger {
+    /// TIM6 TRGO
+    Tim6 = 0b000,
+    /// TIM8 TRGO
+    Tim8 = 0b001,
+    /// TIM7 TRGO
+    Tim7 = 0b010,
+    /// TIM5 TRGO
+    Tim5 = 0b011,
+    /// TIM2 TRGO
+    Tim2 = 0b100,
+    /// TIM4 TRGO
+    Tim4 = 0b101,
+    /// EXTI line 9
+    Exti9 = 0b110,
+    /// [`trigger`](C1::trigger) in software
+    Software = 0b111,
+}
+
+/// DAC channel 1, on PA4
+pub struct C1 {
+    pin: Pin<'A', 4, Analog>,
+}
+
+/// DAC channel 2, on PA5
+pub struct C2 {
+    pin: Pin<'A', 5, Analog>,
+}
+
+/// A pin or a pair of pins of the DAC channels
+pub trait Pins<DAC>: Sized {
+    /// The channels driving the pins
+    type Output: Channels<Pins = Self>;
+
+    #[doc(hidden)]
+    fn init(self) -> Self::Output;
+}
+
+/// A channel or a pair of channels returned by [`dac`]
+pub trait Channels {
+    /// The pins driven by the channels
+    type Pins;
+
+    #[doc(hidden)]
+    fn into_pins(self) -> Self::Pins;
+}
+
+impl Pins<pac::DAC> for Pin<'A', 4, Analog> {
+    type Output = C1;
+
+    fn init(self) -> C1 {
+        C1 { pin: self }
+    }
+}
+
+impl Pins<pac::DAC> for Pin<'A', 5, Analog> {
+    type Output = C2;
+
+    fn init(self) -> C2 {
+        C2 { pin: self }
+    }
+}
+
+impl Pins<pac::DAC> for (Pin<'A', 4, Analog>, Pin<'A', 5, Analog>) {
+    type Output = (C1, C2);
+
+    fn init(self) -> (C1, C2) {
+        (C1 { pin: self.0 }, C2 { pin: self.1 })
+    }
+}
+
+impl Channels for C1 {
+    type Pins = Pin<'A', 4, Analog>;
+
+    fn into_pins(self) -> Self::Pins {
+        self.pin
+    }
+}
+
+impl Channels for C2 {
+    type Pins = Pin<'A', 5, Analog>;
+
+    fn into_pins(self) -> Self::Pins {
+        self.pin
+    }
+}
+
+impl Channels for (C1, C2) {
+    type Pins = (Pin<'A', 4, Analog>, Pin<'A', 5, Analog>);
+
+    fn into_pins(self) -> Self::Pins {
+        (self.0.pin, self.1.pin)
+    }
+}
+
+/// Enables the DAC clock and returns the channels of `pins`, disabled.
+///
+/// The output buffer is on, which lets the pins drive a load but keeps
+/// them ~0.2 V off the rails.
+pub fn dac<PINS: Pins<pac::DAC>>(_dac: pac::DAC, pins: PINS, prec: rec::Dac) -> PINS::Output {
+    prec.enable().reset();
+    pins.init()
+}
+
+/// Disables `channels`, all the channels [`dac`] returned, and releases the
+/// DAC and the pins
+pub fn release<CH: Channels>(channels: CH) -> (pac::DAC, CH::Pins) {
+    regs().CTLR.set(0);
+    // NOTE(unsafe) the DAC was moved into `dac`, and `channels` were all
+    // that was left of it
+    let dac = unsafe { pac::Peripherals::steal().DAC };
+    (dac, channels.into_pins())
+}
+
+macro_rules! dac_channel {
+    ($($CX:ident: $n:literal,)+) => {
+        $(
+            impl $CX {
+                fn modify_ctlr(&mut self, f: impl FnOnce(u32) -> u32) {
+                    // CTLR is shared with the other channel
+                    riscv::interrupt::free(|_| {
+                        let ctlr = &regs().CTLR;
+                        let offset = 16 * $n;
+                        let bits = ctlr.get();
+                        let channel = f(bits >> offset & 0xFFFF);
+                        ctlr.set(bits & !(0xFFFF << offset) | channel << offset);
+                    });
+                }
+
+                /// Starts driving the pin
+                pub fn enable(&mut self) {
+                    self.modify_ctlr(|ctlr| ctlr | EN);
+                }
+
+                /// Stops driving the pin
+                pub fn disable(&mut self) {
+                    self.modify_ctlr(|ctlr| ctlr & !EN);
+                }
+
+                /// Turns the output buffer off, for a high impedance load
+                /// that needs the full range
+                pub fn disable_buffer(&mut self) {
+                    self.modify_ctlr(|ctlr| ctlr | BOFF);
+                }
+
+                /// Holds written values until `trigger`
+                pub fn enable_trigger(&mut self, trigger: Trigger) {
+                    self.modify_ctlr(|ctlr| {
+                        ctlr & !TSEL_MASK | (trigger as u32) << TSEL_OFFSET | TEN
+                    });
+                }
+
+                /// Outputs written values right away again
+                pub fn disable_trigger(&mut self) {
+                    self.modify_ctlr(|ctlr| ctlr & !TEN);
+                }
+
+                /// Outputs the written value, with [`Trigger::Software`]
+                pub fn trigger(&mut self) {
+                    // Cleared by hardware, writing 0 to the other bit is
+                    // harmless
+                    regs().SWTR.set(1 << $n);
+                }
+
+                /// Writes a 12-bit value, right aligned
+                pub fn set_value(&mut self, value: u16) {
+                    regs().DHR[$n][0].set(u32::from(value));
+                }
+
+                /// Writes a 12-bit value, left aligned: bits 15:4
+                pub fn set_value_left(&mut self, value: u16) {
+                    regs().DHR[$n][1].set(u32::from(value));
+                }
+
+                /// Writes an 8-bit value, the top bits of the 12-bit output
+                pub fn set_value_8bit(&mut self, value: u8) {
+                    regs().DHR[$n][2].set(u32::from(value));
+                }
+
+                /// Returns the 12-bit value currently output
+                pub fn get_value(&self) -> u16 {
+                    regs().DOR[$n].get() as u16
+                }
+            }
+        )+
+    };
+}
+
+dac_channel! {
+    C1: 0,
+    C2: 1,
+}
@@ -5,10 +5,36 @@
 //! for time base after calibration.
 //!
 //! **NOTE**: CH32V0x series has no mcycle register.
+//!
+//! [`Delay`] reprograms SysTick on every call, so it must not be used from an
+//! interrupt handler that may preempt another SysTick delay, nor while SysTick
+//! serves as a system tick or as the [`MonoTimer`] timebase. [`CycleDelay`]
+//! only reads the free-running `mcycle` counter and is safe to use anywhere,
+//! [`CycleCounter`] reads it to time code. [`delay_cycles`] waits for a
+//! number of core cycles rather than a duration, for sub-microsecond timing.
+//!
+//! [`MonoTimer`] lets SysTick count up freely as a monotonic clock, to
+//! timestamp events and build timeouts. Use `CycleDelay` for blocking delays
+//! while it runs:
+//!
+//! ```
+//! let mono = MonoTimer::new(StkSrc::HclkDiv8, &ccdr.clocks);
+//! let mut delay = CycleDelay::new(ccdr.clocks.hclk());
+//!
+//! let start = mono.now();
+//! delay.delay_ms(5u32);
+//! let ticks = start.elapsed();
+//! ```
+
+use core::convert::Infallible;
+use core::ptr;
 
 use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::timer::CountDown;
+use riscv::register::mcycle;
 
-use crate::time::Hertz;
+use crate::rcc::CoreClocks;
+use crate::time::{Hertz, Period};
 
 #[allow(non_snake_case)]
 #[repr(C)]
@@ -21,6 +47,21 @@ pub struct SYSTICK {
 
 pub const SYSTICK_BASE_ADDR: u32 = 0xE000F000;
 
+// CTLR bits
+const STE: u32 = 1 << 0;
+// set for HCLK, clear for HCLK/8
+const STCLK: u32 = 1 << 2;
+
+/// SysTick time base
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StkSrc {
+    /// HCLK
+    Hclk,
+    /// HCLK / 8, the reset default
+    HclkDiv8,
+}
+
 /// System timer (SysTick) as a delay provider.
 pub struct Delay {
     frequency: u32,
@@ -37,32 +78,178 @@ impl Delay {
         }
     }
 
-    /// Delay using the Cortex-M systick for a certain duration, in µs.
-    #[allow(clippy::missing_inline_in_public_items)]
-    pub fn delay_us(&mut self, us: u32) {
-        let mut systick = unsafe { &mut *(SYSTICK_BASE_ADDR as *mut SYSTICK) };
+    /// Configures SysTick as a delay provider, at the frequency of the time
+    /// base it is currently set to.
+    #[inline]
+    pub fn with_clocks(clocks: &CoreClocks) -> Self {
+        // NOTE(unsafe) atomic read with no side effects
+        let ctlr = unsafe { core::ptr::read_volatile(SYSTICK_BASE_ADDR as *const u32) };
+        let source = if ctlr & STCLK != 0 {
+            StkSrc::Hclk
+        } else {
+            StkSrc::HclkDiv8
+        };
+        Self::new(Self::frequency(source, clocks))
+    }
 
-        systick.SR &= !(1 << 0);
-        let i = (us as u64) * (self.frequency as u64) / 1_000_000;
-        systick.CMP = i;
-        systick.CTLR |= 0b110001;
+    /// Switches SysTick to the time base `source` and configures it as a
+    /// delay provider.
+    #[inline]
+    pub fn with_source(source: StkSrc, clocks: &CoreClocks) -> Self {
+        let systick = unsafe { &mut *(SYSTICK_BASE_ADDR as *mut SYSTICK) };
+        match source {
+            StkSrc::Hclk => systick.CTLR |= STCLK,
+            StkSrc::HclkDiv8 => systick.CTLR &= !STCLK,
+        }
+        Self::new(Self::frequency(source, clocks))
+    }
+
+    fn frequency(source: StkSrc, clocks: &CoreClocks) -> Hertz {
+        match source {
+            StkSrc::Hclk => clocks.hclk(),
+            StkSrc::HclkDiv8 => clocks.hclk() / 8,
+        }
+    }
+
+    /// Delay using the Cortex-M systick for a certain duration, in ns.
+    ///
+    /// A zero delay returns right away without touching SysTick; any other
+    /// delay lasts at least one SysTick period.
+    #[inline]
+    pub fn delay_ns(&mut self, ns: u32) {
+        self.delay_ticks(to_ticks(ns, self.frequency, 1_000_000_000));
+    }
 
-        while systick.SR & 0b1 != 1 {}
-        systick.CTLR &= !(1 << 0);
+    /// Delay using the Cortex-M systick for a certain duration, in µs.
+    ///
+    /// A zero delay returns right away without touching SysTick; any other
+    /// delay lasts at least one SysTick period.
+    #[inline]
+    pub fn delay_us(&mut self, us: u32) {
+        self.delay_ticks(to_ticks(us, self.frequency, 1_000_000));
     }
 
     /// Delay using the Cortex-M systick for a certain duration, in ms.
+    ///
+    /// A zero delay returns right away without touching SysTick.
     #[inline]
     pub fn delay_ms(&mut self, ms: u32) {
-        let mut systick = unsafe { &mut *(SYSTICK_BASE_ADDR as *mut SYSTICK) };
+        self.delay_ticks(to_ticks(ms, self.frequency, 1_000));
+    }
+
+    /// Starts a delay of `timeout` and returns right away, poll
+    /// [`wait`](Delay::wait) for its end.
+    ///
+    /// `block!(delay.wait())` then behaves like a blocking delay, while a
+    /// cooperative scheduler can run other tasks until `wait` succeeds.
+    pub fn start(&mut self, timeout: impl Into<Period>) {
+        let ticks = timeout
+            .into()
+            .cycles(Hertz::from_raw(self.frequency))
+            .max(1);
+        let systick = SYSTICK_BASE_ADDR as *mut SYSTICK;
+        // NOTE(unsafe) SysTick is only written by Delay and MonoTimer
+        unsafe {
+            let ctlr = ptr::addr_of_mut!((*systick).CTLR);
+            ctlr.write_volatile(ctlr.read_volatile() & !STE);
+            ptr::addr_of_mut!((*systick).SR).write_volatile(0);
+            // The counter is stopped, CMP can't match half written
+            ptr::addr_of_mut!((*systick).CMP).write_volatile(ticks);
+            ctlr.write_volatile(ctlr.read_volatile() | 0b110001);
+        }
+    }
+
+    /// Whether the delay from [`start`](Delay::start) has elapsed
+    pub fn wait(&mut self) -> nb::Result<(), Infallible> {
+        let systick = SYSTICK_BASE_ADDR as *mut SYSTICK;
+        // NOTE(unsafe) SysTick is only written by Delay and MonoTimer
+        unsafe {
+            if ptr::addr_of!((*systick).SR).read_volatile() & 0b1 == 0 {
+                return Err(nb::Error::WouldBlock);
+            }
+            let ctlr = ptr::addr_of_mut!((*systick).CTLR);
+            ctlr.write_volatile(ctlr.read_volatile() & !STE);
+        }
+        Ok(())
+    }
+
+    fn delay_ticks(&mut self, ticks: u64) {
+        let systick = systick();
+
+        for chunk in chunks(ticks) {
+            // NOTE(unsafe) SysTick is only written by Delay and MonoTimer
+            unsafe {
+                let sr = ptr::addr_of_mut!((*systick).SR);
+                let ctlr = ptr::addr_of_mut!((*systick).CTLR);
+                sr.write_volatile(sr.read_volatile() & !1);
+                ptr::addr_of_mut!((*systick).CMP).write_volatile(chunk);
+                ctlr.write_volatile(ctlr.read_volatile() | 0b110001);
+
+                while sr.read_volatile() & 0b1 != 1 {}
+                ctlr.write_volatile(ctlr.read_volatile() & !STE);
+            }
+        }
+    }
+}
+
+#[cfg(not(test))]
+#[inline(always)]
+fn systick() -> *mut SYSTICK {
+    SYSTICK_BASE_ADDR as *mut SYSTICK
+}
+
+#[cfg(test)]
+fn systick() -> *mut SYSTICK {
+    mock::SYSTICK.0.get()
+}
+
+/// In-memory SysTick registers backing [`systick`] in tests
+#[cfg(test)]
+mod mock {
+    use core::cell::UnsafeCell;
+
+    pub struct Systick(pub UnsafeCell<super::SYSTICK>);
+    unsafe impl Sync for Systick {}
+
+    pub static SYSTICK: Systick = Systick(UnsafeCell::new(super::SYSTICK {
+        CTLR: 0,
+        SR: 0,
+        CNT: 0,
+        CMP: 0,
+    }));
+}
+
+// Longest single SysTick wait, in ticks. Keeps the compare value within the
+// low word of CMP, which is written as two halves.
+const MAX_CHUNK: u64 = u32::MAX as u64;
 
-        systick.SR &= !(1 << 0);
-        let i = (ms as u64) * (self.frequency as u64) / 1_000;
-        systick.CMP = i as u64;
-        systick.CTLR |= 0b110001;
+/// Converts `amount` units of `1 / per_second` s into SysTick ticks,
+/// rounding up. Cannot overflow: `u32::MAX * u32::MAX + per_second` fits
+/// in a u64 for any `per_second` up to 1e9.
+fn to_ticks(amount: u32, frequency: u32, per_second: u64) -> u64 {
+    ((amount as u64) * (frequency as u64) + per_second - 1) / per_second
+}
+
+/// Splits `ticks` into waits of at most `MAX_CHUNK` ticks.
+fn chunks(ticks: u64) -> impl Iterator<Item = u64> {
+    let rest = ticks % MAX_CHUNK;
+    (0..ticks / MAX_CHUNK)
+        .map(|_| MAX_CHUNK)
+        .chain((rest != 0).then_some(rest))
+}
+
+impl CountDown for Delay {
+    type Time = Period;
 
-        while systick.SR & 0b1 != 1 {}
-        systick.CTLR &= !(1 << 0);
+    fn start<T>(&mut self, timeout: T)
+    where
+        T: Into<Period>,
+    {
+        Delay::start(self, timeout);
+    }
+
+    fn wait(&mut self) -> nb::Result<(), void::Void> {
+        Delay::wait(self).map_err(|e| e.map(|never| match never {}))
     }
 }
 
@@ -125,3 +312,361 @@ impl DelayUs<u8> for Delay {
         Delay::delay_us(self, u32::from(us))
     }
 }
+
+/// SysTick as a free-running monotonic timebase.
+///
+/// The 64-bit counter doesn't wrap in practice. Blocking [`Delay`]s stop and
+/// reprogram SysTick, don't use them while a `MonoTimer` runs.
+#[derive(Clone, Copy)]
+pub struct MonoTimer {
+    frequency: u32,
+}
+
+impl MonoTimer {
+    /// Starts SysTick counting up from 0 at the time base `source`.
+    pub fn new(source: StkSrc, clocks: &CoreClocks) -> Self {
+        let ctlr = SYSTICK_BASE_ADDR as *mut u32;
+        let stclk = match source {
+            StkSrc::Hclk => STCLK,
+            StkSrc::HclkDiv8 => 0,
+        };
+        // NOTE(unsafe) SysTick is only written by Delay and MonoTimer
+        unsafe {
+            // Stop, then clear the counter while the up-counting mode,
+            // auto-reload and interrupt bits are cleared
+            ctlr.write_volatile(0);
+            (SYSTICK_BASE_ADDR as *mut u64).add(1).write_volatile(0);
+            ctlr.write_volatile(stclk | STE);
+        }
+        MonoTimer {
+            frequency: Delay::frequency(source, clocks).raw(),
+        }
+    }
+
+    /// Frequency of the ticks
+    #[inline]
+    pub fn frequency(&self) -> Hertz {
+        Hertz::from_raw(self.frequency)
+    }
+
+    /// The current time
+    #[inline]
+    pub fn now(&self) -> Instant {
+        Instant { ticks: read_cnt() }
+    }
+
+    /// Converts a number of ticks to µs.
+    #[inline]
+    pub fn ticks_to_us(&self, ticks: u64) -> u64 {
+        ((ticks as u128) * 1_000_000 / (self.frequency as u128)) as u64
+    }
+}
+
+/// Yields to the executor until the delay has elapsed.
+///
+/// There is no interrupt behind it: the future wakes itself on every poll,
+/// so the executor keeps polling instead of letting the core sleep.
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for MonoTimer {
+    async fn delay_ns(&mut self, ns: u32) {
+        let start = self.now();
+        let ticks = to_ticks(ns, self.frequency, 1_000_000_000);
+        core::future::poll_fn(|cx| {
+            if start.elapsed() >= ticks {
+                core::task::Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                core::task::Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+/// A point in time of a [`MonoTimer`]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Instant {
+    ticks: u64,
+}
+
+impl Instant {
+    /// Ticks elapsed since this instant
+    #[inline]
+    pub fn elapsed(self) -> u64 {
+        read_cnt().wrapping_sub(self.ticks)
+    }
+
+    /// Ticks from `earlier` to this instant
+    #[inline]
+    pub fn duration_since(self, earlier: Instant) -> u64 {
+        self.ticks.wrapping_sub(earlier.ticks)
+    }
+
+    /// Raw counter value
+    #[inline]
+    pub fn ticks(self) -> u64 {
+        self.ticks
+    }
+}
+
+/// Reads the 64-bit counter, which is only accessible as two halves
+fn read_cnt() -> u64 {
+    let cnt = (SYSTICK_BASE_ADDR + 8) as *const u32;
+    loop {
+        // NOTE(unsafe) atomic reads with no side effects
+        let (hi, lo, hi2) = unsafe {
+            (
+                cnt.add(1).read_volatile(),
+                cnt.read_volatile(),
+                cnt.add(1).read_volatile(),
+            )
+        };
+        // Retry if the low half rolled over in between
+        if hi == hi2 {
+            return (u64::from(hi) << 32) | u64::from(lo);
+        }
+    }
+}
+
+/// Spins for at least `cycles` core cycles, whatever the clock frequency.
+///
+/// Only the low word of `mcycle` is read, which keeps the polling loop to a
+/// few instructions, so short waits overshoot by a handful of cycles at
+/// most. Interrupts are not masked and stretch the wait if they fire.
+///
+/// ```
+/// // 5 cycles of setup time before the clock edge
+/// data.set_high();
+/// delay::delay_cycles(5);
+/// clk.set_high();
+/// ```
+#[inline(always)]
+pub fn delay_cycles(cycles: u32) {
+    let start = mcycle::read() as u32;
+    while (mcycle::read() as u32).wrapping_sub(start) < cycles {}
+}
+
+/// Core cycle counter (`mcycle`) as a delay provider.
+///
+/// Never writes any shared state, so any number of instances may be used at
+/// once, including from interrupt handlers.
+#[derive(Clone, Copy)]
+pub struct CycleDelay {
+    frequency: u32,
+}
+
+impl CycleDelay {
+    /// Configures the cycle counter as a delay provider.
+    ///
+    /// `frequency` is the core clock, HCLK, which `mcycle` counts.
+    #[inline]
+    pub fn new(frequency: Hertz) -> Self {
+        CycleDelay {
+            frequency: frequency.raw(),
+        }
+    }
+
+    /// Delay for a certain number of core cycles.
+    ///
+    /// See [`delay_cycles`] for short waits.
+    #[inline]
+    pub fn delay_cycles(&self, cycles: u64) {
+        let start = mcycle::read64();
+        while mcycle::read64().wrapping_sub(start) < cycles {}
+    }
+
+    /// Delay using the cycle counter for a certain duration, in ns.
+    ///
+    /// Rounds up to a whole core cycle, the call overhead adds a few more.
+    #[inline]
+    pub fn delay_ns(&self, ns: u32) {
+        self.delay_cycles(((ns as u64) * (self.frequency as u64) + 999_999_999) / 1_000_000_000);
+    }
+
+    /// Delay using the cycle counter for a certain duration, in µs.
+    #[inline]
+    pub fn delay_us(&self, us: u32) {
+        self.delay_cycles((us as u64) * (self.frequency as u64) / 1_000_000);
+    }
+
+    /// Delay using the cycle counter for a certain duration, in ms.
+    #[inline]
+    pub fn delay_ms(&self, ms: u32) {
+        self.delay_cycles((ms as u64) * (self.frequency as u64) / 1_000);
+    }
+}
+
+impl DelayMs<u32> for CycleDelay {
+    #[inline]
+    fn delay_ms(&mut self, ms: u32) {
+        CycleDelay::delay_ms(self, ms);
+    }
+}
+
+// This is a workaround to allow `delay_ms(42)` construction without specifying a type.
+impl DelayMs<i32> for CycleDelay {
+    #[inline(always)]
+    fn delay_ms(&mut self, ms: i32) {
+        assert!(ms >= 0);
+        CycleDelay::delay_ms(self, ms as u32);
+    }
+}
+
+impl DelayMs<u16> for CycleDelay {
+    #[inline(always)]
+    fn delay_ms(&mut self, ms: u16) {
+        CycleDelay::delay_ms(self, u32::from(ms));
+    }
+}
+
+impl DelayMs<u8> for CycleDelay {
+    #[inline(always)]
+    fn delay_ms(&mut self, ms: u8) {
+        CycleDelay::delay_ms(self, u32::from(ms));
+    }
+}
+
+impl DelayUs<u32> for CycleDelay {
+    #[inline]
+    fn delay_us(&mut self, us: u32) {
+        CycleDelay::delay_us(self, us);
+    }
+}
+
+// This is a workaround to allow `delay_us(42)` construction without specifying a type.
+impl DelayUs<i32> for CycleDelay {
+    #[inline(always)]
+    fn delay_us(&mut self, us: i32) {
+        assert!(us >= 0);
+        CycleDelay::delay_us(self, us as u32);
+    }
+}
+
+impl DelayUs<u16> for CycleDelay {
+    #[inline(always)]
+    fn delay_us(&mut self, us: u16) {
+        CycleDelay::delay_us(self, u32::from(us))
+    }
+}
+
+impl DelayUs<u8> for CycleDelay {
+    #[inline(always)]
+    fn delay_us(&mut self, us: u8) {
+        CycleDelay::delay_us(self, u32::from(us))
+    }
+}
+
+/// Core cycle counter (`mcycle`) as a timestamp source, for profiling.
+///
+/// ```
+/// let counter = CycleCounter::new(ccdr.clocks.hclk());
+///
+/// let start = counter.now();
+/// hot_loop();
+/// let ns = counter.cycles_to_ns(counter.elapsed(start));
+/// ```
+///
+/// The counter is 64 bits wide and doesn't wrap in practice.
+#[derive(Clone, Copy)]
+pub struct CycleCounter {
+    frequency: u32,
+}
+
+impl CycleCounter {
+    /// `frequency` is the core clock, HCLK.
+    #[inline]
+    pub fn new(frequency: Hertz) -> Self {
+        CycleCounter {
+            frequency: frequency.raw(),
+        }
+    }
+
+    /// Number of core cycles since reset.
+    #[inline]
+    pub fn now(&self) -> u64 {
+        mcycle::read64()
+    }
+
+    /// Number of core cycles since the timestamp `since`.
+    #[inline]
+    pub fn elapsed(&self, since: u64) -> u64 {
+        self.now().wrapping_sub(since)
+    }
+
+    /// Converts a number of core cycles to ns.
+    #[inline]
+    pub fn cycles_to_ns(&self, cycles: u64) -> u64 {
+        ((cycles as u128) * 1_000_000_000 / (self.frequency as u128)) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_ticks_rounds_up() {
+        assert_eq!(to_ticks(0, 18_000_000, 1_000_000), 0);
+        assert_eq!(to_ticks(1, 18_000_000, 1_000_000), 18);
+        assert_eq!(to_ticks(1, 18_000_000, 1_000_000_000), 1);
+        assert_eq!(to_ticks(100_000, 144_000_000, 1_000), 14_400_000_000);
+    }
+
+    #[test]
+    fn to_ticks_does_not_overflow() {
+        let max = u32::MAX as u64;
+        assert_eq!(to_ticks(u32::MAX, u32::MAX, 1), max * max);
+        assert_eq!(
+            to_ticks(u32::MAX, u32::MAX, 1_000_000_000),
+            (max * max).div_ceil(1_000_000_000)
+        );
+    }
+
+    #[test]
+    fn zero_delay_leaves_systick_alone() {
+        // Any other delay would spin forever, SR never sets in the mock
+        let systick = systick();
+        unsafe {
+            (*systick).CTLR = 0x5A;
+            (*systick).CMP = 0xDEAD_BEEF;
+        }
+
+        let mut delay = Delay::new(Hertz::from_raw(18_000_000));
+        delay.delay_ns(0);
+        delay.delay_us(0);
+        delay.delay_ms(0);
+
+        unsafe {
+            assert_eq!((*systick).CTLR, 0x5A);
+            assert_eq!((*systick).CMP, 0xDEAD_BEEF);
+        }
+    }
+
+    #[test]
+    fn chunks_cover_ticks() {
+        for ticks in [
+            0,
+            1,
+            MAX_CHUNK - 1,
+            MAX_CHUNK,
+            MAX_CHUNK + 1,
+            14_400_000_000,
+        ] {
+            assert_eq!(chunks(ticks).map(u128::from).sum::<u128>(), ticks as u128);
+            assert!(chunks(ticks).all(|chunk| chunk > 0 && chunk <= MAX_CHUNK));
+        }
+    }
+
+    #[test]
+    fn chunks_near_rollover() {
+        // u64::MAX = MAX_CHUNK * (MAX_CHUNK + 2)
+        let count = (MAX_CHUNK + 2) as usize;
+        assert_eq!(chunks(u64::MAX).size_hint(), (count, Some(count)));
+        assert_eq!(chunks(u64::MAX - 1).size_hint(), (count, Some(count)));
+        assert_eq!(
+            chunks(u64::MAX - MAX_CHUNK).size_hint(),
+            (count - 1, Some(count - 1))
+        );
+    }
+}
@@ -0,0 +1,515 @@
+//! Direct Memory Access (DMA)
+//!
+//! DMA1 and DMA2 have seven channels each. Each channel serves a fixed set
+//! of peripheral requests (e.g. DMA1 channel 5 is USART1 RX), see the
+//! request mapping table in the reference manual. The peripheral still has
+//! to be told to issue the requests, which is done by [`DmaPeripheral`] or
+//! by the drivers that accept a [`Channel`].
+//!
+//! ```
+//! static mut BUF: [u8; 5] = *b"hello";
+//!
+//! let dma1 = dp.DMA1.split(ccdr.peripheral.DMA1);
+//! let transfer = Transfer::write(dma1.ch4, tx, unsafe { &BUF });
+//! // ...
+//! let (buf, ch4, tx) = transfer.wait().unwrap();
+//! ```
+//!
+//! A transfer fails with [`DmaError::Transfer`] when the channel hits a bus
+//! error, usually an address outside of RAM, flash and the peripherals, and
+//! the channel stops right away. The addresses must also be aligned to the
+//! word size, [`Transfer`] checks that before starting.
+//!
+//! **NOTE**: The additional DMA2 channels 8-11 of the CH32V305/307 are not
+//! supported.
+
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use vcell::VolatileCell;
+
+use crate::pac::{DMA1, DMA2};
+use crate::rcc::rec::{self, ResetEnable};
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct CH {
+    CFGR: VolatileCell<u32>,
+    CNTR: VolatileCell<u32>,
+    PADDR: VolatileCell<u32>,
+    MADDR: VolatileCell<u32>,
+    _reserved: u32,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct DMA {
+    INTFR: VolatileCell<u32>,
+    INTFCR: VolatileCell<u32>,
+    CH: [CH; 7],
+}
+
+// CFGR bits
+const EN: u32 = 1 << 0;
+const TCIE: u32 = 1 << 1;
+const HTIE: u32 = 1 << 2;
+const TEIE: u32 = 1 << 3;
+const DIR: u32 = 1 << 4;
+const CIRC: u32 = 1 << 5;
+const PINC: u32 = 1 << 6;
+const MINC: u32 = 1 << 7;
+const PSIZE_OFFSET: u32 = 8;
+const MSIZE_OFFSET: u32 = 10;
+
+// INTFR/INTFCR bits, shifted by 4 * (N - 1)
+const GIF: u32 = 1 << 0;
+const TCIF: u32 = 1 << 1;
+const HTIF: u32 = 1 << 2;
+const TEIF: u32 = 1 << 3;
+
+/// Extension trait to split a DMA controller into independent channels
+pub trait DmaExt {
+    /// The channels to split the DMA controller into
+    type Channels;
+
+    /// The Reset and Enable control block for this DMA controller
+    type Rec: ResetEnable;
+
+    /// Enables the DMA clock, stops every channel and splits the controller
+    /// into its channels
+    fn split(self, prec: Self::Rec) -> Self::Channels;
+}
+
+/// DMA error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum DmaError {
+    /// A bus error occurred (TEIF), the channel stopped
+    Transfer,
+}
+
+/// Channel interrupt events
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The transfer completed (TCIF)
+    TransferComplete,
+    /// Half of the transfer completed (HTIF)
+    HalfTransfer,
+    /// A bus error stopped the channel (TEIF)
+    TransferError,
+}
+
+impl Event {
+    fn cfgr_bit(self) -> u32 {
+        match self {
+            Event::TransferComplete => TCIE,
+            Event::HalfTransfer => HTIE,
+            Event::TransferError => TEIE,
+        }
+    }
+}
+
+/// Transfer direction
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// From the peripheral address to the memory address
+    PeripheralToMemory,
+    /// From the memory address to the peripheral address
+    MemoryToPeripheral,
+}
+
+/// Size of a single transfer
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u32)]
+pub enum WordSize {
+    Bits8 = 0b00,
+    Bits16 = 0b01,
+    Bits32 = 0b10,
+}
+
+/// A DMA transfer word
+pub trait Word: Copy + crate::Sealed {
+    /// Matching transfer size
+    const SIZE: WordSize;
+}
+
+impl Word for u8 {
+    const SIZE: WordSize = WordSize::Bits8;
+}
+
+impl Word for u16 {
+    const SIZE: WordSize = WordSize::Bits16;
+}
+
+impl Word for u32 {
+    const SIZE: WordSize = WordSize::Bits32;
+}
+
+/// A peripheral data register served by channel `N` of DMA controller `D`
+///
+/// # Safety
+///
+/// `address` must be the data register of the peripheral, for words of type
+/// `Word`, and the request must be mapped to that channel.
+pub unsafe trait DmaPeripheral<const D: u8, const N: u8> {
+    /// Data register word
+    type Word: Word;
+
+    /// Address of the data register
+    fn address(&self) -> u32;
+
+    /// Makes the peripheral issue DMA requests
+    fn start_dma(&mut self);
+
+    /// Stops the DMA requests
+    fn stop_dma(&mut self);
+}
+
+/// Channel `N` of DMA controller `D`
+pub struct Channel<const D: u8, const N: u8> {
+    _0: (),
+}
+
+impl<const D: u8, const N: u8> Channel<D, N> {
+    #[inline(always)]
+    fn dma() -> &'static DMA {
+        let ptr = match D {
+            1 => DMA1::ptr() as *const DMA,
+            _ => DMA2::ptr() as *const DMA,
+        };
+        // NOTE(unsafe) the channel only touches its own registers
+        unsafe { &*ptr }
+    }
+
+    #[inline(always)]
+    fn ch(&self) -> &'static CH {
+        &Self::dma().CH[usize::from(N - 1)]
+    }
+
+    #[inline(always)]
+    fn flags(&self) -> u32 {
+        Self::dma().INTFR.get() >> (4 * (N - 1))
+    }
+
+    #[inline(always)]
+    fn modify_cfgr(&mut self, mask: u32, set: bool) {
+        let cfgr = &self.ch().CFGR;
+        if set {
+            cfgr.set(cfgr.get() | mask);
+        } else {
+            cfgr.set(cfgr.get() & !mask);
+        }
+    }
+
+    /// Sets the peripheral address and whether it is incremented after
+    /// each transfer
+    pub fn set_peripheral_address(&mut self, address: u32, inc: bool) {
+        self.ch().PADDR.set(address);
+        self.modify_cfgr(PINC, inc);
+    }
+
+    /// Sets the memory address and whether it is incremented after each
+    /// transfer
+    pub fn set_memory_address(&mut self, address: u32, inc: bool) {
+        self.ch().MADDR.set(address);
+        self.modify_cfgr(MINC, inc);
+    }
+
+    /// Sets the number of transfers. Only takes effect while the channel is
+    /// stopped.
+    pub fn set_transfer_length(&mut self, len: u16) {
+        self.ch().CNTR.set(u32::from(len));
+    }
+
+    /// Returns the number of transfers left. In circular mode the counter
+    /// reloads once it reaches zero.
+    pub fn get_transfer_length(&self) -> u16 {
+        self.ch().CNTR.get() as u16
+    }
+
+    /// Sets the size of the peripheral and of the memory words, 8 bits
+    /// after reset
+    pub fn set_word_size(&mut self, size: WordSize) {
+        let cfgr = &self.ch().CFGR;
+        let mask = (0b11 << PSIZE_OFFSET) | (0b11 << MSIZE_OFFSET);
+        let bits = ((size as u32) << PSIZE_OFFSET) | ((size as u32) << MSIZE_OFFSET);
+        cfgr.set((cfgr.get() & !mask) | bits);
+    }
+
+    /// Sets the transfer direction
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.modify_cfgr(DIR, direction == Direction::MemoryToPeripheral);
+    }
+
+    /// Enables or disables circular mode, which restarts the transfer from
+    /// the initial addresses and length once it completes
+    pub fn set_circular(&mut self, circular: bool) {
+        self.modify_cfgr(CIRC, circular);
+    }
+
+    /// Enables the interrupt for `event`
+    pub fn listen(&mut self, event: Event) {
+        self.modify_cfgr(event.cfgr_bit(), true);
+    }
+
+    /// Disables the interrupt for `event`
+    pub fn unlisten(&mut self, event: Event) {
+        self.modify_cfgr(event.cfgr_bit(), false);
+    }
+
+    /// Clears the flags of this channel and starts it
+    pub fn start(&mut self) {
+        self.clear_flags();
+        self.modify_cfgr(EN, true);
+    }
+
+    /// Stops the channel
+    pub fn stop(&mut self) {
+        self.modify_cfgr(EN, false);
+    }
+
+    /// Whether the channel is enabled
+    pub fn is_running(&self) -> bool {
+        self.ch().CFGR.get() & EN != 0
+    }
+
+    /// Whether the transfer completed
+    pub fn is_complete(&self) -> bool {
+        self.flags() & TCIF != 0
+    }
+
+    /// Whether half of the transfer completed
+    pub fn is_half_complete(&self) -> bool {
+        self.flags() & HTIF != 0
+    }
+
+    /// Whether a bus error occurred, which also stops the channel
+    pub fn is_error(&self) -> bool {
+        self.flags() & TEIF != 0
+    }
+
+    /// Clears all flags of this channel
+    pub fn clear_flags(&mut self) {
+        // Write one to clear, no read-modify-write required
+        Self::dma()
+            .INTFCR
+            .set((GIF | TCIF | HTIF | TEIF) << (4 * (N - 1)));
+    }
+}
+
+/// A channel serving `PERIPH` in a [`Transfer`]
+pub trait TransferChannel<PERIPH> {
+    /// Stops the requests of `periph` and the channel
+    #[doc(hidden)]
+    fn abort(&mut self, periph: &mut PERIPH);
+}
+
+impl<PERIPH, const D: u8, const N: u8> TransferChannel<PERIPH> for Channel<D, N>
+where
+    PERIPH: DmaPeripheral<D, N>,
+{
+    fn abort(&mut self, periph: &mut PERIPH) {
+        periph.stop_dma();
+        self.stop();
+        compiler_fence(Ordering::Acquire);
+    }
+}
+
+/// A DMA transfer in progress
+///
+/// Owns the buffer, the channel and the peripheral until the transfer is
+/// over, so neither can be touched while the DMA is using them. Dropping a
+/// transfer stops it, so the DMA never writes to a buffer that was handed
+/// back.
+pub struct Transfer<BUF, CHANNEL: TransferChannel<PERIPH>, PERIPH> {
+    buf: BUF,
+    channel: CHANNEL,
+    periph: PERIPH,
+}
+
+impl<W, PERIPH, const D: u8, const N: u8> Transfer<&'static mut [W], Channel<D, N>, PERIPH>
+where
+    W: Word,
+    PERIPH: DmaPeripheral<D, N, Word = W>,
+{
+    /// Fills `buf` from the peripheral
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is longer than 65535 words, or if the peripheral
+    /// address is not aligned to the word size.
+    pub fn read(mut channel: Channel<D, N>, mut periph: PERIPH, buf: &'static mut [W]) -> Self {
+        assert!(buf.len() <= usize::from(u16::MAX));
+        check_alignment::<W>(periph.address(), buf.as_ptr() as u32);
+        channel.stop();
+        channel.set_peripheral_address(periph.address(), false);
+        channel.set_memory_address(buf.as_mut_ptr() as u32, true);
+        channel.set_transfer_length(buf.len() as u16);
+        channel.set_word_size(W::SIZE);
+        channel.set_direction(Direction::PeripheralToMemory);
+        channel.set_circular(false);
+        compiler_fence(Ordering::Release);
+        channel.start();
+        periph.start_dma();
+
+        Transfer {
+            buf,
+            channel,
+            periph,
+        }
+    }
+}
+
+impl<W, PERIPH, const D: u8, const N: u8> Transfer<&'static [W], Channel<D, N>, PERIPH>
+where
+    W: Word,
+    PERIPH: DmaPeripheral<D, N, Word = W>,
+{
+    /// Sends `buf` to the peripheral
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is longer than 65535 words, or if the peripheral
+    /// address is not aligned to the word size.
+    pub fn write(mut channel: Channel<D, N>, mut periph: PERIPH, buf: &'static [W]) -> Self {
+        assert!(buf.len() <= usize::from(u16::MAX));
+        check_alignment::<W>(periph.address(), buf.as_ptr() as u32);
+        channel.stop();
+        channel.set_peripheral_address(periph.address(), false);
+        channel.set_memory_address(buf.as_ptr() as u32, true);
+        channel.set_transfer_length(buf.len() as u16);
+        channel.set_word_size(W::SIZE);
+        channel.set_direction(Direction::MemoryToPeripheral);
+        channel.set_circular(false);
+        compiler_fence(Ordering::Release);
+        channel.start();
+        periph.start_dma();
+
+        Transfer {
+            buf,
+            channel,
+            periph,
+        }
+    }
+}
+
+impl<BUF, PERIPH, const D: u8, const N: u8> Transfer<BUF, Channel<D, N>, PERIPH>
+where
+    PERIPH: DmaPeripheral<D, N>,
+{
+    /// Whether the transfer completed or failed
+    pub fn is_done(&self) -> bool {
+        self.channel.is_complete() || self.channel.is_error()
+    }
+
+    /// Waits for the transfer to complete or fail, and returns the buffer,
+    /// the channel and the peripheral.
+    ///
+    /// On a bus error, the error flag is cleared and they are returned along
+    /// with [`DmaError::Transfer`]. The buffer content is then undefined.
+    #[allow(clippy::type_complexity)]
+    pub fn wait(
+        mut self,
+    ) -> Result<(BUF, Channel<D, N>, PERIPH), (DmaError, BUF, Channel<D, N>, PERIPH)> {
+        while !self.is_done() {}
+        let failed = self.channel.is_error();
+        let (buf, mut channel, periph) = self.stop();
+        if failed {
+            channel.clear_flags();
+            Err((DmaError::Transfer, buf, channel, periph))
+        } else {
+            Ok((buf, channel, periph))
+        }
+    }
+
+    /// Aborts the transfer, and returns the buffer, the channel and the
+    /// peripheral. Check [`Channel::get_transfer_length`] for the words
+    /// that were not transferred.
+    pub fn stop(mut self) -> (BUF, Channel<D, N>, PERIPH) {
+        self.channel.abort(&mut self.periph);
+        // NOTE(unsafe) each field is moved out once, and `self` is
+        // forgotten so that `drop` does not run on them
+        let parts = unsafe {
+            (
+                ptr::read(&self.buf),
+                ptr::read(&self.channel),
+                ptr::read(&self.periph),
+            )
+        };
+        mem::forget(self);
+        parts
+    }
+}
+
+impl<BUF, CHANNEL: TransferChannel<PERIPH>, PERIPH> Drop for Transfer<BUF, CHANNEL, PERIPH> {
+    fn drop(&mut self) {
+        self.channel.abort(&mut self.periph);
+    }
+}
+
+/// Both addresses must be aligned to the word size
+fn check_alignment<W: Word>(peripheral: u32, memory: u32) {
+    let align = core::mem::size_of::<W>() as u32;
+    assert!(
+        peripheral % align == 0 && memory % align == 0,
+        "misaligned DMA address"
+    );
+}
+
+macro_rules! dma {
+    ($($DMAX:ident: ($dmax:ident, $D:literal, $Rec:ident),)+) => {
+        $(
+            pub mod $dmax {
+                //! Channels of the DMA controller
+
+                use super::Channel;
+
+                /// The DMA controller split into its channels
+                pub struct Channels {
+                    pub ch1: Channel<$D, 1>,
+                    pub ch2: Channel<$D, 2>,
+                    pub ch3: Channel<$D, 3>,
+                    pub ch4: Channel<$D, 4>,
+                    pub ch5: Channel<$D, 5>,
+                    pub ch6: Channel<$D, 6>,
+                    pub ch7: Channel<$D, 7>,
+                }
+            }
+
+            impl DmaExt for $DMAX {
+                type Channels = $dmax::Channels;
+                type Rec = rec::$Rec;
+
+                fn split(self, prec: rec::$Rec) -> $dmax::Channels {
+                    // No reset bit, stop the channels by hand
+                    prec.enable();
+                    let dma = Channel::<$D, 1>::dma();
+                    for ch in &dma.CH {
+                        ch.CFGR.set(0);
+                    }
+                    dma.INTFCR.set(u32::MAX);
+
+                    $dmax::Channels {
+                        ch1: Channel { _0: () },
+                        ch2: Channel { _0: () },
+                        ch3: Channel { _0: () },
+                        ch4: Channel { _0: () },
+                        ch5: Channel { _0: () },
+                        ch6: Channel { _0: () },
+                        ch7: Channel { _0: () },
+                    }
+                }
+            }
+        )+
+    };
+}
+
+dma! {
+    DMA1: (dma1, 1, Dma1),
+    DMA2: (dma2, 2, Dma2),
+}
@@ -0,0 +1,121 @@
+//! Extended configuration (EXTEND)
+//!
+//! `EXTEND_CTR` holds CH32-specific controls that have no STM32 equivalent.
+//! The PLL HSI prescaler bit in the same register is owned by [`rcc`](crate::rcc)
+//! and is left alone here.
+//!
+//! **NOTE**: Unlike some other CH32 parts, the CH32V30x has no per-pin drive
+//! strength or 5V tolerance selection in `EXTEND`. The FT pins listed in the
+//! datasheet are always 5V tolerant in input and open-drain modes.
+
+use crate::opa::Opamps;
+use crate::pac::EXTEND;
+
+// EXTEND_CTR bits
+const USBDLS: u32 = 1 << 0;
+const USBDPU: u32 = 1 << 1;
+const LKUPEN: u32 = 1 << 6;
+const LKUPRST: u32 = 1 << 7;
+
+/// Extension trait that constrains the `EXTEND` peripheral
+pub trait ExtendExt {
+    /// Constrains the `EXTEND` peripheral so it plays nicely with the
+    /// other abstractions
+    fn constrain(self) -> Extend;
+}
+
+impl ExtendExt for EXTEND {
+    fn constrain(self) -> Extend {
+        Extend {
+            rb: self,
+            opamps_taken: false,
+        }
+    }
+}
+
+/// Constrained EXTEND peripheral
+pub struct Extend {
+    rb: EXTEND,
+    opamps_taken: bool,
+}
+
+/// USBD (full-speed device) PHY speed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UsbdSpeed {
+    /// Full speed, 12Mbps, pull-up on D+ (default)
+    Full,
+    /// Low speed, 1.5Mbps, pull-up on D-
+    Low,
+}
+
+impl Extend {
+    /// Select the USBD PHY speed, which also moves the internal pull-up
+    /// between D+ and D-. Defaults to full speed after reset.
+    pub fn set_usbd_speed(&mut self, speed: UsbdSpeed) {
+        self.modify(USBDLS, speed == UsbdSpeed::Low);
+    }
+
+    /// Connect or disconnect the USBD PHY internal 1.5k pull-up, which
+    /// signals the device's presence to the host. Disconnected after reset.
+    pub fn set_usbd_pull_up(&mut self, enable: bool) {
+        self.modify(USBDPU, enable);
+    }
+
+    /// Reset the core when it locks up (e.g. an exception inside an
+    /// exception handler). Disabled after reset.
+    pub fn set_lockup_reset(&mut self, enable: bool) {
+        self.modify(LKUPEN, enable);
+    }
+
+    /// Whether the last reset was caused by a core lockup
+    pub fn is_lockup_reset(&self) -> bool {
+        self.rb.extend_ctr.read().bits() & LKUPRST != 0
+    }
+
+    /// Clear the lockup reset flag
+    pub fn clear_lockup_reset(&mut self) {
+        // the flag is cleared by writing 1, the other bits are kept
+        self.rb
+            .extend_ctr
+            .modify(|r, w| unsafe { w.bits(r.bits() | LKUPRST) });
+    }
+
+    /// Hands out the op-amps, on the first call only. `OPA_CTLR` sits next
+    /// to `EXTEND_CTR` but is driven by [`opa`](crate::opa).
+    pub fn split_opamps(&mut self) -> Option<Opamps> {
+        if self.opamps_taken {
+            return None;
+        }
+        self.opamps_taken = true;
+        Some(Opamps::new())
+    }
+
+    /// Release the peripheral
+    pub fn free(self) -> EXTEND {
+        self.rb
+    }
+
+    fn modify(&mut self, mask: u32, set: bool) {
+        self.rb.extend_ctr.modify(|r, w| unsafe {
+            // Writing back a set LKUPRST would clear it
+            let bits = r.bits() & !LKUPRST;
+            if set {
+                w.bits(bits | mask)
+            } else {
+                w.bits(bits & !mask)
+            }
+        });
+    }
+}
+
+/// Sets the PLL HSI prescaler bit on behalf of [`rcc`](crate::rcc), without
+/// clearing the lockup reset flag
+pub(crate) fn set_pll_hsi_pre(div2: bool) {
+    // NOTE(unsafe) only rcc writes this bit, while it sets up the clocks
+    unsafe {
+        (*EXTEND::ptr())
+            .extend_ctr
+            .modify(|r, w| w.bits(r.bits() & !LKUPRST).pll_hsi_pre().bit(div2));
+    }
+}
@@ -0,0 +1,253 @@
+//! Flash memory programming and erasing
+//!
+//! ```
+//! let mut flash = Flash::new(dp.FLASH);
+//!
+//! // The last 4K page of a 256K part
+//! const CALIBRATION: u32 = 0x0803_F000;
+//!
+//! let mut unlocked = flash.unlock();
+//! unlocked.erase_page(CALIBRATION)?;
+//! unlocked.program(CALIBRATION, &calibration.to_le_bytes())?;
+//! // Locked again when `unlocked` is dropped
+//! ```
+//!
+//! Standard pages are 4K and are programmed one half-word at a time. The
+//! fast mode erases 256-byte pages and programs them in one go, see
+//! [`erase_fast_page`](UnlockedFlash::erase_fast_page) and
+//! [`program_fast_page`](UnlockedFlash::program_fast_page).
+//!
+//! Erased flash reads as `0xFF`. A half-word can only be programmed once
+//! between erases.
+//!
+//! There are no wait states to configure: the CH32V30x runs code from the
+//! zero-wait area, which is copied from flash into SRAM at reset, so
+//! [`Rcc::freeze`](crate::rcc::Rcc::freeze) can switch straight to 144MHz.
+//! Faults right after raising SYSCLK point to an unstable clock source or a
+//! sysclk above the part's limit instead.
+
+use core::ptr;
+
+use vcell::VolatileCell;
+
+use crate::pac;
+
+/// Start of the code flash in the memory map
+pub const FLASH_START: u32 = 0x0800_0000;
+/// Size of a standard erase page
+pub const PAGE_SIZE: u32 = 4096;
+/// Size of a fast erase and program page
+pub const FAST_PAGE_SIZE: u32 = 256;
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+struct FLASH {
+    ACTLR: VolatileCell<u32>,
+    KEYR: VolatileCell<u32>,
+    OBKEYR: VolatileCell<u32>,
+    STATR: VolatileCell<u32>,
+    CTLR: VolatileCell<u32>,
+    ADDR: VolatileCell<u32>,
+    _reserved: u32,
+    OBR: VolatileCell<u32>,
+    WPR: VolatileCell<u32>,
+    MODEKEYR: VolatileCell<u32>,
+}
+
+#[inline(always)]
+fn regs() -> &'static FLASH {
+    unsafe { &*(pac::FLASH::ptr() as *const FLASH) }
+}
+
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+// STATR bits
+const BSY: u32 = 1 << 0;
+const WR_BSY: u32 = 1 << 1;
+const WRPRTERR: u32 = 1 << 4;
+const EOP: u32 = 1 << 5;
+// CTLR bits
+const PG: u32 = 1 << 0;
+const PER: u32 = 1 << 1;
+const STRT: u32 = 1 << 6;
+const LOCK: u32 = 1 << 7;
+const FLOCK: u32 = 1 << 15;
+const FTPG: u32 = 1 << 16;
+const FTER: u32 = 1 << 17;
+const PGSTRT: u32 = 1 << 21;
+
+/// Flash error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The address is not aligned on the page or the half-word
+    Alignment,
+    /// The address is outside of the code flash
+    Address,
+    /// The page is write protected
+    WriteProtection,
+    /// The programmed data doesn't read back, the half-word wasn't erased
+    Verify,
+}
+
+/// Flash memory controller
+pub struct Flash {
+    flash: pac::FLASH,
+}
+
+impl Flash {
+    /// Takes the flash controller
+    pub fn new(flash: pac::FLASH) -> Self {
+        Flash { flash }
+    }
+
+    /// Lifts the erase and program lock, standard and fast, until the
+    /// returned [`UnlockedFlash`] is dropped.
+    pub fn unlock(&mut self) -> UnlockedFlash<'_> {
+        let rb = regs();
+        rb.KEYR.set(KEY1);
+        rb.KEYR.set(KEY2);
+        rb.MODEKEYR.set(KEY1);
+        rb.MODEKEYR.set(KEY2);
+        UnlockedFlash { flash: self }
+    }
+
+    /// Size of the code flash, in bytes
+    pub fn size(&self) -> u32 {
+        u32::from(crate::signature::flash_size_kb()) * 1024
+    }
+
+    /// Releases the flash controller
+    pub fn release(self) -> pac::FLASH {
+        self.flash
+    }
+}
+
+/// Flash controller with the erase and program lock lifted
+///
+/// Locks the flash again when dropped.
+pub struct UnlockedFlash<'a> {
+    flash: &'a mut Flash,
+}
+
+impl Drop for UnlockedFlash<'_> {
+    fn drop(&mut self) {
+        let rb = regs();
+        rb.CTLR.set(rb.CTLR.get() | LOCK | FLOCK);
+    }
+}
+
+impl UnlockedFlash<'_> {
+    fn check_range(&self, address: u32, len: u32) -> Result<(), Error> {
+        let end = FLASH_START + self.flash.size();
+        if address < FLASH_START || address.saturating_add(len) > end {
+            Err(Error::Address)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Waits for the end of the operation and clears its flags
+    fn wait(&self) -> Result<(), Error> {
+        let rb = regs();
+        while rb.STATR.get() & BSY != 0 {}
+
+        let statr = rb.STATR.get();
+        // Flags are cleared by writing 1
+        rb.STATR.set(statr & (EOP | WRPRTERR));
+        if statr & WRPRTERR != 0 {
+            Err(Error::WriteProtection)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs an erase of the page at `address` with the erase bit `erase`
+    fn erase(&mut self, address: u32, erase: u32) -> Result<(), Error> {
+        let rb = regs();
+        rb.CTLR.set(rb.CTLR.get() | erase);
+        rb.ADDR.set(address);
+        rb.CTLR.set(rb.CTLR.get() | STRT);
+        let result = self.wait();
+        rb.CTLR.set(rb.CTLR.get() & !erase);
+        result
+    }
+
+    /// Erases the 4K page starting at `address`
+    pub fn erase_page(&mut self, address: u32) -> Result<(), Error> {
+        if address % PAGE_SIZE != 0 {
+            return Err(Error::Alignment);
+        }
+        self.check_range(address, PAGE_SIZE)?;
+        self.erase(address, PER)
+    }
+
+    /// Erases the 256-byte page starting at `address`
+    pub fn erase_fast_page(&mut self, address: u32) -> Result<(), Error> {
+        if address % FAST_PAGE_SIZE != 0 {
+            return Err(Error::Alignment);
+        }
+        self.check_range(address, FAST_PAGE_SIZE)?;
+        self.erase(address, FTER)
+    }
+
+    /// Programs `data` at `address`, one half-word at a time.
+    ///
+    /// `address` must be half-word aligned and the range erased. An odd
+    /// trailing byte is padded with `0xFF`.
+    pub fn program(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        if address % 2 != 0 {
+            return Err(Error::Alignment);
+        }
+        self.check_range(address, data.len() as u32)?;
+
+        let rb = regs();
+        rb.CTLR.set(rb.CTLR.get() | PG);
+        let result = data.chunks(2).enumerate().try_for_each(|(i, bytes)| {
+            let half_word = u16::from_le_bytes([bytes[0], *bytes.get(1).unwrap_or(&0xFF)]);
+            let dst = (address as usize + 2 * i) as *mut u16;
+            // NOTE(unsafe) in range, aligned, and PG is set
+            unsafe { ptr::write_volatile(dst, half_word) };
+            self.wait()?;
+            if unsafe { ptr::read_volatile(dst) } != half_word {
+                return Err(Error::Verify);
+            }
+            Ok(())
+        });
+        rb.CTLR.set(rb.CTLR.get() & !PG);
+        result
+    }
+
+    /// Programs a whole erased 256-byte page starting at `address`
+    pub fn program_fast_page(&mut self, address: u32, data: &[u32; 64]) -> Result<(), Error> {
+        if address % FAST_PAGE_SIZE != 0 {
+            return Err(Error::Alignment);
+        }
+        self.check_range(address, FAST_PAGE_SIZE)?;
+
+        let rb = regs();
+        rb.CTLR.set(rb.CTLR.get() | FTPG);
+        while rb.STATR.get() & BSY != 0 {}
+        while rb.STATR.get() & WR_BSY != 0 {}
+        for (i, word) in data.iter().enumerate() {
+            let dst = (address as usize + 4 * i) as *mut u32;
+            // NOTE(unsafe) in range, aligned, and FTPG is set: the words
+            // go to the page buffer
+            unsafe { ptr::write_volatile(dst, *word) };
+            while rb.STATR.get() & WR_BSY != 0 {}
+        }
+        // The page buffer is programmed by PGSTRT, STRT only starts erases
+        rb.CTLR.set(rb.CTLR.get() | PGSTRT);
+        let result = self.wait();
+        rb.CTLR.set(rb.CTLR.get() & !FTPG);
+        result?;
+
+        let written = unsafe { core::slice::from_raw_parts(address as *const u32, 64) };
+        if written != data {
+            return Err(Error::Verify);
+        }
+        Ok(())
+    }
+}
@@ -1,37 +1,265 @@
 use super::*;
 
 impl<const P: char, const N: u8, MODE: PinMode> Pin<P, N, MODE> {
+    /// Configures the pin to operate as an alternate function push-pull
+    /// output pin, handing the line to a peripheral.
+    pub fn into_alternate(mut self) -> Pin<P, N, Alternate<PushPull>> {
+        self.mode::<Alternate<PushPull>>();
+        Pin::new()
+    }
+
+    /// Configures the pin to operate as an alternate function open-drain
+    /// output pin, e.g. for I2C.
+    pub fn into_alternate_open_drain(mut self) -> Pin<P, N, Alternate<OpenDrain>> {
+        self.mode::<Alternate<OpenDrain>>();
+        Pin::new()
+    }
+
+    /// Configures the pin to operate as a floating input pin.
+    ///
+    /// This is also the mode for peripheral inputs, such as USART RX.
+    pub fn into_floating_input(mut self) -> Pin<P, N, Input<Floating>> {
+        self.mode::<Input<Floating>>();
+        Pin::new()
+    }
+
+    /// Configures the pin to operate as a pulled down input pin
+    pub fn into_pull_down_input(mut self) -> Pin<P, N, Input<PullDown>> {
+        self.mode::<Input<PullDown>>();
+        Pin::new()
+    }
+
+    /// Configures the pin to operate as a pulled up input pin
+    pub fn into_pull_up_input(mut self) -> Pin<P, N, Input<PullUp>> {
+        self.mode::<Input<PullUp>>();
+        Pin::new()
+    }
+
+    /// Configures the pin to operate as an analog input pin, for the ADC,
+    /// the DAC or the OPA.
+    pub fn into_analog(mut self) -> Pin<P, N, Analog> {
+        self.mode::<Analog>();
+        Pin::new()
+    }
+
+    /// Configures the pin to operate as an open-drain output pin.
+    ///
+    /// Setting the pin high only releases the line. The internal pull-up and
+    /// pull-down resistors are connected in input mode only, so an
+    /// open-drain output needs an external pull-up to reach the high level.
+    pub fn into_open_drain_output(mut self) -> Pin<P, N, Output<OpenDrain>> {
+        self.mode::<Output<OpenDrain>>();
+        Pin::new()
+    }
+
+    /// Configures the pin to operate as an open-drain output pin.
+    ///
+    /// `initial_state` is latched before the mode changes, so the pin never
+    /// pulls the line low when it should start released, or the other way
+    /// round.
+    pub fn into_open_drain_output_in_state(
+        mut self,
+        initial_state: PinState,
+    ) -> Pin<P, N, Output<OpenDrain>> {
+        self._set_state(initial_state);
+        self.mode::<Output<OpenDrain>>();
+        Pin::new()
+    }
+
+    /// Configures the pin to operate as a push-pull output pin.
+    ///
+    /// The pin drives whatever level is in its output latch right away,
+    /// use [`into_push_pull_output_in_state`](Self::into_push_pull_output_in_state)
+    /// to choose it.
+    pub fn into_push_pull_output(mut self) -> Pin<P, N, Output<PushPull>> {
+        self.mode::<Output<PushPull>>();
+        Pin::new()
+    }
+
+    /// Configures the pin to operate as a push-pull output pin.
+    ///
+    /// `initial_state` is latched before the mode changes, so the pin never
+    /// drives the wrong level, even briefly.
+    pub fn into_push_pull_output_in_state(
+        mut self,
+        initial_state: PinState,
+    ) -> Pin<P, N, Output<PushPull>> {
+        self._set_state(initial_state);
+        self.mode::<Output<PushPull>>();
+        Pin::new()
+    }
+
+    /// Configures the pin as a pin that can change between input
+    /// and output without changing the type. It starts out
+    /// as a floating input
+    pub fn into_dynamic(mut self) -> DynamicPin<P, N> {
+        self.mode::<Input<Floating>>();
+        DynamicPin::new(Dynamic::InputFloating)
+    }
+
+    /// Puts the pin into mode `M` for the duration of `f`, then restores
+    /// its configuration and output latch, whatever `MODE` is
+    #[inline]
+    pub(crate) fn with_mode<M: PinMode, R>(&mut self, f: impl FnOnce(&mut Pin<P, N, M>) -> R) -> R {
+        let cfgr = self.cfgr();
+        let set_low = self._is_set_low();
+
+        self.mode::<M>();
+        let ret = f(&mut Pin::new());
+
+        // Restore the latch while the level it drives doesn't matter: before
+        // an output drives it, after an input only uses it for the pull
+        let restore_latch = |pin: &mut Self| match set_low {
+            true => pin._set_low(),
+            false => pin._set_high(),
+        };
+        if cfgr & 0b11 != 0 {
+            restore_latch(self);
+            self.set_cfgr(cfgr);
+        } else {
+            self.set_cfgr(cfgr);
+            restore_latch(self);
+        }
+        ret
+    }
+
+    /// Temporarily configures the pin as a floating input
+    #[inline]
+    pub fn with_floating_input<R>(
+        &mut self,
+        f: impl FnOnce(&mut Pin<P, N, Input<Floating>>) -> R,
+    ) -> R {
+        self.with_mode(f)
+    }
+
+    /// Temporarily configures the pin as a pulled up input
+    #[inline]
+    pub fn with_pull_up_input<R>(
+        &mut self,
+        f: impl FnOnce(&mut Pin<P, N, Input<PullUp>>) -> R,
+    ) -> R {
+        self.with_mode(f)
+    }
+
+    /// Temporarily configures the pin as a pulled down input
+    #[inline]
+    pub fn with_pull_down_input<R>(
+        &mut self,
+        f: impl FnOnce(&mut Pin<P, N, Input<PullDown>>) -> R,
+    ) -> R {
+        self.with_mode(f)
+    }
+
+    /// Temporarily configures the pin as an analog input
+    #[inline]
+    pub fn with_analog<R>(&mut self, f: impl FnOnce(&mut Pin<P, N, Analog>) -> R) -> R {
+        self.with_mode(f)
+    }
+
+    /// Temporarily configures the pin as an open-drain output, keeping the
+    /// current output latch
+    #[inline]
+    pub fn with_open_drain_output<R>(
+        &mut self,
+        f: impl FnOnce(&mut Pin<P, N, Output<OpenDrain>>) -> R,
+    ) -> R {
+        self.with_mode(f)
+    }
+
+    /// Temporarily configures the pin as an open-drain output starting in
+    /// `state`
+    #[inline]
+    pub fn with_open_drain_output_in_state<R>(
+        &mut self,
+        state: PinState,
+        f: impl FnOnce(&mut Pin<P, N, Output<OpenDrain>>) -> R,
+    ) -> R {
+        self.with_mode(|pin: &mut Pin<P, N, Output<OpenDrain>>| {
+            // Only changes the pull of an input, or the level of an output
+            // that is about to be set to it anyway
+            pin._set_state(state);
+            f(pin)
+        })
+    }
+
+    /// Temporarily configures the pin as a push-pull output, keeping the
+    /// current output latch
+    #[inline]
+    pub fn with_push_pull_output<R>(
+        &mut self,
+        f: impl FnOnce(&mut Pin<P, N, Output<PushPull>>) -> R,
+    ) -> R {
+        self.with_mode(f)
+    }
+
+    /// Temporarily configures the pin as a push-pull output starting in
+    /// `state`, e.g. to briefly drive an input low:
+    ///
+    /// ```
+    /// pin.with_push_pull_output_in_state(PinState::Low, |_| delay.delay_us(10u32));
+    /// ```
+    #[inline]
+    pub fn with_push_pull_output_in_state<R>(
+        &mut self,
+        state: PinState,
+        f: impl FnOnce(&mut Pin<P, N, Output<PushPull>>) -> R,
+    ) -> R {
+        self.with_mode(|pin: &mut Pin<P, N, Output<PushPull>>| {
+            pin._set_state(state);
+            f(pin)
+        })
+    }
+
+    /// Raw configuration nibble, `<<CNF:2, MODE:2>>`
+    #[inline(always)]
+    fn cfgr(&self) -> u32 {
+        let offset = 4 * (N % 8);
+        // NOTE(unsafe) atomic read with no side effects
+        let cfgr = unsafe {
+            if N < 8 {
+                (*Gpio::<P>::ptr()).cfglr.read().bits()
+            } else {
+                (*Gpio::<P>::ptr()).cfghr.read().bits()
+            }
+        };
+        (cfgr >> offset) & 0b1111
+    }
+
+    #[inline(always)]
+    fn set_cfgr(&mut self, cfgr: u32) {
+        Gpio::<P>::modify_cfgr(N, 0b1111, cfgr);
+    }
+
     /// Puts `self` into mode `M`.
     ///
     /// This violates the type state constraints from `MODE`, so callers must
     /// ensure they use this properly.
     #[inline(always)]
     pub(super) fn mode<M: PinMode>(&mut self) {
-        let offset = 4 * N % 8;
-        unsafe {
-            if MODE::CFGR != M::CFGR {
-                if N < 8 {
-                    (*Gpio::<P>::ptr()).cfglr.modify(|r, w| {
-                        w.bits(r.bits() & !(0b1111 << offset) | (M::CFGR << offset))
-                    });
-                } else {
-                    (*Gpio::<P>::ptr()).cfghr.modify(|r, w| {
-                        w.bits(r.bits() & !(0b1111 << offset) | (M::CFGR << offset))
-                    });
-                }
-            }
+        if MODE::CFGR != M::CFGR {
+            Gpio::<P>::modify_cfgr(N, 0b1111, M::CFGR);
+        }
 
+        unsafe {
             // PullUp/PullDown is controlled by the BCR(down) / BSHR(up) register.
             // seems undocumented, but it's in the hal library.
-            if MODE::PULL_DOWN {
+            if M::PULL_DOWN {
                 (*Gpio::<P>::ptr()).bcr.write(|w| w.bits(0b1 << N));
-            } else if MODE::PULL_UP {
+            } else if M::PULL_UP {
                 (*Gpio::<P>::ptr()).bshr.write(|w| w.bits(0b1 << N));
             }
         }
     }
 }
 
+impl<const P: char, const N: u8> Pin<P, N, Debugger> {
+    /// Hands a debug pin out for reconfiguration, once the debug interface
+    /// has been disabled
+    pub(crate) fn into_unconfigured(self) -> Pin<P, N, Unconfigured> {
+        Pin::new()
+    }
+}
+
 /// Marker trait for valid pin modes (type state).
 ///
 /// This trait is sealed and cannot be implemented by outside types
@@ -47,6 +275,10 @@ pub trait PinMode: crate::Sealed {
     const PULL_UP: bool = false;
 }
 
+// Never equal to a real mode, so leaving it always rewrites the config bits
+impl crate::Sealed for Unconfigured {}
+impl PinMode for Unconfigured {}
+
 impl<IType> crate::Sealed for Input<IType> {}
 impl PinMode for Input<Floating> {
     const CFGR: u32 = 0b01_00;
@@ -68,10 +300,10 @@ impl PinMode for Analog {
 // use 0b10 for low speed, 2MHz max
 impl<Otype> crate::Sealed for Output<Otype> {}
 impl PinMode for Output<PushPull> {
-    const CFGR: u32 = 0b10_00;
+    const CFGR: u32 = 0b00_10;
 }
 impl PinMode for Output<OpenDrain> {
-    const CFGR: u32 = 0b10_01;
+    const CFGR: u32 = 0b01_10;
 }
 
 impl<Otype> crate::Sealed for Alternate<Otype> {}
@@ -79,5 +311,5 @@ impl PinMode for Alternate<PushPull> {
     const CFGR: u32 = 0b10_10;
 }
 impl PinMode for Alternate<OpenDrain> {
-    const CFGR: u32 = 0b10_11;
+    const CFGR: u32 = 0b11_10;
 }
@@ -0,0 +1,156 @@
+use super::*;
+
+/// Pin type with dynamic mode
+///
+/// - `P` is port name: `A` for GPIOA, `B` for GPIOB, etc.
+/// - `N` is pin number: from `0` to `15`.
+pub struct DynamicPin<const P: char, const N: u8> {
+    /// Current pin mode
+    pub(crate) mode: Dynamic,
+}
+
+/// Tracks the current pin state for dynamic pins
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Dynamic {
+    /// Floating input mode
+    InputFloating,
+    /// Pull-up input mode
+    InputPullUp,
+    /// Pull-down input mode
+    InputPullDown,
+    /// Push-pull output mode
+    OutputPushPull,
+    /// Open-drain output mode
+    OutputOpenDrain,
+}
+
+/// Error for [DynamicPin]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PinModeError {
+    /// For operations unsupported in current mode
+    IncorrectMode,
+}
+
+impl Dynamic {
+    /// Is pin in readable mode
+    pub fn is_input(&self) -> bool {
+        use Dynamic::*;
+        match self {
+            InputFloating | InputPullUp | InputPullDown | OutputOpenDrain => true,
+            OutputPushPull => false,
+        }
+    }
+
+    /// Is pin in writable mode
+    pub fn is_output(&self) -> bool {
+        use Dynamic::*;
+        match self {
+            InputFloating | InputPullUp | InputPullDown => false,
+            OutputPushPull | OutputOpenDrain => true,
+        }
+    }
+}
+
+impl<const P: char, const N: u8> OutputPin for DynamicPin<P, N> {
+    type Error = PinModeError;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        if self.mode.is_output() {
+            Pin::<P, N, Output<PushPull>>::new()._set_high();
+            Ok(())
+        } else {
+            Err(PinModeError::IncorrectMode)
+        }
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        if self.mode.is_output() {
+            Pin::<P, N, Output<PushPull>>::new()._set_low();
+            Ok(())
+        } else {
+            Err(PinModeError::IncorrectMode)
+        }
+    }
+}
+
+impl<const P: char, const N: u8> InputPin for DynamicPin<P, N> {
+    type Error = PinModeError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.is_low().map(|b| !b)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        if self.mode.is_input() {
+            Ok(Pin::<P, N, Input<Floating>>::new()._is_low())
+        } else {
+            Err(PinModeError::IncorrectMode)
+        }
+    }
+}
+
+impl<const P: char, const N: u8> DynamicPin<P, N> {
+    pub(super) const fn new(mode: Dynamic) -> Self {
+        Self { mode }
+    }
+
+    /// Current pin mode
+    pub fn get_mode(&self) -> Dynamic {
+        self.mode
+    }
+
+    /// Switch pin into floating input
+    #[inline]
+    pub fn make_floating_input(&mut self) {
+        Pin::<P, N, Unconfigured>::new().mode::<Input<Floating>>();
+        self.mode = Dynamic::InputFloating;
+    }
+
+    /// Switch pin into pull-up input
+    #[inline]
+    pub fn make_pull_up_input(&mut self) {
+        Pin::<P, N, Unconfigured>::new().mode::<Input<PullUp>>();
+        self.mode = Dynamic::InputPullUp;
+    }
+
+    /// Switch pin into pull-down input
+    #[inline]
+    pub fn make_pull_down_input(&mut self) {
+        Pin::<P, N, Unconfigured>::new().mode::<Input<PullDown>>();
+        self.mode = Dynamic::InputPullDown;
+    }
+
+    /// Switch pin into push-pull output
+    #[inline]
+    pub fn make_push_pull_output(&mut self) {
+        Pin::<P, N, Unconfigured>::new().mode::<Output<PushPull>>();
+        self.mode = Dynamic::OutputPushPull;
+    }
+
+    /// Switch pin into push-pull output with required voltage state
+    #[inline]
+    pub fn make_push_pull_output_in_state(&mut self, state: PinState) {
+        let mut pin = Pin::<P, N, Unconfigured>::new();
+        pin._set_state(state);
+        pin.mode::<Output<PushPull>>();
+        self.mode = Dynamic::OutputPushPull;
+    }
+
+    /// Switch pin into open-drain output
+    #[inline]
+    pub fn make_open_drain_output(&mut self) {
+        Pin::<P, N, Unconfigured>::new().mode::<Output<OpenDrain>>();
+        self.mode = Dynamic::OutputOpenDrain;
+    }
+
+    /// Switch pin into open-drain output with required voltage state
+    #[inline]
+    pub fn make_open_drain_output_in_state(&mut self, state: PinState) {
+        let mut pin = Pin::<P, N, Unconfigured>::new();
+        pin._set_state(state);
+        pin.mode::<Output<OpenDrain>>();
+        self.mode = Dynamic::OutputOpenDrain;
+    }
+}
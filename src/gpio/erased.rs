@@ -0,0 +1,115 @@
+use super::*;
+
+/// Fully erased pin
+///
+/// - `MODE` is one of the pin modes (see [Modes](crate::gpio#modes) section).
+pub struct ErasedPin<MODE> {
+    // Bits 0-3: Pin, Bits 4-7: Port
+    pin_port: u8,
+    _mode: PhantomData<MODE>,
+}
+
+impl<MODE> PinExt for ErasedPin<MODE> {
+    type Mode = MODE;
+
+    #[inline(always)]
+    fn pin_id(&self) -> u8 {
+        self.pin_port & 0x0f
+    }
+    #[inline(always)]
+    fn port_id(&self) -> u8 {
+        self.pin_port >> 4
+    }
+}
+
+impl<MODE> ErasedPin<MODE> {
+    pub(crate) fn new(port: u8, pin: u8) -> Self {
+        Self {
+            pin_port: port << 4 | pin,
+            _mode: PhantomData,
+        }
+    }
+
+    #[inline(always)]
+    fn block(&self) -> &crate::pac::gpioa::RegisterBlock {
+        // NOTE(unsafe) the port id always comes from a valid `Pin`
+        unsafe { &*port_ptr(self.port_id()) }
+    }
+
+    #[inline(always)]
+    fn _set_high(&mut self) {
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe { self.block().bshr.write(|w| w.bits(1 << self.pin_id())) }
+    }
+    #[inline(always)]
+    fn _set_low(&mut self) {
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe {
+            self.block()
+                .bshr
+                .write(|w| w.bits(1 << (16 + self.pin_id())))
+        }
+    }
+    #[inline(always)]
+    fn _is_set_low(&self) -> bool {
+        self.block().outdr.read().bits() & (1 << self.pin_id()) == 0
+    }
+    #[inline(always)]
+    fn _is_low(&self) -> bool {
+        self.block().indr.read().bits() & (1 << self.pin_id()) == 0
+    }
+}
+
+impl<const P: char, const N: u8, MODE> From<Pin<P, N, MODE>> for ErasedPin<MODE> {
+    /// Pin-to-erased pin conversion using the [`From`] trait.
+    ///
+    /// Note that [`From`] is the reciprocal of [`Into`].
+    fn from(p: Pin<P, N, MODE>) -> Self {
+        p.erase()
+    }
+}
+
+impl<MODE> OutputPin for ErasedPin<Output<MODE>> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self._set_high();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self._set_low();
+        Ok(())
+    }
+}
+
+impl<MODE> StatefulOutputPin for ErasedPin<Output<MODE>> {
+    #[inline(always)]
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(!self._is_set_low())
+    }
+
+    #[inline(always)]
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(self._is_set_low())
+    }
+}
+
+impl<MODE> InputPin for ErasedPin<MODE>
+where
+    MODE: marker::Readable,
+{
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(!self._is_low())
+    }
+
+    #[inline(always)]
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(self._is_low())
+    }
+}
@@ -0,0 +1,113 @@
+use super::{marker, PinExt};
+use crate::afio::Afio;
+use crate::pac::EXTI;
+
+/// Edges that can trigger a configurable interrupt line.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edge {
+    /// Rising edge of the input signal
+    Rising,
+    /// Falling edge of the input signal
+    Falling,
+    /// Both rising and falling edges of the input signal
+    RisingFalling,
+}
+
+/// External Interrupt Pin
+pub trait ExtiPin {
+    /// Route this pin to its EXTI line. Each line serves the pin with the
+    /// same number on exactly one port.
+    fn make_interrupt_source(&mut self, afio: &mut Afio);
+
+    /// Generate interrupt on rising edge, falling edge or both
+    fn trigger_on_edge(&mut self, exti: &mut EXTI, level: Edge);
+
+    /// Enable external interrupts from this pin.
+    fn enable_interrupt(&mut self, exti: &mut EXTI);
+
+    /// Disable external interrupts from this pin
+    fn disable_interrupt(&mut self, exti: &mut EXTI);
+
+    /// Clear the interrupt pending bit for this pin
+    fn clear_interrupt_pending_bit(&mut self);
+
+    /// Reads the interrupt pending bit for this pin
+    fn check_interrupt(&self) -> bool;
+}
+
+impl<PIN> ExtiPin for PIN
+where
+    PIN: PinExt,
+    PIN::Mode: marker::Interruptable,
+{
+    #[inline(always)]
+    fn make_interrupt_source(&mut self, afio: &mut Afio) {
+        let i = self.pin_id();
+        let port = self.port_id() as u32;
+        let offset = 4 * (i % 4);
+        let rb = &afio.rb;
+        unsafe {
+            match i {
+                0..=3 => rb
+                    .exticr1
+                    .modify(|r, w| w.bits((r.bits() & !(0xf << offset)) | (port << offset))),
+                4..=7 => rb
+                    .exticr2
+                    .modify(|r, w| w.bits((r.bits() & !(0xf << offset)) | (port << offset))),
+                8..=11 => rb
+                    .exticr3
+                    .modify(|r, w| w.bits((r.bits() & !(0xf << offset)) | (port << offset))),
+                12..=15 => rb
+                    .exticr4
+                    .modify(|r, w| w.bits((r.bits() & !(0xf << offset)) | (port << offset))),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn trigger_on_edge(&mut self, exti: &mut EXTI, edge: Edge) {
+        let i = self.pin_id();
+        unsafe {
+            match edge {
+                Edge::Rising => {
+                    exti.rtenr.modify(|r, w| w.bits(r.bits() | (1 << i)));
+                    exti.ftenr.modify(|r, w| w.bits(r.bits() & !(1 << i)));
+                }
+                Edge::Falling => {
+                    exti.ftenr.modify(|r, w| w.bits(r.bits() | (1 << i)));
+                    exti.rtenr.modify(|r, w| w.bits(r.bits() & !(1 << i)));
+                }
+                Edge::RisingFalling => {
+                    exti.rtenr.modify(|r, w| w.bits(r.bits() | (1 << i)));
+                    exti.ftenr.modify(|r, w| w.bits(r.bits() | (1 << i)));
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn enable_interrupt(&mut self, exti: &mut EXTI) {
+        exti.intenr
+            .modify(|r, w| unsafe { w.bits(r.bits() | (1 << self.pin_id())) });
+    }
+
+    #[inline(always)]
+    fn disable_interrupt(&mut self, exti: &mut EXTI) {
+        exti.intenr
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << self.pin_id())) });
+    }
+
+    #[inline(always)]
+    fn clear_interrupt_pending_bit(&mut self) {
+        // NOTE(unsafe) write 1 to clear, other lines are left alone
+        unsafe { (*EXTI::ptr()).intfr.write(|w| w.bits(1 << self.pin_id())) };
+    }
+
+    #[inline(always)]
+    fn check_interrupt(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { ((*EXTI::ptr()).intfr.read().bits() & (1 << self.pin_id())) != 0 }
+    }
+}
@@ -0,0 +1,191 @@
+//! Async edge and level waits on EXTI lines
+//!
+//! The HAL doesn't own the interrupt handlers: call [`on_exti_interrupt`]
+//! from each EXTI handler serving an [`ExtiInput`], and unmask those
+//! interrupts in the PFIC.
+//!
+//! ```
+//! #[interrupt]
+//! fn EXTI0() {
+//!     gpio::on_exti_interrupt();
+//! }
+//!
+//! let mut button = ExtiInput::new(gpioa.pa0.into_pull_up_input(), &mut afio);
+//! button.wait_for_falling_edge().await.unwrap();
+//! ```
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+
+use embedded_hal_async::digital::Wait;
+use embedded_hal_one::digital::{ErrorType, InputPin};
+use riscv::interrupt::{self, Mutex};
+
+use super::{Edge, ExtiPin, PinExt};
+use crate::afio::Afio;
+use crate::hal::digital::v2 as hal02;
+use crate::pac::EXTI;
+
+const NO_WAKER: Mutex<RefCell<Option<Waker>>> = Mutex::new(RefCell::new(None));
+static WAKERS: [Mutex<RefCell<Option<Waker>>>; 16] = [NO_WAKER; 16];
+
+/// Wakes the [`ExtiInput`]s whose lines fired
+///
+/// Masks the pending GPIO lines and clears their flags, the waiting futures
+/// take a masked line as their event.
+pub fn on_exti_interrupt() {
+    interrupt::free(|cs| {
+        // NOTE(unsafe) the EXTI registers are only modified in critical
+        // sections
+        let exti = unsafe { &*EXTI::ptr() };
+        let pending = exti.intfr.read().bits() & exti.intenr.read().bits() & 0xffff;
+        exti.intenr
+            .modify(|r, w| unsafe { w.bits(r.bits() & !pending) });
+        exti.intfr.write(|w| unsafe { w.bits(pending) });
+
+        for (line, waker) in WAKERS.iter().enumerate() {
+            if pending & (1 << line) != 0 {
+                if let Some(waker) = waker.borrow(cs).take() {
+                    waker.wake();
+                }
+            }
+        }
+    });
+}
+
+/// An input pin that can be awaited through its EXTI line
+pub struct ExtiInput<PIN> {
+    pin: PIN,
+}
+
+impl<PIN> ExtiInput<PIN>
+where
+    PIN: ExtiPin + PinExt + hal02::InputPin<Error = Infallible>,
+{
+    /// Routes the EXTI line of the pin number to `pin`
+    pub fn new(mut pin: PIN, afio: &mut Afio) -> Self {
+        pin.make_interrupt_source(afio);
+        ExtiInput { pin }
+    }
+
+    /// Disables the line and releases the pin
+    pub fn release(self) -> PIN {
+        self.disarm();
+        self.pin
+    }
+
+    fn arm(&self, edge: Edge) {
+        let mask = 1 << self.pin.pin_id();
+        interrupt::free(|_| {
+            // NOTE(unsafe) the EXTI registers are only modified in critical
+            // sections
+            let exti = unsafe { &*EXTI::ptr() };
+            let (rising, falling) = match edge {
+                Edge::Rising => (mask, 0),
+                Edge::Falling => (0, mask),
+                Edge::RisingFalling => (mask, mask),
+            };
+            exti.rtenr
+                .modify(|r, w| unsafe { w.bits(r.bits() & !mask | rising) });
+            exti.ftenr
+                .modify(|r, w| unsafe { w.bits(r.bits() & !mask | falling) });
+            exti.intfr.write(|w| unsafe { w.bits(mask) });
+            exti.intenr
+                .modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        });
+    }
+
+    fn disarm(&self) {
+        let mask = 1 << self.pin.pin_id();
+        interrupt::free(|_| {
+            // NOTE(unsafe) the EXTI registers are only modified in critical
+            // sections
+            let exti = unsafe { &*EXTI::ptr() };
+            exti.intenr
+                .modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        });
+    }
+
+    async fn fired(&self) {
+        let line = self.pin.pin_id();
+        poll_fn(|cx| {
+            interrupt::free(|cs| {
+                // NOTE(unsafe) atomic read with no side effects
+                let intenr = unsafe { (*EXTI::ptr()).intenr.read().bits() };
+                if intenr & (1 << line) == 0 {
+                    Poll::Ready(())
+                } else {
+                    WAKERS[line as usize]
+                        .borrow(cs)
+                        .replace(Some(cx.waker().clone()));
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    async fn wait_for_edge(&mut self, edge: Edge) {
+        self.arm(edge);
+        self.fired().await
+    }
+
+    async fn wait_for_level(&mut self, high: bool) {
+        self.arm(if high { Edge::Rising } else { Edge::Falling });
+        // Armed first, so an edge right after the check isn't missed
+        if self.pin.is_high() == Ok(high) {
+            self.disarm();
+            return;
+        }
+        self.fired().await
+    }
+}
+
+impl<PIN> ErrorType for ExtiInput<PIN> {
+    type Error = Infallible;
+}
+
+impl<PIN> InputPin for ExtiInput<PIN>
+where
+    PIN: ExtiPin + PinExt + hal02::InputPin<Error = Infallible>,
+{
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        self.pin.is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        self.pin.is_low()
+    }
+}
+
+impl<PIN> Wait for ExtiInput<PIN>
+where
+    PIN: ExtiPin + PinExt + hal02::InputPin<Error = Infallible>,
+{
+    async fn wait_for_high(&mut self) -> Result<(), Infallible> {
+        self.wait_for_level(true).await;
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Infallible> {
+        self.wait_for_level(false).await;
+        Ok(())
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Infallible> {
+        self.wait_for_edge(Edge::Rising).await;
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Infallible> {
+        self.wait_for_edge(Edge::Falling).await;
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Infallible> {
+        self.wait_for_edge(Edge::RisingFalling).await;
+        Ok(())
+    }
+}
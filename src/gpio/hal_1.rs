@@ -0,0 +1,100 @@
+//! embedded-hal 1.0 digital traits, on top of the 0.2 ones
+
+use core::convert::Infallible;
+
+use embedded_hal_one::digital::{
+    Error, ErrorKind, ErrorType, InputPin, OutputPin, StatefulOutputPin,
+};
+
+use super::{marker, DynamicPin, ErasedPin, Output, PartiallyErasedPin, Pin, PinModeError};
+use crate::hal::digital::v2 as hal02;
+
+impl Error for PinModeError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+macro_rules! impl_hal_1 {
+    ($({$($gen:tt)*} $Pin:ty, $Out:ty, $In:ty;)+) => {
+        $(
+            impl<$($gen)* MODE> ErrorType for $Pin {
+                type Error = Infallible;
+            }
+
+            impl<$($gen)* MODE> OutputPin for $Out {
+                #[inline(always)]
+                fn set_high(&mut self) -> Result<(), Self::Error> {
+                    hal02::OutputPin::set_high(self)
+                }
+
+                #[inline(always)]
+                fn set_low(&mut self) -> Result<(), Self::Error> {
+                    hal02::OutputPin::set_low(self)
+                }
+            }
+
+            impl<$($gen)* MODE> StatefulOutputPin for $Out {
+                #[inline(always)]
+                fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+                    hal02::StatefulOutputPin::is_set_high(self)
+                }
+
+                #[inline(always)]
+                fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+                    hal02::StatefulOutputPin::is_set_low(self)
+                }
+            }
+
+            impl<$($gen)* MODE> InputPin for $In
+            where
+                MODE: marker::Readable,
+            {
+                #[inline(always)]
+                fn is_high(&mut self) -> Result<bool, Self::Error> {
+                    hal02::InputPin::is_high(self)
+                }
+
+                #[inline(always)]
+                fn is_low(&mut self) -> Result<bool, Self::Error> {
+                    hal02::InputPin::is_low(self)
+                }
+            }
+        )+
+    };
+}
+
+impl_hal_1! {
+    {const P: char, const N: u8,}
+        Pin<P, N, MODE>, Pin<P, N, Output<MODE>>, Pin<P, N, MODE>;
+    {const P: char,}
+        PartiallyErasedPin<P, MODE>,
+        PartiallyErasedPin<P, Output<MODE>>,
+        PartiallyErasedPin<P, MODE>;
+    {}
+        ErasedPin<MODE>, ErasedPin<Output<MODE>>, ErasedPin<MODE>;
+}
+
+impl<const P: char, const N: u8> ErrorType for DynamicPin<P, N> {
+    type Error = PinModeError;
+}
+
+impl<const P: char, const N: u8> OutputPin for DynamicPin<P, N> {
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        hal02::OutputPin::set_high(self)
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        hal02::OutputPin::set_low(self)
+    }
+}
+
+impl<const P: char, const N: u8> InputPin for DynamicPin<P, N> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        hal02::InputPin::is_high(self)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        hal02::InputPin::is_low(self)
+    }
+}
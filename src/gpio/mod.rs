@@ -9,10 +9,28 @@
 //! - PushPull Output
 //! - Alternate Function (input or output)
 //!
-//! Power On: Floating Input except for some Alternate Function
+//! Power On: Floating Input except for PA13 and PA14, which are handed out
+//! in the [`Debugger`] state
+//!
+//! [`Pins::new`] splits all ports at once, into a single struct holding
+//! every pin.
+//!
+//! Pins handed out by `split` are in the [`Unconfigured`] state and must be
+//! converted into one of the modes above before they can be read or driven.
+//!
+//! Pull-up and pull-down resistors only apply to inputs, open-drain outputs
+//! need an external pull-up, see [`OpenDrain`].
+//!
+//! Output drive is only adjustable through the slew rate ([`Speed`]). The
+//! CH32V30x has no per-pin drive strength or 5V tolerance selection: pins
+//! marked FT in the datasheet pinout are always 5V tolerant, but only in
+//! input and open-drain modes and not while configured as analog. All other
+//! pins must stay below VDD + 0.3V.
 
-// CH32V's GPIO is not toggleable, ToggleableOutputPin.
-use crate::hal::digital::v2::{InputPin, OutputPin, PinState, StatefulOutputPin};
+// CH32V's GPIO has no toggle register, ToggleableOutputPin reads OUTDR back.
+use crate::hal::digital::v2::{
+    InputPin, OutputPin, PinState, StatefulOutputPin, ToggleableOutputPin,
+};
 use crate::pac::{GPIOA, GPIOB, GPIOC, GPIOD, GPIOE};
 use crate::rcc::rec::ResetEnable;
 
@@ -21,6 +39,25 @@ use core::marker::PhantomData;
 
 mod convert;
 pub use convert::PinMode;
+mod dynamic;
+pub use dynamic::{Dynamic, DynamicPin, PinModeError};
+mod erased;
+pub use erased::ErasedPin;
+mod exti;
+pub use exti::{Edge, ExtiPin};
+#[cfg(feature = "async")]
+mod exti_async;
+#[cfg(feature = "async")]
+pub use exti_async::{on_exti_interrupt, ExtiInput};
+#[cfg(feature = "embedded-hal-1")]
+mod hal_1;
+mod partially_erased;
+pub use partially_erased::PartiallyErasedPin;
+mod port;
+pub use port::{Port, PortSnapshot};
+
+#[cfg(test)]
+mod tests;
 
 /// Extension trait to split a GPIO peripheral into independent pins and
 /// registers
@@ -35,6 +72,9 @@ pub trait GpioExt {
     /// (ZSTs) representing individual pins. These are public
     /// members of the return type.
     ///
+    /// The port clock is enabled and the port is reset, so the pins are
+    /// usable right away.
+    ///
     /// ```
     /// let device_peripherals = ch32xxx::Peripherals.take().unwrap();
     /// let ccdr = ...; // From RCC
@@ -44,6 +84,13 @@ pub trait GpioExt {
     /// let pa0 = gpioa.pa0; // Pin 0
     /// ```
     fn split(self, prec: Self::Rec) -> Self::Parts;
+
+    /// As [`split`](GpioExt::split), but leaves the port clock and
+    /// configuration untouched.
+    ///
+    /// The pins do nothing until the port clock is enabled through `prec`,
+    /// which is handed back for that purpose.
+    fn split_without_enable(self, prec: Self::Rec) -> (Self::Parts, Self::Rec);
 }
 
 /// Id, port and mode for any pin
@@ -56,6 +103,13 @@ pub trait PinExt {
     fn port_id(&self) -> u8;
 }
 
+/// Unconfigured mode (type state)
+///
+/// The state of a pin right after `split`. The hardware reset state is
+/// floating input (or a debug alternate function), but the HAL makes no
+/// promise about it, so no I/O traits are implemented for this state.
+pub struct Unconfigured;
+
 /// Some alternate mode (type state)
 pub struct Alternate<Otype = PushPull>(PhantomData<Otype>);
 
@@ -80,17 +134,39 @@ pub struct Output<MODE = PushPull> {
 pub struct PushPull;
 
 /// Open drain input or output (type state)
+///
+/// An open-drain output stays readable: `InputPin` samples `INDR`, the
+/// actual line level, so it reports low whenever another device pulls a
+/// shared bus down, regardless of what the pin itself drives.
+///
+/// The internal pull-up and pull-down resistors are only connected in input
+/// mode, the port has no way to combine them with an output. A released
+/// open-drain line is only brought high by an external pull-up, which is
+/// mandatory for buses such as I2C. The internal pull-ups (around 40k) would
+/// be too weak for them anyway: with the few tens of pF of a bus, the line
+/// takes microseconds to rise.
+///
+/// When a slow line is acceptable, a [`DynamicPin`] can emulate an open-drain
+/// output with pull-up, switching between
+/// [`make_pull_up_input`](DynamicPin::make_pull_up_input) to release the line
+/// and [`make_open_drain_output_in_state(PinState::Low)`](DynamicPin::make_open_drain_output_in_state)
+/// to pull it low.
 pub struct OpenDrain;
 
 /// Analog mode (type state)
 pub struct Analog;
 
-/// JTAG/SWD mote (type state)
-pub type Debugger = Alternate<PushPull>;
+/// Debug interface mode (type state)
+///
+/// PA13 (SWDIO) and PA14 (SWCLK) belong to the 2-wire debug interface
+/// after reset. Pins in this state have no `into_*` conversions, they are
+/// only released by [`Afio::deactivate_swj`](crate::afio::Afio::deactivate_swj),
+/// which disables debug access until the next reset.
+pub struct Debugger;
 
 mod marker {
-    // /// Marker trait that show if `ExtiPin` can be implemented
-    // pub trait Interruptable {}
+    /// Marker trait that show if `ExtiPin` can be implemented
+    pub trait Interruptable {}
     /// Marker trait for readable pin modes
     pub trait Readable {}
     /// Marker trait for slew rate configurable pin modes
@@ -101,8 +177,8 @@ mod marker {
     pub trait NotAlt {}
 }
 
-// impl<MODE> marker::Interruptable for Output<MODE> {}
-// impl marker::Interruptable for Input {}
+impl<MODE> marker::Interruptable for Output<MODE> {}
+impl<IType> marker::Interruptable for Input<IType> {}
 impl<IType> marker::Readable for Input<IType> {}
 impl marker::Readable for Output<OpenDrain> {}
 impl<IType> marker::Active for Input<IType> {}
@@ -116,6 +192,7 @@ impl marker::NotAlt for Analog {}
 
 /// GPIO Pin speed selection
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Speed {
     /// Low speed, 2MHz
     Low = 0b10,
@@ -125,12 +202,52 @@ pub enum Speed {
     High = 0b11,
 }
 
+impl Speed {
+    /// Decodes the MODE bits of pin `n` from its CFGLR/CFGHR value
+    fn from_cfgr(cfgr: u32, n: u8) -> Speed {
+        let offset = 4 * (n % 8);
+
+        match (cfgr >> offset) & 0b11 {
+            0b01 => Speed::Medium,
+            0b11 => Speed::High,
+            // 0b00 means input, which an output-capable mode never has
+            _ => Speed::Low,
+        }
+    }
+}
+
+/// Level change between two consecutive samples of a pin
+///
+/// Used for edge detection by polling. Unlike EXTI, polling misses pulses
+/// shorter than the sampling period.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Transition {
+    /// Level did not change
+    None,
+    /// Low to high
+    Rising,
+    /// High to low
+    Falling,
+}
+
+impl Transition {
+    /// Compares two consecutive samples
+    pub fn detect(prev: PinState, now: PinState) -> Self {
+        match (prev, now) {
+            (PinState::Low, PinState::High) => Transition::Rising,
+            (PinState::High, PinState::Low) => Transition::Falling,
+            _ => Transition::None,
+        }
+    }
+}
+
 /// Generic pin type
 ///
 /// - `MODE` is one of the pin modes (see [Modes](crate::gpio#modes) section).
 /// - `P` is port name: `A` for GPIOA, `B` for GPIOB, etc.
 /// - `N` is pin number: from `0` to `15`.
-pub struct Pin<const P: char, const N: u8, MODE = Analog> {
+pub struct Pin<const P: char, const N: u8, MODE = Unconfigured> {
     _mode: PhantomData<MODE>,
 }
 impl<const P: char, const N: u8, MODE> Pin<P, N, MODE> {
@@ -158,22 +275,7 @@ where
 {
     /// Set pin speed
     pub fn set_speed(&mut self, speed: Speed) {
-        let offset = 4 * { N } % 32;
-
-        // lower ports
-        if N < 8 {
-            unsafe {
-                (*Gpio::<P>::ptr()).cfglr.modify(|r, w| {
-                    w.bits((r.bits() & !(0b11 << offset)) | ((speed as u32) << offset))
-                });
-            }
-        } else {
-            unsafe {
-                (*Gpio::<P>::ptr()).cfghr.modify(|r, w| {
-                    w.bits((r.bits() & !(0b11 << offset)) | ((speed as u32) << offset))
-                });
-            }
-        }
+        Gpio::<P>::modify_cfgr(N, 0b11, speed as u32);
     }
 
     /// Set pin speed
@@ -181,9 +283,168 @@ where
         self.set_speed(speed);
         self
     }
+
+    /// Get pin speed
+    pub fn get_speed(&self) -> Speed {
+        // NOTE(unsafe) atomic read with no side effects
+        let cfgr = unsafe {
+            if N < 8 {
+                (*Gpio::<P>::ptr()).cfglr.read().bits()
+            } else {
+                (*Gpio::<P>::ptr()).cfghr.read().bits()
+            }
+        };
+
+        Speed::from_cfgr(cfgr, N)
+    }
 }
 
-// TODO: erased pin
+impl<const P: char, const N: u8, MODE> Pin<P, N, MODE> {
+    /// Erases the pin number and the port from the type
+    ///
+    /// This is useful when you want to collect the pins into an array where you
+    /// need all the elements to have the same type
+    pub fn erase(self) -> ErasedPin<MODE> {
+        ErasedPin::new(P as u8 - b'A', N)
+    }
+
+    /// Erases the pin number from the type
+    ///
+    /// This is useful when you want to collect the pins into an array where you
+    /// need all the elements to have the same type
+    pub fn erase_number(self) -> PartiallyErasedPin<P, MODE> {
+        PartiallyErasedPin::new(N)
+    }
+
+    /// Freezes the configuration of this pin until the next reset.
+    ///
+    /// Mode and speed can't be changed afterwards, the `into_*` conversions
+    /// silently stop having an effect. This is irreversible: only a reset
+    /// unlocks the pin.
+    ///
+    /// The lock key sequence can only succeed once per port, which also
+    /// freezes the lock state of the other pins of the port. Use
+    /// [`Port::lock`] to lock several pins of a port. Returns whether the
+    /// lock took effect.
+    pub fn lock(&mut self) -> bool {
+        Gpio::<P>::lock(1 << N)
+    }
+}
+
+impl<const P: char, const N: u8, MODE> OutputPin for Pin<P, N, Output<MODE>> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self._set_high();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self._set_low();
+        Ok(())
+    }
+}
+
+impl<const P: char, const N: u8, MODE> StatefulOutputPin for Pin<P, N, Output<MODE>> {
+    #[inline(always)]
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(!self._is_set_low())
+    }
+
+    #[inline(always)]
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(self._is_set_low())
+    }
+}
+
+impl<const P: char, const N: u8, MODE> Pin<P, N, Output<MODE>> {
+    /// Drives the pin to the opposite of its current output level.
+    ///
+    /// There is no toggle register, so this reads `OUTDR` back first and is
+    /// not atomic against another context driving the same pin.
+    #[inline(always)]
+    pub fn toggle(&mut self) {
+        if self._is_set_low() {
+            self._set_high()
+        } else {
+            self._set_low()
+        }
+    }
+}
+
+impl<const P: char, const N: u8, Otype> Pin<P, N, Alternate<Otype>> {
+    /// Writes the output latch (`OUTDR`) of the pin.
+    ///
+    /// The peripheral drives the pin in alternate mode, so this has no
+    /// effect on the line until the pin is turned back into a GPIO output,
+    /// e.g. to leave a bus line parked high when the peripheral lets go of
+    /// it. To drive a safe level *before* the peripheral takes over, start
+    /// from [`into_push_pull_output_in_state`](Pin::into_push_pull_output_in_state)
+    /// and switch to alternate mode once the peripheral is configured.
+    #[inline(always)]
+    pub fn set_latch(&mut self, state: PinState) {
+        self._set_state(state)
+    }
+
+    /// Reads the output latch (`OUTDR`) of the pin
+    #[inline(always)]
+    pub fn latch(&self) -> PinState {
+        PinState::from(!self._is_set_low())
+    }
+}
+
+impl<const P: char, const N: u8, MODE> ToggleableOutputPin for Pin<P, N, Output<MODE>> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        Pin::toggle(self);
+        Ok(())
+    }
+}
+
+impl<const P: char, const N: u8, MODE> Pin<P, N, MODE>
+where
+    MODE: marker::Readable,
+{
+    /// Samples the pin and compares it with the previous sample `prev`.
+    ///
+    /// Returns the transition and the new sample, to be passed as `prev` on
+    /// the next call.
+    #[inline]
+    pub fn sample_transition(&self, prev: PinState) -> (Transition, PinState) {
+        let now = self.get_state();
+        (Transition::detect(prev, now), now)
+    }
+
+    /// Samples the level on the pin from `INDR`.
+    ///
+    /// For an open-drain output this is the bus level, see
+    /// [`is_set_low`](StatefulOutputPin::is_set_low) for the driven level.
+    #[inline(always)]
+    pub fn get_state(&self) -> PinState {
+        PinState::from(!self._is_low())
+    }
+}
+
+impl<const P: char, const N: u8, MODE> InputPin for Pin<P, N, MODE>
+where
+    MODE: marker::Readable,
+{
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(!self._is_low())
+    }
+
+    #[inline(always)]
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(self._is_low())
+    }
+}
 
 impl<const P: char, const N: u8, MODE> Pin<P, N, MODE> {
     /// Set the output of the pin regardless of its mode.
@@ -219,9 +480,190 @@ impl<const P: char, const N: u8, MODE> Pin<P, N, MODE> {
     }
 }
 
+macro_rules! gpio {
+    ($($GPIOX:ident, $gpiox:ident, $port_id:literal, $Rec:ident, [
+        $($PXi:ident: ($pxi:ident, $i:literal, $MODE:ty),)+
+    ];)+) => {
+        $(
+            /// GPIO
+            pub mod $gpiox {
+                use super::*;
+                use crate::rcc::rec::$Rec;
+
+                /// GPIO parts
+                pub struct Parts {
+                    $(
+                        /// Pin
+                        pub $pxi: $PXi<$MODE>,
+                    )+
+                    /// Whole-port access
+                    pub port: Port<$port_id>,
+                }
+
+                impl GpioExt for $GPIOX {
+                    type Parts = Parts;
+                    type Rec = $Rec;
+
+                    fn split(self, prec: $Rec) -> Parts {
+                        prec.enable().reset();
+                        self.split_without_enable(prec).0
+                    }
+
+                    fn split_without_enable(self, prec: $Rec) -> (Parts, $Rec) {
+                        let parts = Parts {
+                            $(
+                                $pxi: Pin::new(),
+                            )+
+                            port: Port { _0: () },
+                        };
+                        (parts, prec)
+                    }
+                }
+
+                $(
+                    #[doc=concat!("P", $port_id, $i, " pin")]
+                    pub type $PXi<MODE = Unconfigured> = Pin<$port_id, $i, MODE>;
+                )+
+            }
+
+            pub use $gpiox::{$($PXi,)+};
+        )+
+
+        /// The pins of all GPIO ports, for when splitting them one by one is
+        /// not worth it
+        ///
+        /// ```
+        /// let pins = gpio::Pins::new(
+        ///     (dp.GPIOA, ccdr.peripheral.GPIOA),
+        ///     (dp.GPIOB, ccdr.peripheral.GPIOB),
+        ///     (dp.GPIOC, ccdr.peripheral.GPIOC),
+        ///     (dp.GPIOD, ccdr.peripheral.GPIOD),
+        ///     (dp.GPIOE, ccdr.peripheral.GPIOE),
+        /// );
+        ///
+        /// let led = pins.pc13.into_push_pull_output();
+        /// ```
+        pub struct Pins {
+            $($(
+                /// Pin
+                pub $pxi: $PXi<$MODE>,
+            )+)+
+        }
+
+        impl Pins {
+            /// [`split`](GpioExt::split)s every port, which enables and
+            /// resets all of them
+            pub fn new($($gpiox: ($GPIOX, crate::rcc::rec::$Rec),)+) -> Self {
+                $(
+                    let $gpiox = $gpiox.0.split($gpiox.1);
+                )+
+                Pins {
+                    $($(
+                        $pxi: $gpiox.$pxi,
+                    )+)+
+                }
+            }
+        }
+    };
+}
+
+gpio! {
+    GPIOA, gpioa, 'A', Gpioa, [
+        PA0: (pa0, 0, Unconfigured),
+        PA1: (pa1, 1, Unconfigured),
+        PA2: (pa2, 2, Unconfigured),
+        PA3: (pa3, 3, Unconfigured),
+        PA4: (pa4, 4, Unconfigured),
+        PA5: (pa5, 5, Unconfigured),
+        PA6: (pa6, 6, Unconfigured),
+        PA7: (pa7, 7, Unconfigured),
+        PA8: (pa8, 8, Unconfigured),
+        PA9: (pa9, 9, Unconfigured),
+        PA10: (pa10, 10, Unconfigured),
+        PA11: (pa11, 11, Unconfigured),
+        PA12: (pa12, 12, Unconfigured),
+        PA13: (pa13, 13, Debugger),
+        PA14: (pa14, 14, Debugger),
+        PA15: (pa15, 15, Unconfigured),
+    ];
+    GPIOB, gpiob, 'B', Gpiob, [
+        PB0: (pb0, 0, Unconfigured),
+        PB1: (pb1, 1, Unconfigured),
+        PB2: (pb2, 2, Unconfigured),
+        PB3: (pb3, 3, Unconfigured),
+        PB4: (pb4, 4, Unconfigured),
+        PB5: (pb5, 5, Unconfigured),
+        PB6: (pb6, 6, Unconfigured),
+        PB7: (pb7, 7, Unconfigured),
+        PB8: (pb8, 8, Unconfigured),
+        PB9: (pb9, 9, Unconfigured),
+        PB10: (pb10, 10, Unconfigured),
+        PB11: (pb11, 11, Unconfigured),
+        PB12: (pb12, 12, Unconfigured),
+        PB13: (pb13, 13, Unconfigured),
+        PB14: (pb14, 14, Unconfigured),
+        PB15: (pb15, 15, Unconfigured),
+    ];
+    GPIOC, gpioc, 'C', Gpioc, [
+        PC0: (pc0, 0, Unconfigured),
+        PC1: (pc1, 1, Unconfigured),
+        PC2: (pc2, 2, Unconfigured),
+        PC3: (pc3, 3, Unconfigured),
+        PC4: (pc4, 4, Unconfigured),
+        PC5: (pc5, 5, Unconfigured),
+        PC6: (pc6, 6, Unconfigured),
+        PC7: (pc7, 7, Unconfigured),
+        PC8: (pc8, 8, Unconfigured),
+        PC9: (pc9, 9, Unconfigured),
+        PC10: (pc10, 10, Unconfigured),
+        PC11: (pc11, 11, Unconfigured),
+        PC12: (pc12, 12, Unconfigured),
+        PC13: (pc13, 13, Unconfigured),
+        PC14: (pc14, 14, Unconfigured),
+        PC15: (pc15, 15, Unconfigured),
+    ];
+    GPIOD, gpiod, 'D', Gpiod, [
+        PD0: (pd0, 0, Unconfigured),
+        PD1: (pd1, 1, Unconfigured),
+        PD2: (pd2, 2, Unconfigured),
+        PD3: (pd3, 3, Unconfigured),
+        PD4: (pd4, 4, Unconfigured),
+        PD5: (pd5, 5, Unconfigured),
+        PD6: (pd6, 6, Unconfigured),
+        PD7: (pd7, 7, Unconfigured),
+        PD8: (pd8, 8, Unconfigured),
+        PD9: (pd9, 9, Unconfigured),
+        PD10: (pd10, 10, Unconfigured),
+        PD11: (pd11, 11, Unconfigured),
+        PD12: (pd12, 12, Unconfigured),
+        PD13: (pd13, 13, Unconfigured),
+        PD14: (pd14, 14, Unconfigured),
+        PD15: (pd15, 15, Unconfigured),
+    ];
+    GPIOE, gpioe, 'E', Gpioe, [
+        PE0: (pe0, 0, Unconfigured),
+        PE1: (pe1, 1, Unconfigured),
+        PE2: (pe2, 2, Unconfigured),
+        PE3: (pe3, 3, Unconfigured),
+        PE4: (pe4, 4, Unconfigured),
+        PE5: (pe5, 5, Unconfigured),
+        PE6: (pe6, 6, Unconfigured),
+        PE7: (pe7, 7, Unconfigured),
+        PE8: (pe8, 8, Unconfigured),
+        PE9: (pe9, 9, Unconfigured),
+        PE10: (pe10, 10, Unconfigured),
+        PE11: (pe11, 11, Unconfigured),
+        PE12: (pe12, 12, Unconfigured),
+        PE13: (pe13, 13, Unconfigured),
+        PE14: (pe14, 14, Unconfigured),
+        PE15: (pe15, 15, Unconfigured),
+    ];
+}
+
 /// Gpio port helper
 struct Gpio<const P: char>;
 impl<const P: char> Gpio<P> {
+    #[cfg(not(test))]
     const fn ptr() -> *const crate::pac::gpioa::RegisterBlock {
         match P {
             'A' => crate::pac::GPIOA::ptr(),
@@ -232,4 +674,97 @@ impl<const P: char> Gpio<P> {
             _ => panic!("Unknown GPIO port"),
         }
     }
+
+    // Host-side tests run against plain memory instead of the fixed addresses
+    #[cfg(test)]
+    fn ptr() -> *const crate::pac::gpioa::RegisterBlock {
+        assert!(('A'..='E').contains(&P), "Unknown GPIO port");
+        let port = (P as u8 - b'A') as usize;
+        (mock::PORTS.0.get() as *const [u32; mock::REGS]).wrapping_add(port) as *const _
+    }
+}
+
+impl<const P: char> Gpio<P> {
+    /// Replaces the `mask` bits of the `<<CNF:2, MODE:2>>` nibble of pin `n`
+    /// with `bits`.
+    ///
+    /// The nibbles of 8 pins share CFGLR/CFGHR and, unlike the level with
+    /// BSHR/BCR, there is no atomic way to change one of them, so the
+    /// read-modify-write runs in a critical section. Otherwise an interrupt
+    /// reconfiguring another pin of the port in between would be undone.
+    fn modify_cfgr(n: u8, mask: u32, bits: u32) {
+        let offset = 4 * (n % 8);
+        let update = |r: u32| r & !(mask << offset) | (bits << offset);
+        free(|| unsafe {
+            if n < 8 {
+                (*Self::ptr()).cfglr.modify(|r, w| w.bits(update(r.bits())));
+            } else {
+                (*Self::ptr()).cfghr.modify(|r, w| w.bits(update(r.bits())));
+            }
+        })
+    }
+
+    /// Runs the `LCKR` key sequence for the pins in `mask`, returns whether
+    /// the port and all the pins in `mask` are locked afterwards
+    fn lock(mask: u16) -> bool {
+        const LCKK: u32 = 1 << 16;
+        let mask = u32::from(mask);
+        // The sequence is aborted by any other LCKR access in between
+        free(|| {
+            // NOTE(unsafe) LCKR is only written here
+            let lckr = unsafe { &(*Self::ptr()).lckr };
+            lckr.write(|w| unsafe { w.bits(LCKK | mask) });
+            lckr.write(|w| unsafe { w.bits(mask) });
+            lckr.write(|w| unsafe { w.bits(LCKK | mask) });
+            lckr.read();
+            // A port locked earlier reads LCKK with its own pins, which
+            // may not be the ones in `mask`
+            lckr.read().bits() & (LCKK | mask) == LCKK | mask
+        })
+    }
+}
+
+/// Runs `f` with interrupts disabled
+#[cfg(not(test))]
+#[inline(always)]
+fn free<R>(f: impl FnOnce() -> R) -> R {
+    riscv::interrupt::free(|_| f())
+}
+
+// Host-side tests have no interrupts to mask
+#[cfg(test)]
+fn free<R>(f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+/// Register block of the port with id `port`, starting from 0 for GPIOA
+fn port_ptr(port: u8) -> *const crate::pac::gpioa::RegisterBlock {
+    match port {
+        0 => Gpio::<'A'>::ptr(),
+        1 => Gpio::<'B'>::ptr(),
+        2 => Gpio::<'C'>::ptr(),
+        3 => Gpio::<'D'>::ptr(),
+        4 => Gpio::<'E'>::ptr(),
+        _ => panic!("Unknown GPIO port"),
+    }
+}
+
+/// In-memory GPIO register blocks backing [`Gpio::ptr`] in tests
+#[cfg(test)]
+mod mock {
+    use core::cell::UnsafeCell;
+
+    /// CFGLR, CFGHR, INDR, OUTDR, BSHR, BCR, LCKR
+    pub const REGS: usize = 7;
+    pub const CFGLR: usize = 0;
+    pub const CFGHR: usize = 1;
+    pub const INDR: usize = 2;
+    pub const OUTDR: usize = 3;
+    pub const BSHR: usize = 4;
+    pub const BCR: usize = 5;
+
+    pub struct Ports(pub UnsafeCell<[[u32; REGS]; 5]>);
+    unsafe impl Sync for Ports {}
+
+    pub static PORTS: Ports = Ports(UnsafeCell::new([[0; REGS]; 5]));
 }
@@ -0,0 +1,132 @@
+use super::*;
+
+/// Whole-port access
+///
+/// Samples or drives several pins of port `P` with a single register access,
+/// e.g. for a parallel bus where all bits must change or be sampled at the
+/// same instant.
+///
+/// A `Port` does not own any pin and can't change pin modes; the pins must
+/// be configured through their [`Pin`] first. Writes only touch the pins in
+/// their `mask`, keep it to the pins the `Port` is meant to drive.
+///
+/// [`split`](GpioExt::split) hands one out as `port`, next to the pins:
+///
+/// ```
+/// let gpiod = dp.GPIOD.split(ccdr.peripheral.GPIOD);
+/// let bus = gpiod.port.read_all() & 0x00FF;
+/// ```
+pub struct Port<const P: char> {
+    pub(super) _0: (),
+}
+
+impl<const P: char> Port<P> {
+    /// Creates another handle to port `P`, next to the one from
+    /// [`split`](GpioExt::split)
+    ///
+    /// # Safety
+    ///
+    /// Writes affect every pin in `mask`. The caller must make sure no other
+    /// code drives those pins at the same time.
+    pub unsafe fn steal() -> Self {
+        Port { _0: () }
+    }
+
+    /// Reads the input level of all 16 pins (`INDR`) at once
+    #[inline(always)]
+    pub fn read_all(&self) -> u16 {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*Gpio::<P>::ptr()).indr.read().bits() as u16 }
+    }
+
+    /// Samples the input level of all 16 pins at once, to query them one by
+    /// one without skew between them
+    ///
+    /// ```
+    /// let keys = port.snapshot();
+    /// if keys.is_low(3) && keys.is_low(4) {
+    ///     // both pressed
+    /// }
+    /// ```
+    #[inline(always)]
+    pub fn snapshot(&self) -> PortSnapshot<P> {
+        PortSnapshot {
+            bits: self.read_all(),
+        }
+    }
+
+    /// Reads the output latch of all 16 pins (`OUTDR`) at once
+    #[inline(always)]
+    pub fn read_output(&self) -> u16 {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*Gpio::<P>::ptr()).outdr.read().bits() as u16 }
+    }
+
+    /// Drives the pins in `mask` to the matching bits of `value` with a
+    /// single atomic `BSHR` write. Pins outside `mask` are left alone.
+    #[inline(always)]
+    pub fn write_all(&mut self, mask: u16, value: u16) {
+        let set = u32::from(value & mask);
+        let reset = u32::from(!value & mask);
+        // NOTE(unsafe) atomic write to a stateless register
+        unsafe {
+            (*Gpio::<P>::ptr())
+                .bshr
+                .write(|w| w.bits(set | (reset << 16)))
+        }
+    }
+
+    /// Drives the pins in `mask` high
+    #[inline(always)]
+    pub fn set_high(&mut self, mask: u16) {
+        self.write_all(mask, 0xffff);
+    }
+
+    /// Drives the pins in `mask` low
+    #[inline(always)]
+    pub fn set_low(&mut self, mask: u16) {
+        self.write_all(mask, 0);
+    }
+
+    /// Freezes the configuration of the pins in `mask` until the next reset.
+    ///
+    /// This is irreversible: only a reset unlocks the pins. The key sequence
+    /// can only succeed once per port, so all pins that need locking must be
+    /// in `mask`. Returns whether the lock took effect.
+    pub fn lock(&mut self, mask: u16) -> bool {
+        Gpio::<P>::lock(mask)
+    }
+}
+
+/// Input levels of port `P` sampled by [`Port::snapshot`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PortSnapshot<const P: char> {
+    bits: u16,
+}
+
+impl<const P: char> PortSnapshot<P> {
+    /// Whether pin `n` was high
+    #[inline(always)]
+    pub fn is_high(&self, n: u8) -> bool {
+        self.bits & (1 << n) != 0
+    }
+
+    /// Whether pin `n` was low
+    #[inline(always)]
+    pub fn is_low(&self, n: u8) -> bool {
+        !self.is_high(n)
+    }
+
+    /// Level of `pin` when the snapshot was taken
+    #[inline(always)]
+    pub fn pin_state<const N: u8, MODE>(&self, _pin: &Pin<P, N, MODE>) -> PinState {
+        PinState::from(self.is_high(N))
+    }
+
+    /// The levels of all pins, bit `n` for pin `n`
+    #[inline(always)]
+    pub fn bits(&self) -> u16 {
+        self.bits
+    }
+}
@@ -0,0 +1,282 @@
+extern crate std;
+
+use std::sync::{Mutex, MutexGuard};
+
+use super::mock::{self, BCR, BSHR, CFGHR, CFGLR, INDR, OUTDR};
+use super::*;
+
+// The mock ports are shared by every test
+static LOCK: Mutex<()> = Mutex::new(());
+
+fn lock() -> MutexGuard<'static, ()> {
+    let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe { *mock::PORTS.0.get() = [[0; mock::REGS]; 5] };
+    guard
+}
+
+fn reg(port: char, reg: usize) -> u32 {
+    unsafe { (*mock::PORTS.0.get())[(port as u8 - b'A') as usize][reg] }
+}
+
+fn set_reg(port: char, reg: usize, value: u32) {
+    unsafe { (*mock::PORTS.0.get())[(port as u8 - b'A') as usize][reg] = value }
+}
+
+/// Config nibble of pin `n`, and the rest of both config registers
+fn cfg(port: char, n: u8) -> (u32, u64) {
+    let all = u64::from(reg(port, CFGLR)) | (u64::from(reg(port, CFGHR)) << 32);
+    let offset = 4 * u32::from(n);
+    (((all >> offset) & 0b1111) as u32, all & !(0b1111 << offset))
+}
+
+fn check_mode<const N: u8, M: PinMode>(cfgr: u32) {
+    let _guard = lock();
+    // neighbours must survive the read-modify-write
+    set_reg('A', CFGLR, 0x4444_4444);
+    set_reg('A', CFGHR, 0x4444_4444);
+    Pin::<'A', N, Unconfigured>::new().mode::<M>();
+
+    let (nibble, rest) = cfg('A', N);
+    assert_eq!(nibble, cfgr, "pin {}", N);
+    assert_eq!(
+        rest,
+        0x4444_4444_4444_4444 & !(0b1111 << (4 * N)),
+        "pin {}",
+        N
+    );
+}
+
+fn check_pull<const N: u8>() {
+    {
+        let _guard = lock();
+        Pin::<'A', N, Unconfigured>::new().mode::<Input<PullUp>>();
+        assert_eq!(reg('A', BSHR), 1 << N);
+        assert_eq!(reg('A', BCR), 0);
+    }
+    {
+        let _guard = lock();
+        Pin::<'A', N, Unconfigured>::new().mode::<Input<PullDown>>();
+        assert_eq!(reg('A', BSHR), 0);
+        assert_eq!(reg('A', BCR), 1 << N);
+    }
+    {
+        let _guard = lock();
+        Pin::<'A', N, Unconfigured>::new().mode::<Input<Floating>>();
+        assert_eq!(reg('A', BSHR), 0);
+        assert_eq!(reg('A', BCR), 0);
+    }
+}
+
+fn check_level<const N: u8>() {
+    let _guard = lock();
+    let mut pin = Pin::<'C', N, Output<PushPull>>::new();
+    pin._set_high();
+    assert_eq!(reg('C', BSHR), 1 << N);
+    pin._set_low();
+    assert_eq!(reg('C', BSHR), 1 << (16 + N));
+
+    set_reg('C', OUTDR, !(1 << N));
+    assert!(pin._is_set_low());
+    set_reg('C', OUTDR, 1 << N);
+    assert!(!pin._is_set_low());
+
+    set_reg('C', INDR, !(1 << N));
+    assert!(pin._is_low());
+    set_reg('C', INDR, 1 << N);
+    assert!(!pin._is_low());
+}
+
+fn check_open_drain_reads_line<const N: u8>() {
+    let _guard = lock();
+    let pin = Pin::<'D', N, Output<OpenDrain>>::new();
+    // Released by us, pulled low by another device
+    set_reg('D', OUTDR, 1 << N);
+    set_reg('D', INDR, !(1 << N));
+    assert_eq!(pin.is_low(), Ok(true));
+    assert_eq!(pin.get_state(), PinState::Low);
+    assert_eq!(pin.is_set_high(), Ok(true));
+
+    set_reg('D', INDR, 1 << N);
+    assert_eq!(pin.get_state(), PinState::High);
+}
+
+fn check_speed<const N: u8>() {
+    for speed in [Speed::Low, Speed::Medium, Speed::High] {
+        let _guard = lock();
+        set_reg('B', CFGLR, 0x4444_4444);
+        set_reg('B', CFGHR, 0x4444_4444);
+        let mut pin = Pin::<'B', N, Output<PushPull>>::new();
+        pin.set_speed(speed);
+
+        let (nibble, rest) = cfg('B', N);
+        assert_eq!(nibble, 0b01_00 | speed as u32, "pin {}", N);
+        assert_eq!(
+            rest,
+            0x4444_4444_4444_4444 & !(0b1111 << (4 * N)),
+            "pin {}",
+            N
+        );
+        assert_eq!(pin.get_speed(), speed, "pin {}", N);
+    }
+}
+
+fn check_with_mode<const N: u8>() {
+    let _guard = lock();
+    // Pulled up input, left over from an earlier owner
+    let cfgr = 0x4444_4444_4444_4444 & !(0b0100 << (4 * N)) | (0b1000 << (4 * N));
+    set_reg('E', CFGLR, cfgr as u32);
+    set_reg('E', CFGHR, (cfgr >> 32) as u32);
+    set_reg('E', OUTDR, 1 << N);
+    let mut pin = Pin::<'E', N, Unconfigured>::new();
+
+    pin.with_push_pull_output_in_state(PinState::Low, |_| {
+        assert_eq!(cfg('E', N).0, 0b00_10, "pin {}", N);
+        assert_eq!(reg('E', BSHR), 1 << (16 + N), "pin {}", N);
+    });
+
+    // Config and latch (the pull-up) restored, neighbours untouched
+    let (nibble, rest) = cfg('E', N);
+    assert_eq!(nibble, 0b10_00, "pin {}", N);
+    assert_eq!(
+        rest,
+        0x4444_4444_4444_4444 & !(0b1111 << (4 * N)),
+        "pin {}",
+        N
+    );
+    assert_eq!(reg('E', BSHR), 1 << N, "pin {}", N);
+}
+
+#[test]
+fn port_write_all_is_one_bshr_write() {
+    let _guard = lock();
+    let mut port = unsafe { Port::<'D'>::steal() };
+    port.write_all(0x00ff, 0x0f3c);
+    assert_eq!(reg('D', BSHR), 0x00c3_003c);
+
+    set_reg('D', INDR, 0xa5a5);
+    assert_eq!(port.read_all(), 0xa5a5);
+}
+
+#[test]
+fn port_snapshot_keeps_levels() {
+    let _guard = lock();
+    let port = unsafe { Port::<'C'>::steal() };
+    set_reg('C', INDR, 0x0009);
+    let snapshot = port.snapshot();
+    set_reg('C', INDR, 0x0000);
+
+    assert!(snapshot.is_high(0) && snapshot.is_high(3));
+    assert!(snapshot.is_low(1) && snapshot.is_low(15));
+    assert_eq!(snapshot.bits(), 0x0009);
+}
+
+macro_rules! for_all_pins {
+    ($f:ident) => {
+        $f::<0>();
+        $f::<1>();
+        $f::<2>();
+        $f::<3>();
+        $f::<4>();
+        $f::<5>();
+        $f::<6>();
+        $f::<7>();
+        $f::<8>();
+        $f::<9>();
+        $f::<10>();
+        $f::<11>();
+        $f::<12>();
+        $f::<13>();
+        $f::<14>();
+        $f::<15>();
+    };
+}
+
+fn check_all_modes<const N: u8>() {
+    check_mode::<N, Input<Floating>>(0b01_00);
+    check_mode::<N, Input<PullUp>>(0b10_00);
+    check_mode::<N, Input<PullDown>>(0b10_00);
+    check_mode::<N, Analog>(0b00_00);
+    check_mode::<N, Output<PushPull>>(0b00_10);
+    check_mode::<N, Output<OpenDrain>>(0b01_10);
+    check_mode::<N, Alternate<PushPull>>(0b10_10);
+    check_mode::<N, Alternate<OpenDrain>>(0b11_10);
+}
+
+#[test]
+fn mode_writes_own_config_nibble() {
+    for_all_pins!(check_all_modes);
+}
+
+#[test]
+fn mode_sets_pull_direction() {
+    for_all_pins!(check_pull);
+}
+
+#[test]
+fn level_masks() {
+    for_all_pins!(check_level);
+}
+
+#[test]
+fn speed_decodes_own_mode_bits() {
+    for n in 0..16u8 {
+        for speed in [Speed::Low, Speed::Medium, Speed::High] {
+            // Every other pin is a floating input, 0b01_00
+            let offset = 4 * (n % 8);
+            let cfgr = 0x4444_4444 & !(0b1111 << offset) | (0b01_00 | speed as u32) << offset;
+            assert_eq!(Speed::from_cfgr(cfgr, n), speed, "pin {}", n);
+        }
+    }
+}
+
+#[test]
+fn speed_lands_in_own_mode_bits() {
+    for_all_pins!(check_speed);
+}
+
+#[test]
+fn with_mode_restores_config_and_latch() {
+    for_all_pins!(check_with_mode);
+}
+
+#[test]
+fn open_drain_output_reads_line_level() {
+    for_all_pins!(check_open_drain_reads_line);
+}
+
+#[test]
+fn transition_detect() {
+    assert_eq!(
+        Transition::detect(PinState::Low, PinState::High),
+        Transition::Rising
+    );
+    assert_eq!(
+        Transition::detect(PinState::High, PinState::Low),
+        Transition::Falling
+    );
+    assert_eq!(
+        Transition::detect(PinState::Low, PinState::Low),
+        Transition::None
+    );
+    assert_eq!(
+        Transition::detect(PinState::High, PinState::High),
+        Transition::None
+    );
+}
+
+#[test]
+fn sample_transition_reads_indr() {
+    let _guard = lock();
+    let pin = Pin::<'A', 3, Input<Floating>>::new();
+
+    set_reg('A', INDR, 1 << 3);
+    let (transition, now) = pin.sample_transition(PinState::Low);
+    assert_eq!((transition, now), (Transition::Rising, PinState::High));
+
+    set_reg('A', INDR, !(1 << 3));
+    let (transition, now) = pin.sample_transition(now);
+    assert_eq!((transition, now), (Transition::Falling, PinState::Low));
+
+    let (transition, _) = pin.sample_transition(now);
+    assert_eq!(transition, Transition::None);
+}
@@ -0,0 +1,583 @@
+//! Inter-Integrated Circuit (I2C) bus, master mode
+//!
+//! ```
+//! let gpiob = dp.GPIOB.split(ccdr.peripheral.GPIOB);
+//!
+//! let mut i2c = I2c::new(
+//!     dp.I2C1,
+//!     (gpiob.pb6, gpiob.pb7),
+//!     Mode::fast(400.kHz()),
+//!     ccdr.peripheral.I2C1,
+//!     &ccdr.clocks,
+//! );
+//!
+//! let mut id = [0];
+//! i2c.write_read(0x76, &[0xD0], &mut id)?;
+//! ```
+//!
+//! Both instances run from PCLK1, which must be between 2MHz and 63MHz. The
+//! remapped I2C1 pins require
+//! [`Afio::remap_i2c1`](crate::afio::Afio::remap_i2c1).
+//!
+//! [`I2c::new`] puts both pins into alternate open-drain mode.
+//! [`I2c::new_from_configured`] takes pins that are already set up and
+//! leaves them alone.
+//!
+//! A slave that was reset in the middle of a read can hold SDA low forever,
+//! and the driver then waits forever for the bus. Set a timeout to get
+//! [`Error::Timeout`] instead, and [`recover`](I2c::recover) the bus:
+//!
+//! ```
+//! let mut i2c = I2c::new(...).with_timeout(10_000.micros());
+//!
+//! if let Err(Error::Timeout) = i2c.write(0x76, &[0xE0, 0xB6]) {
+//!     i2c.recover(&mut delay)?;
+//! }
+//! ```
+
+use riscv::register::mcycle;
+
+use crate::gpio::{Alternate, OpenDrain, Output, Pin, PinMode};
+use crate::hal::blocking::delay::DelayUs;
+use crate::hal::blocking::i2c::{Read, Write, WriteRead};
+use crate::hal::digital::v2::{InputPin, OutputPin};
+use crate::pac::{i2c1, I2C1, I2C2};
+use crate::rcc::rec::{self, ResetEnable};
+use crate::rcc::CoreClocks;
+use crate::time::{Hertz, MicroSeconds};
+
+// STAR1 bits
+const SB: u32 = 1 << 0;
+const ADDR: u32 = 1 << 1;
+const BTF: u32 = 1 << 2;
+const RXNE: u32 = 1 << 6;
+const TXE: u32 = 1 << 7;
+const BERR: u32 = 1 << 8;
+const ARLO: u32 = 1 << 9;
+const AF: u32 = 1 << 10;
+const OVR: u32 = 1 << 11;
+
+/// I2C error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Misplaced start or stop condition
+    Bus,
+    /// Lost arbitration to another master
+    Arbitration,
+    /// No acknowledge from the slave, for the address or a data byte
+    Nack,
+    /// Overrun or underrun
+    Overrun,
+    /// A flag did not show up within the timeout, see
+    /// [`with_timeout`](I2c::with_timeout)
+    Timeout,
+}
+
+/// Fast mode SCL duty cycle, low to high time
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DutyCycle {
+    Ratio2to1,
+    Ratio16to9,
+}
+
+/// Bus speed mode
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// Up to 100kHz
+    Standard { frequency: Hertz },
+    /// Up to 400kHz
+    Fast {
+        frequency: Hertz,
+        duty_cycle: DutyCycle,
+    },
+}
+
+impl Mode {
+    /// Standard mode at `frequency`
+    pub fn standard(frequency: Hertz) -> Self {
+        Mode::Standard { frequency }
+    }
+
+    /// Fast mode at `frequency` with a 2:1 duty cycle
+    pub fn fast(frequency: Hertz) -> Self {
+        Mode::Fast {
+            frequency,
+            duty_cycle: DutyCycle::Ratio2to1,
+        }
+    }
+}
+
+/// An I2C peripheral
+pub trait Instance: crate::Sealed {
+    /// The Reset and Enable control block for this I2C
+    type Rec: ResetEnable;
+
+    /// Pointer to the register block
+    fn ptr() -> *const i2c1::RegisterBlock;
+}
+
+/// A pin that can be used as the SCL line of `I2C`
+pub trait PinScl<I2C> {}
+
+/// A pin that can be used as the SDA line of `I2C`
+pub trait PinSda<I2C> {}
+
+/// A `(SCL, SDA)` pin pair
+pub trait Pins<I2C> {}
+
+impl<I2C, SCL, SDA> Pins<I2C> for (SCL, SDA)
+where
+    SCL: PinScl<I2C>,
+    SDA: PinSda<I2C>,
+{
+}
+
+/// A `(SCL, SDA)` pin pair in any mode, that [`I2c::new`] configures
+pub trait IntoPins<I2C> {
+    /// The pins in the modes the I2C needs
+    type Pins: Pins<I2C>;
+
+    #[doc(hidden)]
+    fn into_pins(self) -> Self::Pins;
+}
+
+impl<I2C, const CP: char, const CN: u8, CM, const DP: char, const DN: u8, DM> IntoPins<I2C>
+    for (Pin<CP, CN, CM>, Pin<DP, DN, DM>)
+where
+    CM: PinMode,
+    DM: PinMode,
+    Pin<CP, CN, Alternate<OpenDrain>>: PinScl<I2C>,
+    Pin<DP, DN, Alternate<OpenDrain>>: PinSda<I2C>,
+{
+    type Pins = (
+        Pin<CP, CN, Alternate<OpenDrain>>,
+        Pin<DP, DN, Alternate<OpenDrain>>,
+    );
+
+    fn into_pins(self) -> Self::Pins {
+        (
+            self.0.into_alternate_open_drain(),
+            self.1.into_alternate_open_drain(),
+        )
+    }
+}
+
+macro_rules! i2c {
+    ($($I2CX:ident: $Rec:ident => {
+        scl: [$(($SclP:literal, $SclN:literal)),*],
+        sda: [$(($SdaP:literal, $SdaN:literal)),*],
+    })+) => {
+        $(
+            impl crate::Sealed for $I2CX {}
+
+            impl Instance for $I2CX {
+                type Rec = rec::$Rec;
+
+                #[inline(always)]
+                fn ptr() -> *const i2c1::RegisterBlock {
+                    $I2CX::ptr()
+                }
+            }
+
+            $(
+                impl PinScl<$I2CX> for Pin<$SclP, $SclN, Alternate<OpenDrain>> {}
+            )*
+            $(
+                impl PinSda<$I2CX> for Pin<$SdaP, $SdaN, Alternate<OpenDrain>> {}
+            )*
+        )+
+    };
+}
+
+i2c! {
+    I2C1: I2c1 => {
+        scl: [('B', 6), ('B', 8)],
+        sda: [('B', 7), ('B', 9)],
+    }
+    I2C2: I2c2 => {
+        scl: [('B', 10)],
+        sda: [('B', 11)],
+    }
+}
+
+/// Programs the clock registers for `mode` while the I2C is disabled, and
+/// returns the resulting SCL frequency
+fn configure(rb: &i2c1::RegisterBlock, mode: Mode, clocks: &CoreClocks) -> Hertz {
+    let pclk1 = clocks.pclk1().raw();
+    let freq_mhz = pclk1 / 1_000_000;
+    assert!((2..=63).contains(&freq_mhz), "PCLK1 out of range");
+
+    // Keeps the interrupt enables of a running I2C
+    rb.ctlr2
+        .modify(|_, w| unsafe { w.freq().bits(freq_mhz as u8) });
+
+    match mode {
+        Mode::Standard { frequency } => {
+            assert!(frequency.raw() <= 100_000);
+            // SCL high and low time are both CCR PCLK1 cycles
+            let ccr = (pclk1 / (frequency.raw() * 2)).max(4);
+            // Max rise time is 1000ns
+            rb.rtr
+                .write(|w| unsafe { w.trise().bits(freq_mhz as u8 + 1) });
+            rb.ckcfgr.write(|w| unsafe { w.ccr().bits(ccr as u16) });
+            Hertz::from_raw(pclk1 / (ccr * 2))
+        }
+        Mode::Fast {
+            frequency,
+            duty_cycle,
+        } => {
+            assert!(frequency.raw() <= 400_000);
+            let (ccr, duty, period) = match duty_cycle {
+                DutyCycle::Ratio2to1 => (pclk1 / (frequency.raw() * 3), false, 3),
+                DutyCycle::Ratio16to9 => (pclk1 / (frequency.raw() * 25), true, 25),
+            };
+            let ccr = ccr.max(1);
+            // Max rise time is 300ns
+            rb.rtr
+                .write(|w| unsafe { w.trise().bits((freq_mhz * 300 / 1000) as u8 + 1) });
+            rb.ckcfgr
+                .write(|w| unsafe { w.ccr().bits(ccr as u16).duty().bit(duty).f_s().set_bit() });
+            Hertz::from_raw(pclk1 / (ccr * period))
+        }
+    }
+}
+
+/// I2C master
+pub struct I2c<I2C: Instance, PINS> {
+    i2c: I2C,
+    pins: PINS,
+    prec: I2C::Rec,
+    hclk: Hertz,
+    // In core cycles, 0 waits forever
+    timeout: u64,
+}
+
+impl<I2C: Instance, PINS: Pins<I2C>> I2c<I2C, PINS> {
+    /// Puts SCL and SDA into alternate open-drain mode, then configures the
+    /// I2C as [`new_from_configured`](I2c::new_from_configured).
+    ///
+    /// # Panics
+    ///
+    /// Panics if PCLK1 is out of range, or if the frequency exceeds the
+    /// limit of `mode`.
+    pub fn new<RAW>(i2c: I2C, pins: RAW, mode: Mode, prec: I2C::Rec, clocks: &CoreClocks) -> Self
+    where
+        RAW: IntoPins<I2C, Pins = PINS>,
+    {
+        Self::new_from_configured(i2c, pins.into_pins(), mode, prec, clocks)
+    }
+
+    /// Configures the I2C as a master with 7-bit addressing, without
+    /// touching the pins.
+    ///
+    /// SCL and SDA must already be in `Alternate<OpenDrain>` mode.
+    ///
+    /// # Panics
+    ///
+    /// Panics if PCLK1 is out of range, or if the frequency exceeds the
+    /// limit of `mode`.
+    pub fn new_from_configured(
+        i2c: I2C,
+        pins: PINS,
+        mode: Mode,
+        prec: I2C::Rec,
+        clocks: &CoreClocks,
+    ) -> Self {
+        let prec = prec.enable().reset();
+
+        // NOTE(unsafe) we own the I2C
+        let rb = unsafe { &*I2C::ptr() };
+
+        rb.ctlr1.write(|w| w.pe().clear_bit());
+        configure(rb, mode, clocks);
+        rb.ctlr1.write(|w| w.pe().set_bit());
+
+        I2c {
+            i2c,
+            pins,
+            prec,
+            hclk: clocks.hclk(),
+            timeout: 0,
+        }
+    }
+}
+
+impl<I2C: Instance, PINS> I2c<I2C, PINS> {
+    /// Fails with [`Error::Timeout`] when a single step of a transfer
+    /// (start, address, one byte, stop) takes longer than `timeout`.
+    ///
+    /// The transfer is abandoned in whatever state the bus is in, follow up
+    /// with [`recover`](I2c::recover).
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: MicroSeconds) -> Self {
+        self.timeout = u64::from(timeout.ticks()) * u64::from(self.hclk.raw()) / 1_000_000;
+        self
+    }
+
+    /// Switches the bus to `mode` and returns the SCL frequency reached,
+    /// e.g. to talk to a slower device on the same bus.
+    ///
+    /// The I2C is briefly disabled, so a transaction in flight must have
+    /// completed first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if PCLK1 is out of range, or if the frequency exceeds the
+    /// limit of `mode`.
+    pub fn set_speed(&mut self, mode: Mode, clocks: &CoreClocks) -> Hertz {
+        let rb = self.rb();
+        // The clock registers may only be written while PE is clear
+        rb.ctlr1.modify(|_, w| w.pe().clear_bit());
+        let frequency = configure(rb, mode, clocks);
+        rb.ctlr1.modify(|_, w| w.pe().set_bit());
+        frequency
+    }
+
+    /// Disables the I2C and its clock, and releases the peripheral, the
+    /// pins and the REC token for a later [`new`](I2c::new)
+    pub fn release(self) -> (I2C, PINS, I2C::Rec) {
+        // NOTE(unsafe) we own the I2C
+        unsafe { (*I2C::ptr()).ctlr1.reset() };
+        (self.i2c, self.pins, self.prec.disable())
+    }
+
+    #[inline(always)]
+    fn rb(&self) -> &i2c1::RegisterBlock {
+        // NOTE(unsafe) we own the I2C
+        unsafe { &*I2C::ptr() }
+    }
+
+    fn check_timeout(&self, start: u64) -> Result<(), Error> {
+        if self.timeout != 0 && mcycle::read64().wrapping_sub(start) > self.timeout {
+            Err(Error::Timeout)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Waits for one of the STAR1 `flags`, failing on any error flag
+    fn wait_for(&self, flags: u32) -> Result<u32, Error> {
+        let rb = self.rb();
+        let start = mcycle::read64();
+        loop {
+            let star1 = rb.star1.read().bits();
+
+            let err = star1 & (BERR | ARLO | AF | OVR);
+            if err != 0 {
+                // Error flags are cleared by writing 0
+                rb.star1.write(|w| unsafe { w.bits(!err & 0xFFFF) });
+                if err & AF != 0 {
+                    rb.ctlr1.modify(|_, w| w.stop().set_bit());
+                }
+                return Err(if err & BERR != 0 {
+                    Error::Bus
+                } else if err & ARLO != 0 {
+                    Error::Arbitration
+                } else if err & AF != 0 {
+                    Error::Nack
+                } else {
+                    Error::Overrun
+                });
+            }
+
+            if star1 & flags != 0 {
+                return Ok(star1);
+            }
+            self.check_timeout(start)?;
+        }
+    }
+
+    fn wait_for_stop(&self) -> Result<(), Error> {
+        let start = mcycle::read64();
+        while self.rb().ctlr1.read().stop().bit_is_set() {
+            self.check_timeout(start)?;
+        }
+        Ok(())
+    }
+
+    fn start(&self, addr: u8, read: bool) -> Result<(), Error> {
+        let rb = self.rb();
+        rb.ctlr1.modify(|_, w| w.start().set_bit());
+        self.wait_for(SB)?;
+
+        rb.datar
+            .write(|w| unsafe { w.bits(u32::from(addr << 1 | u8::from(read))) });
+        self.wait_for(ADDR)?;
+        Ok(())
+    }
+
+    /// Clears ADDR, which releases SCL and starts the data phase
+    fn clear_addr(&self) {
+        let rb = self.rb();
+        rb.star1.read();
+        rb.star2.read();
+    }
+
+    fn write_bytes(&self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+        let rb = self.rb();
+        self.start(addr, false)?;
+        self.clear_addr();
+
+        for byte in bytes {
+            self.wait_for(TXE)?;
+            rb.datar.write(|w| unsafe { w.bits(u32::from(*byte)) });
+        }
+        // BTF never sets without a data byte, an address probe goes
+        // straight on to STOP or the repeated START
+        if !bytes.is_empty() {
+            self.wait_for(BTF)?;
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&self, addr: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        let rb = self.rb();
+
+        rb.ctlr1.modify(|_, w| w.ack().set_bit());
+        self.start(addr, true)?;
+
+        // The ACK of the last byte has to be dropped before it is received,
+        // which takes a different sequence for the last three bytes
+        match buffer.len() {
+            0 => {
+                self.clear_addr();
+                rb.ctlr1.modify(|_, w| w.stop().set_bit());
+            }
+            1 => {
+                rb.ctlr1.modify(|_, w| w.ack().clear_bit());
+                self.clear_addr();
+                rb.ctlr1.modify(|_, w| w.stop().set_bit());
+                self.wait_for(RXNE)?;
+                buffer[0] = rb.datar.read().bits() as u8;
+            }
+            2 => {
+                rb.ctlr1.modify(|_, w| w.pos().set_bit().ack().clear_bit());
+                self.clear_addr();
+                self.wait_for(BTF)?;
+                rb.ctlr1.modify(|_, w| w.stop().set_bit());
+                buffer[0] = rb.datar.read().bits() as u8;
+                buffer[1] = rb.datar.read().bits() as u8;
+                rb.ctlr1.modify(|_, w| w.pos().clear_bit());
+            }
+            len => {
+                self.clear_addr();
+                let (first, last) = buffer.split_at_mut(len - 3);
+                for byte in first {
+                    self.wait_for(RXNE)?;
+                    *byte = rb.datar.read().bits() as u8;
+                }
+                // N-2 in DATAR, N-1 in the shift register
+                self.wait_for(BTF)?;
+                rb.ctlr1.modify(|_, w| w.ack().clear_bit());
+                last[0] = rb.datar.read().bits() as u8;
+                rb.ctlr1.modify(|_, w| w.stop().set_bit());
+                last[1] = rb.datar.read().bits() as u8;
+                self.wait_for(RXNE)?;
+                last[2] = rb.datar.read().bits() as u8;
+            }
+        }
+
+        self.wait_for_stop()
+    }
+}
+
+impl<I2C: Instance, const SP: char, const SN: u8, const DP: char, const DN: u8>
+    I2c<
+        I2C,
+        (
+            Pin<SP, SN, Alternate<OpenDrain>>,
+            Pin<DP, DN, Alternate<OpenDrain>>,
+        ),
+    >
+{
+    /// Frees a bus stuck by a slave holding SDA low, then resets the I2C
+    /// and restores its configuration.
+    ///
+    /// SCL is clocked by hand, up to 9 times, until the slave releases SDA
+    /// and a stop condition is sent. Returns [`Error::Bus`] if SDA is still
+    /// held low afterwards.
+    pub fn recover<D: DelayUs<u16>>(&mut self, delay: &mut D) -> Result<(), Error> {
+        // NOTE(unsafe) we own the I2C
+        let rb = unsafe { &*I2C::ptr() };
+        let (scl, sda) = &mut self.pins;
+
+        let released = scl.with_mode::<Output<OpenDrain>, _>(|scl| {
+            sda.with_mode::<Output<OpenDrain>, _>(|sda| {
+                // Infallible
+                sda.set_high().ok();
+                scl.set_high().ok();
+                delay.delay_us(5);
+
+                for _ in 0..9 {
+                    if sda.is_high().unwrap_or(false) {
+                        break;
+                    }
+                    scl.set_low().ok();
+                    delay.delay_us(5);
+                    scl.set_high().ok();
+                    delay.delay_us(5);
+                }
+
+                // Stop condition: SDA rises while SCL is high
+                scl.set_low().ok();
+                sda.set_low().ok();
+                delay.delay_us(5);
+                scl.set_high().ok();
+                delay.delay_us(5);
+                sda.set_high().ok();
+                delay.delay_us(5);
+
+                sda.is_high().unwrap_or(false)
+            })
+        });
+
+        // The software reset clears the timing configuration too
+        let ctlr2 = rb.ctlr2.read().bits();
+        let ckcfgr = rb.ckcfgr.read().bits();
+        let rtr = rb.rtr.read().bits();
+        rb.ctlr1.write(|w| w.swrst().set_bit());
+        rb.ctlr1.reset();
+        rb.ctlr2.write(|w| unsafe { w.bits(ctlr2) });
+        rb.ckcfgr.write(|w| unsafe { w.bits(ckcfgr) });
+        rb.rtr.write(|w| unsafe { w.bits(rtr) });
+        rb.ctlr1.write(|w| w.pe().set_bit());
+
+        if released {
+            Ok(())
+        } else {
+            Err(Error::Bus)
+        }
+    }
+}
+
+impl<I2C: Instance, PINS> Write for I2c<I2C, PINS> {
+    type Error = Error;
+
+    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.write_bytes(addr, bytes)?;
+        self.rb().ctlr1.modify(|_, w| w.stop().set_bit());
+        self.wait_for_stop()
+    }
+}
+
+impl<I2C: Instance, PINS> Read for I2c<I2C, PINS> {
+    type Error = Error;
+
+    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read_bytes(addr, buffer)
+    }
+}
+
+impl<I2C: Instance, PINS> WriteRead for I2c<I2C, PINS> {
+    type Error = Error;
+
+    fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        // Repeated start, no stop in between
+        self.write_bytes(addr, bytes)?;
+        self.read_bytes(addr, buffer)
+    }
+}
@@ -17,10 +17,40 @@ pub mod delay;
 pub mod prelude;
 pub mod time;
 
+pub mod adc;
+pub mod afio;
+pub mod bkp;
+pub mod can;
+pub mod crc;
+pub mod dac;
+pub mod dma;
+pub mod extend;
+pub mod flash;
 pub mod gpio;
+pub mod i2c;
+pub mod onewire;
+pub mod opa;
+pub mod pfic;
+pub mod pwr;
 pub mod rcc;
+pub mod rtc;
+pub mod serial;
+pub mod signature;
+pub mod spi;
+pub mod timer;
+#[cfg(feature = "usb")]
+pub mod usb;
+#[cfg(feature = "smart-leds")]
+pub mod ws2812;
+pub mod wwdg;
+
+pub use pfic::system_reset as reset;
 
 mod sealed {
     pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
 }
 pub(crate) use sealed::Sealed;
@@ -0,0 +1,282 @@
+//! Software 1-Wire master
+//!
+//! Bit-banged 1-Wire bus on a single open-drain pin, e.g. for DS18B20
+//! temperature sensors. The pin is driven low to signal and released to let
+//! the bus pull-up (usually 4.7k to VDD) bring it high, and is read back to
+//! sample the bus, so an `Output<OpenDrain>` pin is the natural fit.
+//!
+//! ```
+//! let pin = gpioa.pa0.into_open_drain_output();
+//! let mut delay = Delay::with_clocks(&ccdr.clocks);
+//! let mut bus = OneWire::new(pin).unwrap();
+//!
+//! let mut search = Search::new();
+//! while let Some(rom) = bus.search(&mut delay, &mut search).unwrap() {
+//!     // found a device
+//! }
+//! ```
+//!
+//! **NOTE**: The protocol has tight timing: a read slot must be sampled
+//! within 15µs of the master pulling the bus low. The delay provider must be
+//! accurate to a few microseconds, and an interrupt taken in the middle of a
+//! slot stretches it and corrupts the transfer. Run bus transactions inside
+//! `riscv::interrupt::free` when interrupts are in use.
+
+use crate::hal::blocking::delay::DelayUs;
+use crate::hal::digital::v2::{InputPin, OutputPin};
+
+/// Search ROM command
+pub const SEARCH_ROM: u8 = 0xF0;
+/// Read ROM command, only valid with a single device on the bus
+pub const READ_ROM: u8 = 0x33;
+/// Match ROM command, addresses the device with the following ROM code
+pub const MATCH_ROM: u8 = 0x55;
+/// Skip ROM command, addresses every device on the bus
+pub const SKIP_ROM: u8 = 0xCC;
+
+/// A 1-Wire bus master on a single pin
+pub struct OneWire<PIN> {
+    pin: PIN,
+}
+
+/// State of a ROM search over the bus
+///
+/// Keep passing the same value to [`OneWire::search`] to enumerate every
+/// device.
+#[derive(Clone, Copy)]
+pub struct Search {
+    rom: [u8; 8],
+    last_discrepancy: u8,
+    last_device: bool,
+}
+
+impl Search {
+    /// Starts a new search from the first device
+    pub const fn new() -> Self {
+        Search {
+            rom: [0; 8],
+            last_discrepancy: 0,
+            last_device: false,
+        }
+    }
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<PIN, E> OneWire<PIN>
+where
+    PIN: InputPin<Error = E> + OutputPin<Error = E>,
+{
+    /// Creates the bus master, releasing the line
+    pub fn new(mut pin: PIN) -> Result<Self, E> {
+        pin.set_high()?;
+        Ok(OneWire { pin })
+    }
+
+    /// Releases the pin
+    pub fn release(self) -> PIN {
+        self.pin
+    }
+
+    /// Sends a reset pulse and returns whether any device answered with a
+    /// presence pulse
+    pub fn reset<D: DelayUs<u16>>(&mut self, delay: &mut D) -> Result<bool, E> {
+        self.pin.set_low()?;
+        delay.delay_us(480);
+        self.pin.set_high()?;
+        delay.delay_us(70);
+        let present = self.pin.is_low()?;
+        delay.delay_us(410);
+        Ok(present)
+    }
+
+    /// Writes a single bit slot
+    pub fn write_bit<D: DelayUs<u16>>(&mut self, delay: &mut D, bit: bool) -> Result<(), E> {
+        self.pin.set_low()?;
+        if bit {
+            delay.delay_us(6);
+            self.pin.set_high()?;
+            delay.delay_us(64);
+        } else {
+            delay.delay_us(60);
+            self.pin.set_high()?;
+            delay.delay_us(10);
+        }
+        Ok(())
+    }
+
+    /// Reads a single bit slot
+    pub fn read_bit<D: DelayUs<u16>>(&mut self, delay: &mut D) -> Result<bool, E> {
+        self.pin.set_low()?;
+        delay.delay_us(6);
+        self.pin.set_high()?;
+        delay.delay_us(9);
+        let bit = self.pin.is_high()?;
+        delay.delay_us(55);
+        Ok(bit)
+    }
+
+    /// Writes a byte, least significant bit first
+    pub fn write_byte<D: DelayUs<u16>>(&mut self, delay: &mut D, byte: u8) -> Result<(), E> {
+        for i in 0..8 {
+            self.write_bit(delay, byte & (1 << i) != 0)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a byte, least significant bit first
+    pub fn read_byte<D: DelayUs<u16>>(&mut self, delay: &mut D) -> Result<u8, E> {
+        let mut byte = 0;
+        for i in 0..8 {
+            if self.read_bit(delay)? {
+                byte |= 1 << i;
+            }
+        }
+        Ok(byte)
+    }
+
+    /// Writes all bytes of `bytes`
+    pub fn write_bytes<D: DelayUs<u16>>(&mut self, delay: &mut D, bytes: &[u8]) -> Result<(), E> {
+        for byte in bytes {
+            self.write_byte(delay, *byte)?;
+        }
+        Ok(())
+    }
+
+    /// Fills `bytes` from the bus
+    pub fn read_bytes<D: DelayUs<u16>>(
+        &mut self,
+        delay: &mut D,
+        bytes: &mut [u8],
+    ) -> Result<(), E> {
+        for byte in bytes.iter_mut() {
+            *byte = self.read_byte(delay)?;
+        }
+        Ok(())
+    }
+
+    /// Resets the bus and addresses the device with ROM code `rom`.
+    ///
+    /// Returns `false` if no device answered the reset.
+    pub fn select<D: DelayUs<u16>>(&mut self, delay: &mut D, rom: &[u8; 8]) -> Result<bool, E> {
+        if !self.reset(delay)? {
+            return Ok(false);
+        }
+        self.write_byte(delay, MATCH_ROM)?;
+        self.write_bytes(delay, rom)?;
+        Ok(true)
+    }
+
+    /// Resets the bus and addresses every device on it.
+    ///
+    /// Returns `false` if no device answered the reset.
+    pub fn skip<D: DelayUs<u16>>(&mut self, delay: &mut D) -> Result<bool, E> {
+        if !self.reset(delay)? {
+            return Ok(false);
+        }
+        self.write_byte(delay, SKIP_ROM)?;
+        Ok(true)
+    }
+
+    /// Finds the next device on the bus and returns its ROM code, or `None`
+    /// once every device has been found.
+    ///
+    /// The ROM code is not checked, use [`crc8`] on it to reject devices
+    /// that were read through a noisy bus.
+    pub fn search<D: DelayUs<u16>>(
+        &mut self,
+        delay: &mut D,
+        search: &mut Search,
+    ) -> Result<Option<[u8; 8]>, E> {
+        if search.last_device {
+            return Ok(None);
+        }
+        if !self.reset(delay)? {
+            *search = Search::new();
+            return Ok(None);
+        }
+        self.write_byte(delay, SEARCH_ROM)?;
+
+        let mut last_zero = 0;
+        for id_bit_number in 1..=64u8 {
+            let id_bit = self.read_bit(delay)?;
+            let cmp_id_bit = self.read_bit(delay)?;
+            let byte = usize::from((id_bit_number - 1) / 8);
+            let mask = 1 << ((id_bit_number - 1) % 8);
+
+            let direction = if id_bit && cmp_id_bit {
+                // no device took part in this search
+                *search = Search::new();
+                return Ok(None);
+            } else if id_bit != cmp_id_bit {
+                id_bit
+            } else {
+                // devices disagree on this bit
+                let direction = if id_bit_number < search.last_discrepancy {
+                    search.rom[byte] & mask != 0
+                } else {
+                    id_bit_number == search.last_discrepancy
+                };
+                if !direction {
+                    last_zero = id_bit_number;
+                }
+                direction
+            };
+
+            if direction {
+                search.rom[byte] |= mask;
+            } else {
+                search.rom[byte] &= !mask;
+            }
+            self.write_bit(delay, direction)?;
+        }
+
+        search.last_discrepancy = last_zero;
+        search.last_device = last_zero == 0;
+        Ok(Some(search.rom))
+    }
+}
+
+/// Dallas/Maxim CRC-8 (polynomial x^8 + x^5 + x^4 + 1)
+///
+/// Running it over a full ROM code or scratchpad including its CRC byte
+/// yields 0.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0;
+    for byte in data {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 0x01;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc8_check_value() {
+        // CRC-8/MAXIM check value
+        assert_eq!(crc8(b"123456789"), 0xA1);
+        assert_eq!(crc8(&[]), 0);
+    }
+
+    #[test]
+    fn crc8_over_code_and_crc_is_zero() {
+        let mut rom = [0x28, 0xFF, 0x4A, 0x6B, 0x61, 0x16, 0x03, 0];
+        rom[7] = crc8(&rom[..7]);
+        assert_eq!(rom[7], 0xB2);
+        assert_eq!(crc8(&rom), 0);
+    }
+}
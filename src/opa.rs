@@ -0,0 +1,145 @@
+//! Operational amplifiers (OPA)
+//!
+//! The CH32V30x has four op-amps, configured through `OPA_CTLR` next to
+//! `EXTEND_CTR`. Each one selects one of two pins per input and output, see
+//! the datasheet pinout for the OPAx_CHP0/1, OPAx_CHN0/1 and OPAx_OUT0/1
+//! pins. Those pins must be put into analog mode.
+//!
+//! ```
+//! let mut extend = dp.EXTEND.constrain();
+//! let opamps = extend.split_opamps().unwrap();
+//!
+//! let mut opa1 = Opamp::new(opamps.opa1, Positive::Chp0, Negative::Chn0, Output::Out0);
+//! opa1.enable();
+//! ```
+//!
+//! The gain is set by external resistors. The output pins double as ADC
+//! inputs, so e.g. an amplified shunt voltage can be sampled right away.
+
+use vcell::VolatileCell;
+
+use crate::pac::EXTEND;
+
+#[inline(always)]
+fn opa_ctlr() -> &'static VolatileCell<u32> {
+    // NOTE(unsafe) OPA_CTLR follows EXTEND_CTR, each op-amp only touches
+    // its own nibble
+    unsafe { &*((EXTEND::ptr() as *const u32).add(1) as *const VolatileCell<u32>) }
+}
+
+// OPA_CTLR bits, per op-amp nibble
+const EN: u32 = 1 << 0;
+const MODE: u32 = 1 << 1;
+const NSEL: u32 = 1 << 2;
+const PSEL: u32 = 1 << 3;
+
+/// Non-inverting input selection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Positive {
+    /// OPAx_CHP0 pin
+    Chp0,
+    /// OPAx_CHP1 pin
+    Chp1,
+}
+
+/// Inverting input selection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Negative {
+    /// OPAx_CHN0 pin
+    Chn0,
+    /// OPAx_CHN1 pin
+    Chn1,
+}
+
+/// Output selection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Output {
+    /// OPAx_OUT0 pin
+    Out0,
+    /// OPAx_OUT1 pin
+    Out1,
+}
+
+/// Op-amp `N` (1 to 4), not configured yet
+pub struct Opa<const N: u8> {
+    _0: (),
+}
+
+/// The four op-amps, from [`Extend::split_opamps`](crate::extend::Extend::split_opamps)
+pub struct Opamps {
+    /// OPA1
+    pub opa1: Opa<1>,
+    /// OPA2
+    pub opa2: Opa<2>,
+    /// OPA3
+    pub opa3: Opa<3>,
+    /// OPA4
+    pub opa4: Opa<4>,
+}
+
+impl Opamps {
+    pub(crate) fn new() -> Self {
+        Opamps {
+            opa1: Opa { _0: () },
+            opa2: Opa { _0: () },
+            opa3: Opa { _0: () },
+            opa4: Opa { _0: () },
+        }
+    }
+}
+
+/// A configured op-amp
+pub struct Opamp<const N: u8> {
+    opa: Opa<N>,
+}
+
+impl<const N: u8> Opamp<N> {
+    const OFFSET: u32 = 4 * (N as u32 - 1);
+
+    /// Selects the inputs and the output, the op-amp stays disabled
+    pub fn new(opa: Opa<N>, positive: Positive, negative: Negative, output: Output) -> Self {
+        let mut bits = 0;
+        if positive == Positive::Chp1 {
+            bits |= PSEL;
+        }
+        if negative == Negative::Chn1 {
+            bits |= NSEL;
+        }
+        if output == Output::Out1 {
+            bits |= MODE;
+        }
+        Self::modify(0b1111, bits);
+        Opamp { opa }
+    }
+
+    /// Turns the op-amp on
+    pub fn enable(&mut self) {
+        Self::modify(EN, EN);
+    }
+
+    /// Turns the op-amp off, its output floats
+    pub fn disable(&mut self) {
+        Self::modify(EN, 0);
+    }
+
+    /// Whether the op-amp is on
+    pub fn is_enabled(&self) -> bool {
+        opa_ctlr().get() & (EN << Self::OFFSET) != 0
+    }
+
+    /// Disables the op-amp and releases it for another configuration
+    pub fn release(mut self) -> Opa<N> {
+        self.disable();
+        self.opa
+    }
+
+    fn modify(mask: u32, bits: u32) {
+        riscv::interrupt::free(|_| {
+            let ctlr = opa_ctlr();
+            ctlr.set(ctlr.get() & !(mask << Self::OFFSET) | (bits << Self::OFFSET));
+        });
+    }
+}
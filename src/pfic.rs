@@ -0,0 +1,167 @@
+//! Programmable Fast Interrupt Controller (PFIC)
+//!
+//! The QingKe V4F core uses the PFIC instead of the CLIC or an NVIC. Each
+//! interrupt has one enable, pending and active bit, spread over eight
+//! 32-bit registers, and an 8-bit priority in `IPRIOR`.
+//!
+//! Lower priority values are more urgent. Only the upper bits of each
+//! priority byte are implemented: with the default two levels of nesting,
+//! bit 7 selects the preemption level and bits 6:4 the sub-priority within
+//! it. Bits 3:0 are ignored.
+//!
+//! ```
+//! unsafe {
+//!     pfic::set_priority(Interrupt::EXTI0, 0b1000_0000);
+//!     pfic::unmask(Interrupt::EXTI0);
+//! }
+//! ```
+
+use vcell::VolatileCell;
+
+pub use crate::pac::Interrupt;
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct PFIC {
+    ISR: [VolatileCell<u32>; 8],
+    IPR: [VolatileCell<u32>; 8],
+    ITHRESDR: VolatileCell<u32>,
+    _reserved0: u32,
+    CFGR: VolatileCell<u32>,
+    _reserved1: [u32; 45],
+    IENR: [VolatileCell<u32>; 8],
+    _reserved2: [u32; 24],
+    IRER: [VolatileCell<u32>; 8],
+    _reserved3: [u32; 24],
+    IPSR: [VolatileCell<u32>; 8],
+    _reserved4: [u32; 24],
+    IPRR: [VolatileCell<u32>; 8],
+    _reserved5: [u32; 24],
+    IACTR: [VolatileCell<u32>; 8],
+    _reserved6: [u32; 56],
+    IPRIOR: [VolatileCell<u8>; 256],
+    _reserved7: [u32; 516],
+    SCTLR: VolatileCell<u32>,
+}
+
+const PFIC_BASE_ADDR: u32 = 0xE000E000;
+
+#[inline(always)]
+fn pfic() -> &'static PFIC {
+    unsafe { &*(PFIC_BASE_ADDR as *const PFIC) }
+}
+
+// CFGR bits, only written together with the key
+const KEYCODE: u32 = 0xBEEF << 16;
+const SYSRESET: u32 = 1 << 7;
+
+// SCTLR bits
+const SLEEPDEEP: u32 = 1 << 2;
+
+#[inline(always)]
+fn index(irq: Interrupt) -> (usize, u32) {
+    let nr = irq as usize;
+    (nr / 32, 1 << (nr % 32))
+}
+
+/// Enables `irq`
+///
+/// # Safety
+///
+/// This can break mask-based critical sections
+#[inline]
+pub unsafe fn unmask(irq: Interrupt) {
+    let (reg, bit) = index(irq);
+    pfic().IENR[reg].set(bit);
+}
+
+/// Disables `irq`
+#[inline]
+pub fn mask(irq: Interrupt) {
+    let (reg, bit) = index(irq);
+    pfic().IRER[reg].set(bit);
+}
+
+/// Whether `irq` is enabled
+#[inline]
+pub fn is_enabled(irq: Interrupt) -> bool {
+    let (reg, bit) = index(irq);
+    pfic().ISR[reg].get() & bit != 0
+}
+
+/// Whether `irq` is pending
+#[inline]
+pub fn is_pending(irq: Interrupt) -> bool {
+    let (reg, bit) = index(irq);
+    pfic().IPR[reg].get() & bit != 0
+}
+
+/// Whether the handler of `irq` is running (or preempted)
+#[inline]
+pub fn is_active(irq: Interrupt) -> bool {
+    let (reg, bit) = index(irq);
+    pfic().IACTR[reg].get() & bit != 0
+}
+
+/// Forces `irq` into the pending state
+#[inline]
+pub fn pend(irq: Interrupt) {
+    let (reg, bit) = index(irq);
+    pfic().IPSR[reg].set(bit);
+}
+
+/// Clears the pending state of `irq`
+#[inline]
+pub fn unpend(irq: Interrupt) {
+    let (reg, bit) = index(irq);
+    pfic().IPRR[reg].set(bit);
+}
+
+/// Returns the priority of `irq`
+#[inline]
+pub fn get_priority(irq: Interrupt) -> u8 {
+    pfic().IPRIOR[irq as usize].get()
+}
+
+/// Sets the priority of `irq`, see the [module docs](self) for the layout
+///
+/// # Safety
+///
+/// Changing priority levels can break priority-based critical sections
+#[inline]
+pub unsafe fn set_priority(irq: Interrupt, prio: u8) {
+    pfic().IPRIOR[irq as usize].set(prio);
+}
+
+/// Sets the priority threshold: interrupts with a priority value greater
+/// than or equal to `prio` are not taken. `0` disables the threshold.
+///
+/// # Safety
+///
+/// Lowering the threshold can break priority-based critical sections
+#[inline]
+pub unsafe fn set_priority_threshold(prio: u8) {
+    pfic().ITHRESDR.set(u32::from(prio));
+}
+
+/// Makes the next `wfi` enter deep sleep (Stop or Standby, as selected in
+/// PWR) instead of sleep
+#[inline]
+pub(crate) fn set_sleep_deep(deep: bool) {
+    let sctlr = &pfic().SCTLR;
+    if deep {
+        sctlr.set(sctlr.get() | SLEEPDEEP);
+    } else {
+        sctlr.set(sctlr.get() & !SLEEPDEEP);
+    }
+}
+
+/// Resets the whole MCU, like a reset pin event
+///
+/// Also available as [`crate::reset`].
+pub fn system_reset() -> ! {
+    pfic().CFGR.set(KEYCODE | SYSRESET);
+    loop {
+        // Wait for the reset to take effect
+    }
+}
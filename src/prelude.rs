@@ -1,6 +1,26 @@
 //! Prelude
+//!
+//! Brings the extension and driver traits into scope, so
+//! `use ch32v30x_hal::prelude::*;` is enough for `.constrain()`, `.split()`,
+//! `.MHz()`, `.bps()` and the `embedded-hal` methods. The traits are
+//! re-exported under `_ch32v_hal_*` names that won't clash with user code.
 pub use embedded_hal::prelude::*;
 
+pub use embedded_can::nb::Can as _ch32v_hal_embedded_can_nb_Can;
+pub use embedded_can::Frame as _ch32v_hal_embedded_can_Frame;
+pub use fugit::ExtU32 as _ch32v_hal_fugit_ExtU32;
 pub use fugit::RateExtU32 as _ch32v_hal_fugit_RateExtU32;
 
+pub use crate::afio::AfioExt as _ch32v_hal_afio_AfioExt;
+pub use crate::dma::DmaExt as _ch32v_hal_dma_DmaExt;
+pub use crate::extend::ExtendExt as _ch32v_hal_extend_ExtendExt;
+pub use crate::gpio::ExtiPin as _ch32v_hal_gpio_ExtiPin;
+pub use crate::gpio::GpioExt as _ch32v_hal_gpio_GpioExt;
+pub use crate::gpio::PinExt as _ch32v_hal_gpio_PinExt;
+pub use crate::pwr::PwrExt as _ch32v_hal_pwr_PwrExt;
 pub use crate::rcc::RccExt as _ch32v_hal_rcc_RccExt;
+pub use crate::serial::SerialExt as _ch32v_hal_serial_SerialExt;
+pub use crate::time::U32Ext as _ch32v_hal_time_U32Ext;
+pub use crate::timer::capture::CaptureExt as _ch32v_hal_timer_capture_CaptureExt;
+pub use crate::timer::pwm::PwmExt as _ch32v_hal_timer_pwm_PwmExt;
+pub use crate::timer::TimerExt as _ch32v_hal_timer_TimerExt;
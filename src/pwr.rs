@@ -0,0 +1,196 @@
+//! Power control (PWR)
+//!
+//! The RTC, the backup data registers and the LSE/RTC clock selection in
+//! `RCC.BDCTLR` live in the backup domain, which is write-protected after
+//! reset. Writes made while the protection is active are silently dropped.
+//! The [`BackupDomain`] token can only be obtained by lifting that
+//! protection, and drivers touching the backup domain require it.
+//!
+//! # Low-power modes
+//!
+//! - Sleep stops the core clock, any interrupt wakes it up.
+//! - Stop also stops all clocks, keeping SRAM and registers. Any EXTI line
+//!   set up as an interrupt wakes it up, including the RTC alarm with
+//!   [`enable_rtc_alarm_wakeup`](Pwr::enable_rtc_alarm_wakeup). The
+//!   system then runs from HSI, the PLL and HSE must be restarted.
+//! - Standby powers the core domain down. Only the WKUP pin (PA0), the RTC
+//!   alarm, NRST and the IWDG wake it up, through a reset.
+//!
+//! ```
+//! let flags = pwr.wakeup_flags();
+//! pwr.clear_wakeup_flags();
+//! if flags.standby {
+//!     // Woken up from standby
+//! }
+//!
+//! pwr.enable_rtc_alarm_wakeup(&mut dp.EXTI);
+//! rtc.set_alarm(rtc.current_time() + 10);
+//! pwr.enter_stop(StopMode::LowPowerRegulator);
+//! ```
+
+use crate::pac::{EXTI, PWR};
+use crate::pfic;
+use crate::rcc::rec::{self, ResetEnable};
+
+// CTLR bits
+const LPDS: u32 = 1 << 0;
+const PDDS: u32 = 1 << 1;
+const CWUF: u32 = 1 << 2;
+const CSBF: u32 = 1 << 3;
+// CSR bits
+const WUF: u32 = 1 << 0;
+const SBF: u32 = 1 << 1;
+const EWUP: u32 = 1 << 8;
+
+// EXTI line of the RTC alarm
+const RTC_ALARM_LINE: u32 = 1 << 17;
+
+/// Voltage regulator state in Stop mode
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StopMode {
+    /// Regulator on, faster wakeup
+    RegulatorOn,
+    /// Regulator in low-power mode, lower consumption
+    LowPowerRegulator,
+}
+
+/// Why the chip woke up, read at startup
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WakeupFlags {
+    /// The chip was in Standby before the last reset
+    pub standby: bool,
+    /// A wakeup event (WKUP pin or RTC alarm) occurred
+    pub wakeup: bool,
+}
+
+/// Extension trait that constrains the `PWR` peripheral
+pub trait PwrExt {
+    /// Constrains the `PWR` peripheral, enabling its clock
+    fn constrain(self, prec: rec::Pwr) -> Pwr;
+}
+
+impl PwrExt for PWR {
+    fn constrain(self, prec: rec::Pwr) -> Pwr {
+        prec.enable();
+        Pwr { rb: self }
+    }
+}
+
+/// Constrained PWR peripheral
+///
+/// Generated by calling `constrain` on the PAC's PWR peripheral.
+pub struct Pwr {
+    pub(crate) rb: PWR,
+}
+
+impl Pwr {
+    /// Enables the backup interface clock and lifts the backup domain write
+    /// protection (`PWR.CTLR.DBP`).
+    pub fn enable_backup_domain(&mut self, bkp: rec::Bkp) -> BackupDomain {
+        bkp.enable();
+        self.rb.ctlr.modify(|_, w| w.dbp().set_bit());
+        BackupDomain { _0: () }
+    }
+
+    /// Waits for an interrupt with the core clock stopped
+    pub fn enter_sleep(&mut self) {
+        pfic::set_sleep_deep(false);
+        // NOTE(unsafe) wfi has no side effects besides waiting
+        unsafe { riscv::asm::wfi() };
+    }
+
+    /// Enters Stop mode until an EXTI interrupt, see the
+    /// [module docs](self#low-power-modes)
+    pub fn enter_stop(&mut self, mode: StopMode) {
+        let lpds = match mode {
+            StopMode::RegulatorOn => 0,
+            StopMode::LowPowerRegulator => LPDS,
+        };
+        self.rb
+            .ctlr
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(PDDS | LPDS) | lpds) });
+        pfic::set_sleep_deep(true);
+        unsafe { riscv::asm::wfi() };
+        pfic::set_sleep_deep(false);
+    }
+
+    /// Enters Standby mode, which only ends with a reset
+    pub fn enter_standby(&mut self) -> ! {
+        // A pending wakeup flag would wake the chip right away
+        self.rb
+            .ctlr
+            .modify(|r, w| unsafe { w.bits(r.bits() | PDDS | CWUF) });
+        pfic::set_sleep_deep(true);
+        loop {
+            unsafe { riscv::asm::wfi() };
+        }
+    }
+
+    /// Lets a rising edge on the WKUP pin (PA0) wake the chip from Standby.
+    ///
+    /// PA0 is then an input with a pull-down, whatever its GPIO mode.
+    pub fn enable_wakeup_pin(&mut self) {
+        self.rb
+            .csr
+            .modify(|r, w| unsafe { w.bits(r.bits() | EWUP) });
+    }
+
+    /// Releases the WKUP pin
+    pub fn disable_wakeup_pin(&mut self) {
+        self.rb
+            .csr
+            .modify(|r, w| unsafe { w.bits(r.bits() & !EWUP) });
+    }
+
+    /// Lets the RTC alarm wake the chip from Stop, through EXTI line 17.
+    ///
+    /// The RTC alarm wakes the chip from Standby without it.
+    pub fn enable_rtc_alarm_wakeup(&mut self, exti: &mut EXTI) {
+        exti.rtenr
+            .modify(|r, w| unsafe { w.bits(r.bits() | RTC_ALARM_LINE) });
+        exti.intenr
+            .modify(|r, w| unsafe { w.bits(r.bits() | RTC_ALARM_LINE) });
+    }
+
+    /// Clears the RTC alarm EXTI line, needed before entering Stop again
+    pub fn clear_rtc_alarm_wakeup(&mut self) {
+        // NOTE(unsafe) write-1-to-clear, only touches line 17
+        unsafe { (*EXTI::ptr()).intfr.write(|w| w.bits(RTC_ALARM_LINE)) };
+    }
+
+    /// Reads the Standby and wakeup flags, kept across the wakeup reset
+    pub fn wakeup_flags(&self) -> WakeupFlags {
+        let csr = self.rb.csr.read().bits();
+        WakeupFlags {
+            standby: csr & SBF != 0,
+            wakeup: csr & WUF != 0,
+        }
+    }
+
+    /// Clears the Standby and wakeup flags
+    pub fn clear_wakeup_flags(&mut self) {
+        self.rb
+            .ctlr
+            .modify(|r, w| unsafe { w.bits(r.bits() | CSBF | CWUF) });
+    }
+}
+
+/// Proof that backup domain writes are enabled
+///
+/// Required by the drivers that write to the backup domain.
+pub struct BackupDomain {
+    _0: (),
+}
+
+impl BackupDomain {
+    /// Restores the backup domain write protection.
+    ///
+    /// Drivers built from this token keep working for reads, but their
+    /// writes will be ignored by the hardware from now on.
+    pub fn lock(self) {
+        // NOTE(unsafe) DBP is only ever set through this token
+        unsafe { (*PWR::ptr()).ctlr.modify(|_, w| w.dbp().clear_bit()) }
+    }
+}
@@ -4,9 +4,14 @@
 // HSI 8MHz
 // LSI 40kHz
 // max  sysclk 144MHz
+// max  adcclk 14MHz
 // NOTE v203/f203's pll is different from v205/v207/v305/v307
+// NOTE v30x executes from the zero-wait flash area, there are no flash wait
+// states to adjust when raising sysclk
 
-use crate::pac::{EXTEND, RCC};
+use crate::extend;
+use crate::gpio::{Alternate, PushPull, PA8};
+use crate::pac::RCC;
 use crate::time::Hertz;
 
 pub use self::rec::PeripheralREC;
@@ -18,6 +23,8 @@ pub struct Config {
     // 3-25MHz HSE OSC
     hse: Option<u32>,
     bypass_hse: bool,
+    // HSE -> PREDIV1 -> PLLMUL
+    pll_prediv: Option<u8>,
     sysclk: Option<u32>,
 
     // SYSCLK => [AHB prescaler] => HCLK
@@ -26,12 +33,18 @@ pub struct Config {
     rcc_pclk1: Option<u32>,
     // HCLK => [APB2 prescaler] => PCLK2
     rcc_pclk2: Option<u32>,
+    // PCLK2 => [ADC prescaler] => ADCCLK
+    adcclk: Option<u32>,
     // PLLCLK, for SYSCLK, USBCLK, MCO
     pllclk: Option<u32>,
     // HSE -> PREDIV2 -> PLL2MUL
     pll2clk: Option<u32>,
     // HSE -> PREDIV2 -> PLL3MUL
     pll3clk: Option<u32>,
+    // Panic in freeze unless USBCLK can be 48MHz
+    require_usbclk: bool,
+    // Gate all non-essential peripheral clocks in freeze
+    gate_clocks: bool,
 }
 
 /// Extension trait that constrains the `RCC` peripheral
@@ -47,13 +60,17 @@ impl RccExt for RCC {
             config: Config {
                 hse: None,
                 bypass_hse: false,
+                pll_prediv: None,
                 sysclk: None,
                 rcc_hclk: None,
                 rcc_pclk1: None,
                 rcc_pclk2: None,
+                adcclk: None,
                 pllclk: None,
                 pll2clk: None,
                 pll3clk: None,
+                require_usbclk: false,
+                gate_clocks: false,
             },
             rb: self,
         }
@@ -62,11 +79,15 @@ impl RccExt for RCC {
 
 /// Constrained RCC peripheral
 ///
-/// Generated by calling `constrain` on the PAC's RCC peripheral.
+/// Generated by calling `constrain` on the PAC's RCC peripheral. Configure
+/// the clocks with the builder methods, then [`freeze`](Rcc::freeze) them to
+/// get the [`PeripheralREC`] tokens that enable the peripherals.
 ///
 /// ```rust
-/// let dp = stm32::Peripherals::take().unwrap();
-/// let rcc = dp.RCC.constrain();
+/// let dp = pac::Peripherals::take().unwrap();
+/// let ccdr = dp.RCC.constrain().sysclk(144.MHz()).freeze();
+///
+/// let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
 /// ```
 pub struct Rcc {
     config: Config,
@@ -93,6 +114,15 @@ pub struct Ccdr {
 const HSI: u32 = 8_000_000; // Hz
 const LSI: u32 = 32_000; // Hz
 
+const ADC_MAX: u32 = 14_000_000; // Hz
+const USB: u32 = 48_000_000; // Hz
+
+const PLL_IN_MIN: u32 = 3_000_000; // Hz
+const PLL_IN_MAX: u32 = 25_000_000; // Hz
+
+// SRAMEN | FLITFEN, the core stalls if these are gated
+const AHB_ESSENTIAL: u32 = 0x0000_0014;
+
 impl Rcc {
     /// Uses HSE (external oscillator) instead of HSI (internal RC
     /// oscillator) as the clock source. Will result in a hang if an
@@ -111,7 +141,25 @@ impl Rcc {
         self
     }
 
+    /// Set the PREDIV1 division factor (1 to 16) applied to the HSE before
+    /// it enters the PLL.
+    ///
+    /// When not set, the smallest divider that lets the PLL reach `sysclk`
+    /// is picked. The divided clock must stay within the PLL input range of
+    /// 3 to 25MHz, and `sysclk` must be that frequency multiplied by one of
+    /// 3 to 16, 18 or 6.5.
+    #[must_use]
+    pub fn pll_prediv(mut self, div: u8) -> Self {
+        assert!((1..=16).contains(&div), "PREDIV1 must be between 1 and 16");
+        self.config.pll_prediv = Some(div);
+        self
+    }
+
     /// Set input frequency of system clock
+    ///
+    /// Frequencies other than the source clock are generated by the PLL,
+    /// fed from HSE when [`use_hse`](Rcc::use_hse) is set and from HSI (or
+    /// HSI / 2) otherwise. Both reach the full 144MHz: 8MHz x 18.
     #[must_use]
     pub fn sysclk(mut self, freq: Hertz) -> Self {
         self.config.sysclk = Some(freq.raw());
@@ -141,6 +189,42 @@ impl Rcc {
         self
     }
 
+    /// Set the ADC clock frequency, PCLK2 divided by 2, 4, 6 or 8.
+    /// At most 14MHz.
+    ///
+    /// When not set, the fastest ADC clock within 14MHz is used. Above a
+    /// PCLK2 of 112MHz, even PCLK2 / 8 is too fast, and
+    /// [`Adc::new`](crate::adc::Adc::new) panics; lower PCLK2 with
+    /// [`pclk2`](Rcc::pclk2) when the ADC is needed.
+    #[must_use]
+    pub fn adcclk(mut self, freq: Hertz) -> Self {
+        self.config.adcclk = Some(freq.raw());
+        self
+    }
+
+    /// Require the 48MHz USB clock of the USBD, `freeze` panics if it
+    /// can't be derived.
+    ///
+    /// USBCLK is PLLCLK divided by 1, 2 or 3 (USBPRE), so `sysclk` must be
+    /// 48, 96 or 144MHz and come from the PLL.
+    #[must_use]
+    pub fn require_usbclk(mut self) -> Self {
+        self.config.require_usbclk = true;
+        self
+    }
+
+    /// Gate every peripheral clock in `freeze`, except SRAM and the flash
+    /// interface which the core itself needs.
+    ///
+    /// The REC tokens enable their peripheral when a driver takes them, so
+    /// clocks of peripherals that are never used stay off. Debug access
+    /// keeps working as it does not depend on any peripheral clock.
+    #[must_use]
+    pub fn gate_peripheral_clocks(mut self) -> Self {
+        self.config.gate_clocks = true;
+        self
+    }
+
     /// Reset sysclk, use HSI
     fn sysclk_reset(&mut self) {
         let rcc = &self.rb;
@@ -161,11 +245,7 @@ impl Rcc {
         });
         while rcc.ctlr.read().hsirdy().bit_is_clear() {} // wait for HSI ready
         rcc.cfgr0.modify(|_, w| unsafe { w.sw().bits(0b00) });
-        unsafe {
-            (*EXTEND::ptr())
-                .extend_ctr
-                .modify(|_, w| w.pll_hsi_pre().set_bit())
-        }
+        extend::set_pll_hsi_pre(true);
         while rcc.cfgr0.read().sws().bits() != 0b00 {}
     }
 
@@ -180,7 +260,8 @@ impl Rcc {
         self.sysclk_reset();
 
         if use_hse {
-            let (sw, pllmul, pllsrc, prediv1, prediv1src) = sysclk_via_hse(srcclk, sysclk);
+            let (sw, pllmul, pllsrc, prediv1, prediv1src) =
+                sysclk_via_hse(srcclk, sysclk, self.config.pll_prediv);
 
             if self.config.bypass_hse {
                 self.rb.ctlr.modify(|_, w| w.hsebyp().set_bit());
@@ -219,11 +300,7 @@ impl Rcc {
         } else {
             let (sw, pllmul, pllsrc, hsipre) = sysclk_via_hsi(sysclk);
 
-            unsafe {
-                (*EXTEND::ptr())
-                    .extend_ctr
-                    .modify(|_, w| w.pll_hsi_pre().bit(hsipre != 0))
-            }
+            extend::set_pll_hsi_pre(hsipre != 0);
             self.rb.cfgr0.modify(|_, w| unsafe {
                 w.pllsrc()
                     .bit(pllsrc != 0)
@@ -250,7 +327,17 @@ impl Rcc {
         }
     }
 
+    /// Applies the clock configuration.
+    ///
+    /// Returns the resulting frequencies and the peripheral reset and enable
+    /// tokens. `Rcc` is consumed, so the tokens are only handed out once.
     pub fn freeze(mut self) -> Ccdr {
+        if self.config.gate_clocks {
+            self.rb.ahbpcenr.write(|w| unsafe { w.bits(AHB_ESSENTIAL) });
+            self.rb.apb1pcenr.write(|w| unsafe { w.bits(0) });
+            self.rb.apb2pcenr.write(|w| unsafe { w.bits(0) });
+        }
+
         let (sysclk, pllclk) = self.sysclk_setup();
 
         // HCLK defaults to SYSCLK
@@ -271,6 +358,7 @@ impl Rcc {
         let hclk = Hertz::from_raw(hclk);
 
         let pclk1 = self.config.rcc_pclk1.unwrap_or(hclk.raw());
+        let mut ppre1_div = 1;
         let mut pclk1_satisfied = false;
         if pclk1 != hclk.raw() {
             for (ppre1, div) in (0b011..=0b111).zip([1, 2, 4, 8, 16]) {
@@ -278,6 +366,7 @@ impl Rcc {
                     self.rb
                         .cfgr0
                         .modify(|_, w| unsafe { w.ppre1().bits(ppre1) });
+                    ppre1_div = div;
                     pclk1_satisfied = true;
                     break;
                 }
@@ -288,6 +377,7 @@ impl Rcc {
         }
 
         let pclk2 = self.config.rcc_pclk2.unwrap_or(hclk.raw());
+        let mut ppre2_div = 1;
         let mut pclk2_satisfied = false;
         if pclk2 != hclk.raw() {
             for (ppre2, div) in (0b011..=0b111).zip([1, 2, 4, 8, 16]) {
@@ -295,6 +385,7 @@ impl Rcc {
                     self.rb
                         .cfgr0
                         .modify(|_, w| unsafe { w.ppre2().bits(ppre2) });
+                    ppre2_div = div;
                     pclk2_satisfied = true;
                     break;
                 }
@@ -304,13 +395,54 @@ impl Rcc {
             }
         }
 
+        // ADCCLK defaults to the fastest clock the ADC accepts
+        let mut adcclk = None;
+        for (adcpre, div) in (0b00..=0b11).zip([2, 4, 6, 8]) {
+            let satisfied = match self.config.adcclk {
+                Some(freq) => pclk2 / div == freq,
+                None => pclk2 / div <= ADC_MAX || div == 8,
+            };
+            if satisfied {
+                self.rb
+                    .cfgr0
+                    .modify(|_, w| unsafe { w.adcpre().bits(adcpre) });
+                adcclk = Some(pclk2 / div);
+                break;
+            }
+        }
+        // Without an explicit request this may exceed ADC_MAX above a PCLK2
+        // of 112MHz, the ADC driver refuses such a clock
+        let adcclk = match adcclk {
+            Some(adcclk) if self.config.adcclk.is_none() || adcclk <= ADC_MAX => adcclk,
+            _ => panic!("adcclk not satisfied"),
+        };
+
+        // USBCLK is PLLCLK / 1, 2 or 3, only usable at 48MHz
+        let usbclk = pllclk.and_then(|pllclk| {
+            let (usbpre, div) = (0b00..=0b10)
+                .zip([1, 2, 3])
+                .find(|&(_, div)| pllclk.raw() == USB * div)?;
+            self.rb
+                .cfgr0
+                .modify(|_, w| unsafe { w.usbpre().bits(usbpre) });
+            Some(pllclk / div)
+        });
+        assert!(
+            usbclk.is_some() || !self.config.require_usbclk,
+            "usbclk not satisfied"
+        );
+
         Ccdr {
             clocks: CoreClocks {
                 sysclk: sysclk,
                 hclk: hclk,
                 pclk1: Hertz::from_raw(pclk1),
                 pclk2: Hertz::from_raw(pclk2),
+                adcclk: Hertz::from_raw(adcclk),
+                ppre1: ppre1_div as u8,
+                ppre2: ppre2_div as u8,
                 pllclk: pllclk,
+                usbclk: usbclk,
                 pll2clk: None,
                 pll3clk: None,
             },
@@ -320,47 +452,66 @@ impl Rcc {
     }
 }
 
-/// Setters for Micro-Controller Out (MCO)
-impl Rcc {
-    /// Set the MCO output frequency. The clock is sourced from the HSE
-    pub fn mco_from_hse(mut self) -> Self {
-        unimplemented!()
-    }
-
-    /// Set the MCO output frequency. The clock is sourced from the HSI
-    pub fn mco_from_hsi(mut self) -> Self {
-        unimplemented!()
-    }
-
-    /// Set the MCO output frequency. The clock is sourced from the SYSCLK
-    pub fn mco_from_sysclk(mut self) -> Self {
-        unimplemented!()
-    }
+/// Micro-Controller Out (MCO) clock source, see [`Mco`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum McoSource {
+    /// System clock
+    Sysclk = 0b0100,
+    /// HSI
+    Hsi = 0b0101,
+    /// HSE
+    Hse = 0b0110,
+    /// PLL clock divided by 2
+    PllclkDiv2 = 0b0111,
+    /// PLL2 clock
+    Pll2clk = 0b1000,
+    /// PLL3 clock divided by 2
+    Pll3clkDiv2 = 0b1001,
+    /// External 3-25MHz oscillator (XT1)
+    Xt1 = 0b1010,
+    /// PLL3 clock
+    Pll3clk = 0b1011,
+}
 
-    /// Set the MCO output frequency. The clock is sourced from the PLLCLK/2
-    pub fn mco_from_pllclk_div2(mut self) -> Self {
-        unimplemented!()
-    }
+/// Micro-Controller Out (MCO), a clock output on PA8.
+///
+/// Owns PA8 while the clock is output. Set the pin to high speed for clocks
+/// above 10MHz, it can toggle at up to 50MHz. PLL2 and PLL3 are not started
+/// by `freeze`.
+///
+/// ```
+/// let pa8 = gpioa.pa8.into_alternate().speed(Speed::High);
+/// let mco = Mco::new(pa8, McoSource::Sysclk);
+/// ```
+pub struct Mco {
+    pin: PA8<Alternate<PushPull>>,
+}
 
-    /// Set the MCO output frequency. The clock is sourced from the PLL2CLK
-    pub fn mco_from_pll2clk(mut self) -> Self {
-        unimplemented!()
+impl Mco {
+    /// Outputs `source` on `pin`
+    pub fn new(pin: PA8<Alternate<PushPull>>, source: McoSource) -> Self {
+        set_mco(source as u8);
+        Mco { pin }
     }
 
-    /// Set the MCO output frequency. The clock is sourced from the PLL3CLK/2
-    pub fn mco_from_pll3clk_div2(mut self) -> Self {
-        unimplemented!()
+    /// Switches the output to `source`
+    pub fn set_source(&mut self, source: McoSource) {
+        set_mco(source as u8);
     }
 
-    /// Set the MCO output frequency. The clock is sourced from the PLL3CLK
-    pub fn mco_from_pll3clk(mut self) -> Self {
-        unimplemented!()
+    /// Stops the clock output and releases the pin
+    pub fn release(self) -> PA8<Alternate<PushPull>> {
+        set_mco(0);
+        self.pin
     }
+}
 
-    /// Set the MCO output frequency. The clock is sourced from the XTI
-    pub fn mco_from_xti(mut self) -> Self {
-        unimplemented!()
-    }
+fn set_mco(bits: u8) {
+    // NOTE(unsafe) CFGR0 is shared with the other clock settings, MCO is
+    // only written by Mco
+    riscv::interrupt::free(|_| unsafe { (*RCC::ptr()).cfgr0.modify(|_, w| w.mco().bits(bits)) });
 }
 
 /// Frozen core clock frequencies
@@ -377,11 +528,65 @@ pub struct CoreClocks {
     pub pclk1: Hertz,
     /// APB2
     pub pclk2: Hertz,
+    /// ADC
+    pub adcclk: Hertz,
 
     // pll or not
     pub pllclk: Option<Hertz>,
+    /// USBD, only with a 48, 96 or 144MHz PLLCLK
+    pub usbclk: Option<Hertz>,
     pub pll2clk: Option<Hertz>,
     pub pll3clk: Option<Hertz>,
+
+    // APB prescaler dividers, for the timer clocks
+    ppre1: u8,
+    ppre2: u8,
+}
+
+impl CoreClocks {
+    /// Returns the system (core) frequency
+    pub fn sysclk(&self) -> Hertz {
+        self.sysclk
+    }
+
+    /// Returns the frequency of the AHB
+    pub fn hclk(&self) -> Hertz {
+        self.hclk
+    }
+
+    /// Returns the frequency of the APB1
+    pub fn pclk1(&self) -> Hertz {
+        self.pclk1
+    }
+
+    /// Returns the frequency of the APB2
+    pub fn pclk2(&self) -> Hertz {
+        self.pclk2
+    }
+
+    /// Returns the frequency of the APB1 timers (TIM2-TIM7)
+    ///
+    /// Twice PCLK1 when the APB1 prescaler is not 1.
+    pub fn pclk1_tim(&self) -> Hertz {
+        self.pclk1 * if self.ppre1 == 1 { 1 } else { 2 }
+    }
+
+    /// Returns the frequency of the APB2 timers (TIM1, TIM8-TIM10)
+    ///
+    /// Twice PCLK2 when the APB2 prescaler is not 1.
+    pub fn pclk2_tim(&self) -> Hertz {
+        self.pclk2 * if self.ppre2 == 1 { 1 } else { 2 }
+    }
+
+    /// Returns the frequency of the ADC clock
+    pub fn adcclk(&self) -> Hertz {
+        self.adcclk
+    }
+
+    /// Returns the frequency of the USBD clock, if it runs at 48MHz
+    pub fn usbclk(&self) -> Option<Hertz> {
+        self.usbclk
+    }
 }
 
 // sws: sys clock select
@@ -424,11 +629,11 @@ fn sysclk_via_hsi(sysclk: u32) -> (u8, u8, u8, u8) {
 
 // (sw, pllmul, pllsrc, prediv1, prediv1src)
 // NOTE: for D8C
-fn sysclk_via_hse(hse: u32, sysclk: u32) -> (u8, u8, u8, u8, u8) {
+fn sysclk_via_hse(hse: u32, sysclk: u32, prediv: Option<u8>) -> (u8, u8, u8, u8, u8) {
     let mut sw = 0b01; // HSE as SYSCLK
     let mut pllmul = 0b0000;
     let pllsrc = 0b1; // HSE clock not divided or divided by 2;
-    let prediv1 = 0b0000;
+    let mut prediv1 = 0b0000;
     let prediv1src = 0b0; // HSE as PREDIV1 input
 
     if hse != sysclk {
@@ -436,16 +641,27 @@ fn sysclk_via_hse(hse: u32, sysclk: u32) -> (u8, u8, u8, u8, u8) {
 
         // 2nd 6 is fake, never reached
         const PLLMUL: [u32; 16] = [18, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 6, 15, 16];
-        for (i, m) in PLLMUL.iter().enumerate() {
-            if hse * m == sysclk {
-                pllmul = i as u8;
+        let divs = match prediv {
+            Some(div) => div..=div,
+            None => 1..=16,
+        };
+        for div in divs {
+            let pllin = hse / div as u32;
+            if hse % div as u32 != 0 || !(PLL_IN_MIN..=PLL_IN_MAX).contains(&pllin) {
+                continue;
+            }
+            prediv1 = div - 1;
+            for (i, m) in PLLMUL.iter().enumerate() {
+                if pllin * m == sysclk {
+                    pllmul = i as u8;
+                    return (sw, pllmul, pllsrc, prediv1, prediv1src);
+                }
+            }
+            if pllin * 13 / 2 == sysclk && pllin * 13 % 2 == 0 {
+                pllmul = 0b1101; // PLL input x 6.5
                 return (sw, pllmul, pllsrc, prediv1, prediv1src);
             }
         }
-        if hse * 13 / 2 == sysclk {
-            pllmul = 0b1101; // PLL input x 6.5
-            return (sw, pllmul, pllsrc, prediv1, prediv1src);
-        }
         panic!("cannot satisfy sysclk");
     }
     return (sw, pllmul, pllsrc, prediv1, prediv1src);
@@ -2,11 +2,10 @@
 
 use riscv::interrupt;
 
+use super::CoreClocks;
 use crate::pac::{EXTEND, RCC};
 use core::marker::PhantomData;
 
-use super::Rcc;
-
 /// A trait for Resetting, Enabling and Disabling a single peripheral
 pub trait ResetEnable {
     /// Enable this peripheral
@@ -15,13 +14,21 @@ pub trait ResetEnable {
     /// Disable this peripheral
     #[allow(clippy::return_self_not_must_use)]
     fn disable(self) -> Self;
-    /// Reset this peripheral
+    /// Reset this peripheral. Does nothing for peripherals without a
+    /// reset bit, like DMA1, DMA2 and the other AHB peripherals.
     #[allow(clippy::return_self_not_must_use)]
     fn reset(self) -> Self;
+    /// Whether the clock of this peripheral is enabled
+    fn is_enabled(&self) -> bool;
 }
 
 macro_rules! peripheral_reset_and_enable_control_gen {
-    ($($PERIPH:ident: $Periph:ident => ($enr:ident, $enf:ident, $rstr:ident, $rstf:ident) ; )+) => {
+    ($($PERIPH:ident: $Periph:ident => ($enr:ident, $enf:ident $(, $rstr:ident, $rstf:ident)?) ; )+) => {
+        /// Reset and enable tokens for all peripherals
+        ///
+        /// Handed out once by [`Rcc::freeze`](super::Rcc::freeze). Each token
+        /// is moved into the driver of its peripheral, so no two drivers can
+        /// enable or reset the same peripheral.
         pub struct PeripheralREC {
             $(pub $PERIPH: $Periph,)*
         }
@@ -36,10 +43,22 @@ macro_rules! peripheral_reset_and_enable_control_gen {
                     )*
                 }
             }
+
+            /// Creates another set of tokens
+            ///
+            /// # Safety
+            ///
+            /// The tokens alias the ones handed out by `freeze`. The caller
+            /// must make sure no peripheral is enabled, disabled or reset
+            /// through both.
+            pub unsafe fn steal() -> PeripheralREC {
+                Self::new_singleton()
+            }
         }
 
         // Impl Periph
         $(
+            #[doc = concat!("Reset and enable token for ", stringify!($PERIPH))]
             pub struct $Periph {
                 _marker: PhantomData<*const ()>,
             }
@@ -67,35 +86,90 @@ macro_rules! peripheral_reset_and_enable_control_gen {
 
                 #[inline(always)]
                 fn reset(self) -> Self {
-                    interrupt::free(|_| {
-                        let rstr = unsafe { &(*RCC::ptr()).$rstr };
-                        rstr.modify(|_, w| w.$rstf().set_bit())
-                    });
+                    $(
+                        interrupt::free(|_| {
+                            let rstr = unsafe { &(*RCC::ptr()).$rstr };
+                            rstr.modify(|_, w| w.$rstf().set_bit());
+                            rstr.modify(|_, w| w.$rstf().clear_bit())
+                        });
+                    )?
                     self
                 }
+
+                #[inline(always)]
+                fn is_enabled(&self) -> bool {
+                    // NOTE(unsafe) atomic read with no side effects
+                    let enr = unsafe { &(*RCC::ptr()).$enr };
+                    enr.read().$enf().bit_is_set()
+                }
             }
         )*
     };
 }
 
 peripheral_reset_and_enable_control_gen!(
+    DMA1: Dma1 => (ahbpcenr, dma1en) ;
+    DMA2: Dma2 => (ahbpcenr, dma2en) ;
+    CRC: Crc => (ahbpcenr, crcen) ;
+    SRAM: Sram => (ahbpcenr, sramen) ;
+    FSMC: Fsmc => (ahbpcenr, fsmcen) ;
+    RNG: Rng => (ahbpcenr, rngen) ;
+    SDIO: Sdio => (ahbpcenr, sdioen) ;
+    USBHS: Usbhs => (ahbpcenr, usbhsen) ;
+    OTG_FS: OtgFs => (ahbpcenr, otgen) ;
+    DVP: Dvp => (ahbpcenr, dvpen) ;
+    ETH: Eth => (ahbpcenr, ethmacen) ;
+
     GPIOA: Gpioa => (apb2pcenr, iopaen, apb2prstr, ioparst) ;
     GPIOB: Gpiob => (apb2pcenr, iopben, apb2prstr, iopbrst) ;
     GPIOC: Gpioc => (apb2pcenr, iopcen, apb2prstr, iopcrst) ;
     GPIOD: Gpiod => (apb2pcenr, iopden, apb2prstr, iopdrst) ;
-    // GPIOE: Gpioe => (apb2pcenr, iopeen, apb2prstr, ioperst) ;
+    GPIOE: Gpioe => (apb2pcenr, iopeen, apb2prstr, ioperst) ;
 
     AFIO: Afio => (apb2pcenr, afioen, apb2prstr, afiorst) ;
 
     USART1: Usart1 => (apb2pcenr, usart1en, apb2prstr, usart1rst) ;
 
+    SPI1: Spi1 => (apb2pcenr, spi1en, apb2prstr, spi1rst) ;
+    SPI2: Spi2 => (apb1pcenr, spi2en, apb1prstr, spi2rst) ;
+    SPI3: Spi3 => (apb1pcenr, spi3en, apb1prstr, spi3rst) ;
+
+    I2C1: I2c1 => (apb1pcenr, i2c1en, apb1prstr, i2c1rst) ;
+    I2C2: I2c2 => (apb1pcenr, i2c2en, apb1prstr, i2c2rst) ;
+
     ADC1: Adc1 => (apb2pcenr, adc1en, apb2prstr, adc1rst) ;
     ADC2: Adc2 => (apb2pcenr, adc2en, apb2prstr, adc2rst) ;
+    DAC: Dac => (apb1pcenr, dacen, apb1prstr, dacrst) ;
+
+    TIM1: Tim1 => (apb2pcenr, tim1en, apb2prstr, tim1rst) ;
+    TIM2: Tim2 => (apb1pcenr, tim2en, apb1prstr, tim2rst) ;
+    TIM3: Tim3 => (apb1pcenr, tim3en, apb1prstr, tim3rst) ;
+    TIM4: Tim4 => (apb1pcenr, tim4en, apb1prstr, tim4rst) ;
+    TIM5: Tim5 => (apb1pcenr, tim5en, apb1prstr, tim5rst) ;
+    TIM6: Tim6 => (apb1pcenr, tim6en, apb1prstr, tim6rst) ;
+    TIM7: Tim7 => (apb1pcenr, tim7en, apb1prstr, tim7rst) ;
+
+    USART2: Usart2 => (apb1pcenr, usart2en, apb1prstr, usart2rst) ;
+    USART3: Usart3 => (apb1pcenr, usart3en, apb1prstr, usart3rst) ;
+    UART4: Uart4 => (apb1pcenr, uart4en, apb1prstr, uart4rst) ;
+    UART5: Uart5 => (apb1pcenr, uart5en, apb1prstr, uart5rst) ;
+    UART6: Uart6 => (apb1pcenr, uart6en, apb1prstr, uart6rst) ;
+    UART7: Uart7 => (apb1pcenr, uart7en, apb1prstr, uart7rst) ;
+    UART8: Uart8 => (apb1pcenr, uart8en, apb1prstr, uart8rst) ;
+
+    WWDG: Wwdg => (apb1pcenr, wwdgen, apb1prstr, wwdgrst) ;
+    USBD: Usbd => (apb1pcenr, usbden, apb1prstr, usbdrst) ;
+    CAN1: Can1 => (apb1pcenr, can1en, apb1prstr, can1rst) ;
+    CAN2: Can2 => (apb1pcenr, can2en, apb1prstr, can2rst) ;
+
+    PWR: Pwr => (apb1pcenr, pwren, apb1prstr, pwrrst) ;
+    BKP: Bkp => (apb1pcenr, bkpen, apb1prstr, bkprst) ;
 
 );
 
-
 /// ADCPRE, ADC clock source
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum AdcClkSel {
     PCLK2_Div2 = 0b00,
@@ -105,7 +179,22 @@ pub enum AdcClkSel {
 }
 
 impl PeripheralREC {
-    pub fn kernel_adc_clk_mux(&mut self, sel: AdcClkSel) -> &mut Self {
-        unimplemented!()
+    /// Select the ADC clock, PCLK2 divided by 2, 4, 6 or 8, after
+    /// [`freeze`](super::Rcc::freeze). Before it, use
+    /// [`Rcc::adcclk`](super::Rcc::adcclk).
+    ///
+    /// `clocks.adcclk` is updated to the new clock, which
+    /// [`Adc::new`](crate::adc::Adc::new) then checks against its 14MHz
+    /// limit and times its waits from. Copies of the clocks taken earlier,
+    /// and ADCs created earlier, still see the old clock: switch before
+    /// creating the ADCs.
+    pub fn kernel_adc_clk_mux(&mut self, sel: AdcClkSel, clocks: &mut CoreClocks) -> &mut Self {
+        interrupt::free(|_| {
+            let rcc = unsafe { &*RCC::ptr() };
+            rcc.cfgr0
+                .modify(|_, w| unsafe { w.adcpre().bits(sel as u8) });
+        });
+        clocks.adcclk = clocks.pclk2 / (2 * (sel as u32 + 1));
+        self
     }
 }
@@ -0,0 +1,227 @@
+//! Real time clock (RTC)
+//!
+//! A 32-bit seconds counter in the backup domain, which keeps counting
+//! across resets and, with VBAT, across power loss. The driver treats it
+//! as a Unix timestamp.
+//!
+//! ```
+//! let mut pwr = dp.PWR.constrain(ccdr.peripheral.PWR);
+//! let mut backup = pwr.enable_backup_domain(ccdr.peripheral.BKP);
+//!
+//! let mut rtc = Rtc::new(dp.RTC, RtcClock::Lse, &mut backup);
+//! if rtc.current_time() < 1_700_000_000 {
+//!     rtc.set_time(1_700_000_000);
+//! }
+//!
+//! rtc.set_alarm(rtc.current_time() + 60);
+//! rtc.listen(Event::Alarm);
+//! ```
+//!
+//! The RTC is only reinitialized when it is not running from the selected
+//! clock yet, so a reset keeps the time.
+
+use crate::pac::{RCC, RTC};
+use crate::pwr::BackupDomain;
+
+// RCC.BDCTLR bits
+const LSEON: u32 = 1 << 0;
+const LSERDY: u32 = 1 << 1;
+const RTCSEL_OFFSET: u32 = 8;
+const RTCSEL_MASK: u32 = 0b11 << RTCSEL_OFFSET;
+const RTCEN: u32 = 1 << 15;
+const BDRST: u32 = 1 << 16;
+// RCC.RSTSCKR bits
+const LSION: u32 = 1 << 0;
+const LSIRDY: u32 = 1 << 1;
+// CTLRL bits
+const RSF: u32 = 1 << 3;
+const CNF: u32 = 1 << 4;
+const RTOFF: u32 = 1 << 5;
+
+/// The RTC clock source
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RtcClock {
+    /// 32.768 kHz external crystal, the accurate choice
+    Lse,
+    /// ~40 kHz internal RC oscillator, off while the core is powered down
+    Lsi,
+}
+
+impl RtcClock {
+    fn rtcsel(self) -> u32 {
+        match self {
+            RtcClock::Lse => 0b01,
+            RtcClock::Lsi => 0b10,
+        }
+    }
+
+    fn frequency(self) -> u32 {
+        match self {
+            RtcClock::Lse => 32_768,
+            RtcClock::Lsi => 40_000,
+        }
+    }
+}
+
+/// RTC event
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// Every second
+    Second,
+    /// The counter reached the alarm
+    Alarm,
+    /// The counter wrapped around
+    Overflow,
+}
+
+impl Event {
+    // Same bit in CTLRH (enable) and CTLRL (flag)
+    fn bit(self) -> u32 {
+        match self {
+            Event::Second => 1 << 0,
+            Event::Alarm => 1 << 1,
+            Event::Overflow => 1 << 2,
+        }
+    }
+}
+
+/// Real time clock
+pub struct Rtc {
+    rtc: RTC,
+}
+
+impl Rtc {
+    /// Starts the RTC from `source`, counting seconds.
+    ///
+    /// Keeps the current time when the RTC already runs from `source`.
+    /// Switching the source resets the whole backup domain, including the
+    /// backup data registers. Blocks until the oscillator is stable.
+    pub fn new(rtc: RTC, source: RtcClock, _backup: &mut BackupDomain) -> Self {
+        // NOTE(unsafe) BDCTLR and the LSI bits of RSTSCKR are only written
+        // here, with the backup domain unlocked
+        let rcc = unsafe { &*RCC::ptr() };
+
+        match source {
+            RtcClock::Lse => {
+                rcc.bdctlr
+                    .modify(|r, w| unsafe { w.bits(r.bits() | LSEON) });
+                while rcc.bdctlr.read().bits() & LSERDY == 0 {}
+            }
+            RtcClock::Lsi => {
+                rcc.rstsckr
+                    .modify(|r, w| unsafe { w.bits(r.bits() | LSION) });
+                while rcc.rstsckr.read().bits() & LSIRDY == 0 {}
+            }
+        }
+
+        let running = source.rtcsel() << RTCSEL_OFFSET | RTCEN;
+        let bdctlr = rcc.bdctlr.read().bits();
+        let mut rtc = Rtc { rtc };
+
+        if bdctlr & (RTCSEL_MASK | RTCEN) != running {
+            if bdctlr & RTCSEL_MASK != 0 {
+                // RTCSEL can only be changed by a backup domain reset
+                let lse = bdctlr & LSEON;
+                rcc.bdctlr.write(|w| unsafe { w.bits(BDRST) });
+                rcc.bdctlr.write(|w| unsafe { w.bits(lse) });
+                while source == RtcClock::Lse && rcc.bdctlr.read().bits() & LSERDY == 0 {}
+            }
+            rcc.bdctlr
+                .modify(|r, w| unsafe { w.bits(r.bits() | running) });
+
+            let prescaler = source.frequency() - 1;
+            rtc.configure(|rtc| {
+                rtc.pscrh.write(|w| unsafe { w.bits(prescaler >> 16) });
+                rtc.pscrl.write(|w| unsafe { w.bits(prescaler & 0xFFFF) });
+            });
+        }
+
+        // The APB side of the registers must resync after a reset
+        rtc.rtc
+            .ctlrl
+            .modify(|r, w| unsafe { w.bits(r.bits() & !RSF) });
+        while rtc.rtc.ctlrl.read().bits() & RSF == 0 {}
+
+        rtc
+    }
+
+    /// Runs `f` in configuration mode, where the prescaler, counter and
+    /// alarm registers are writable
+    fn configure(&mut self, f: impl FnOnce(&RTC)) {
+        while self.rtc.ctlrl.read().bits() & RTOFF == 0 {}
+        self.rtc
+            .ctlrl
+            .modify(|r, w| unsafe { w.bits(r.bits() | CNF) });
+        f(&self.rtc);
+        self.rtc
+            .ctlrl
+            .modify(|r, w| unsafe { w.bits(r.bits() & !CNF) });
+        while self.rtc.ctlrl.read().bits() & RTOFF == 0 {}
+    }
+
+    /// Sets the current time, in seconds since the Unix epoch
+    pub fn set_time(&mut self, seconds: u32) {
+        self.configure(|rtc| {
+            rtc.cnth.write(|w| unsafe { w.bits(seconds >> 16) });
+            rtc.cntl.write(|w| unsafe { w.bits(seconds & 0xFFFF) });
+        });
+    }
+
+    /// Returns the current time, in seconds since the Unix epoch
+    pub fn current_time(&self) -> u32 {
+        // Retry if CNTL wrapped between the two halves
+        loop {
+            let high = self.rtc.cnth.read().bits();
+            let low = self.rtc.cntl.read().bits();
+            if self.rtc.cnth.read().bits() == high {
+                return high << 16 | low;
+            }
+        }
+    }
+
+    /// Raises [`Event::Alarm`] when the time reaches `seconds`, since the
+    /// Unix epoch. The alarm fires once.
+    pub fn set_alarm(&mut self, seconds: u32) {
+        self.clear_interrupt(Event::Alarm);
+        self.configure(|rtc| {
+            rtc.alrmh.write(|w| unsafe { w.bits(seconds >> 16) });
+            rtc.alrml.write(|w| unsafe { w.bits(seconds & 0xFFFF) });
+        });
+    }
+
+    /// Starts raising the RTC interrupt for `event`
+    pub fn listen(&mut self, event: Event) {
+        while self.rtc.ctlrl.read().bits() & RTOFF == 0 {}
+        self.rtc
+            .ctlrh
+            .modify(|r, w| unsafe { w.bits(r.bits() | event.bit()) });
+    }
+
+    /// Stops raising the RTC interrupt for `event`
+    pub fn unlisten(&mut self, event: Event) {
+        while self.rtc.ctlrl.read().bits() & RTOFF == 0 {}
+        self.rtc
+            .ctlrh
+            .modify(|r, w| unsafe { w.bits(r.bits() & !event.bit()) });
+    }
+
+    /// Whether `event` occurred since its flag was last cleared
+    pub fn is_pending(&self, event: Event) -> bool {
+        self.rtc.ctlrl.read().bits() & event.bit() != 0
+    }
+
+    /// Clears the flag of `event`
+    pub fn clear_interrupt(&mut self, event: Event) {
+        while self.rtc.ctlrl.read().bits() & RTOFF == 0 {}
+        self.rtc
+            .ctlrl
+            .modify(|r, w| unsafe { w.bits(r.bits() & !event.bit()) });
+    }
+
+    /// Releases the RTC, which keeps counting
+    pub fn release(self) -> RTC {
+        self.rtc
+    }
+}
@@ -0,0 +1,945 @@
+//! Serial communication using USART
+//!
+//! ```
+//! let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
+//!
+//! let serial = Serial::new(
+//!     dp.USART1,
+//!     (gpioa.pa9, gpioa.pa10),
+//!     Config::default().baudrate(115_200.bps()),
+//!     ccdr.peripheral.USART1,
+//!     &ccdr.clocks,
+//! );
+//! let (mut tx, mut rx) = serial.split();
+//!
+//! block!(tx.write(b'X')).ok();
+//! let received = block!(rx.read());
+//! ```
+//!
+//! The pins are only checked against the default and remapped pin sets of
+//! the USART. Selecting the mapping that matches the pins is left to
+//! [`Afio`](crate::afio::Afio), e.g. `remap_usart1(Usart1Remap::Remap1)` for
+//! PB6/PB7. UART4-UART8 only accept their default pins.
+//!
+//! [`Serial::new`] puts the pins into the modes the USART needs, TX
+//! alternate push-pull and RX floating input. Pins that are already set
+//! up, e.g. RX with a pull-up, go through
+//! [`Serial::new_from_configured`], which leaves them alone.
+//!
+//! USART1 is clocked from PCLK2, all other instances from PCLK1. The same
+//! driver can also be built through [`SerialExt`]:
+//!
+//! ```
+//! let gps = dp.USART2.serial(
+//!     (gpioa.pa2, gpioa.pa3),
+//!     Config::default().baudrate(9600.bps()),
+//!     ccdr.peripheral.USART2,
+//!     &ccdr.clocks,
+//! );
+//! ```
+//!
+//! At high baud rates, polling [`Rx`] loses bytes as soon as the CPU is busy
+//! for longer than one character. Either [`listen`](Rx::listen) for `Rxne`
+//! and read from the interrupt handler, or let a DMA channel fill a ring
+//! buffer in the background:
+//!
+//! ```
+//! static mut BUF: [u8; 256] = [0; 256];
+//!
+//! let dma1 = dp.DMA1.split(ccdr.peripheral.DMA1);
+//! let mut ring = rx.with_dma_ring(dma1.ch5, unsafe { &mut BUF });
+//!
+//! let mut chunk = [0; 32];
+//! let n = ring.read(&mut chunk);
+//! ```
+//!
+//! Packets of a known length are sent and received in one go with
+//! [`Tx::write_dma`] and [`Rx::read_dma`]. The DMA channels are fixed:
+//!
+//! | USART  | TX            | RX            |
+//! |--------|---------------|---------------|
+//! | USART1 | DMA1 ch4      | DMA1 ch5      |
+//! | USART2 | DMA1 ch7      | DMA1 ch6      |
+//! | USART3 | DMA1 ch2      | DMA1 ch3      |
+//! | UART4  | DMA2 ch5      | DMA2 ch3      |
+//!
+//! UART5-UART8 are served by DMA2 channels 8-11, which are not supported.
+//!
+//! On multidrop networks, [`Rx::mute`] keeps the receiver quiet until the
+//! next message or a message with the node address.
+//!
+//! The USART has no auto-baud detection, the baud rate is always the one in
+//! [`Config`]. A peer with an unknown baud rate can be measured by timing
+//! its start bit with an input capture on the RX pin before the USART is
+//! set up.
+//!
+//! TX and RX can't be swapped and the signals can't be inverted, the USART
+//! has no such controls. Swapped pins need a different remap or a board
+//! change, inverted-logic peers an external inverter.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use riscv::interrupt;
+
+use crate::dma::{Channel, Direction, DmaPeripheral, Transfer, WordSize};
+
+use crate::gpio::{Alternate, Floating, Input, Pin, PinMode, PushPull};
+use crate::hal::{blocking, serial};
+use crate::pac::{usart1, UART4, UART5, UART6, UART7, UART8, USART1, USART2, USART3};
+use crate::rcc::rec::{self, ResetEnable};
+use crate::rcc::CoreClocks;
+use crate::time::{Bps, Hertz};
+
+pub mod config {
+    //! Serial configuration
+
+    use crate::time::{Bps, U32Ext};
+
+    /// Number of data bits in a word, not counting the parity bit
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum WordLength {
+        DataBits8,
+        /// Not available together with parity, the hardware frames at most
+        /// 9 bits
+        DataBits9,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum Parity {
+        ParityNone,
+        ParityEven,
+        ParityOdd,
+    }
+
+    /// CTLR2.STOP
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[repr(u8)]
+    pub enum StopBits {
+        /// 1 stop bit
+        STOP1 = 0b00,
+        /// 0.5 stop bits
+        STOP0P5 = 0b01,
+        /// 2 stop bits
+        STOP2 = 0b10,
+        /// 1.5 stop bits
+        STOP1P5 = 0b11,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct Config {
+        pub baudrate: Bps,
+        pub wordlength: WordLength,
+        pub parity: Parity,
+        pub stopbits: StopBits,
+    }
+
+    impl Config {
+        #[must_use]
+        pub fn baudrate(mut self, baudrate: Bps) -> Self {
+            self.baudrate = baudrate;
+            self
+        }
+
+        #[must_use]
+        pub fn parity_none(mut self) -> Self {
+            self.parity = Parity::ParityNone;
+            self
+        }
+
+        #[must_use]
+        pub fn parity_even(mut self) -> Self {
+            self.parity = Parity::ParityEven;
+            self
+        }
+
+        #[must_use]
+        pub fn parity_odd(mut self) -> Self {
+            self.parity = Parity::ParityOdd;
+            self
+        }
+
+        #[must_use]
+        pub fn wordlength_8(mut self) -> Self {
+            self.wordlength = WordLength::DataBits8;
+            self
+        }
+
+        #[must_use]
+        pub fn wordlength_9(mut self) -> Self {
+            self.wordlength = WordLength::DataBits9;
+            self
+        }
+
+        #[must_use]
+        pub fn stopbits(mut self, stopbits: StopBits) -> Self {
+            self.stopbits = stopbits;
+            self
+        }
+    }
+
+    impl Default for Config {
+        /// 115200 baud, 8 data bits, no parity, 1 stop bit
+        fn default() -> Config {
+            Config {
+                baudrate: 115_200_u32.bps(),
+                wordlength: WordLength::DataBits8,
+                parity: Parity::ParityNone,
+                stopbits: StopBits::STOP1,
+            }
+        }
+    }
+}
+
+pub use config::Config;
+
+/// Interrupt events of the receiver
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// A word was received (RXNE), also fires on overrun
+    Rxne,
+    /// The line went idle for one frame after receiving (IDLE)
+    Idle,
+}
+
+/// How a muted receiver wakes up, see [`Rx::mute`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeUp {
+    /// On an idle line, i.e. at the start of the next message
+    IdleLine,
+    /// On a word with its most significant bit set (the address mark) and
+    /// the node address, see [`Rx::set_address`], in its low 4 bits
+    AddressMark,
+}
+
+/// Serial error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Framing error
+    Framing,
+    /// Noise error
+    Noise,
+    /// RX buffer overrun
+    Overrun,
+    /// Parity check error
+    Parity,
+}
+
+/// A USART peripheral
+pub trait Instance: crate::Sealed {
+    /// The Reset and Enable control block for this USART
+    type Rec: ResetEnable;
+
+    /// Pointer to the register block
+    fn ptr() -> *const usart1::RegisterBlock;
+
+    /// The bus clock the baud rate is derived from
+    fn clock(clocks: &CoreClocks) -> Hertz;
+}
+
+/// A pin that can be used as the TX line of `USART`
+pub trait PinTx<USART> {}
+
+/// A pin that can be used as the RX line of `USART`
+pub trait PinRx<USART> {}
+
+/// A `(TX, RX)` pin pair
+pub trait Pins<USART> {}
+
+impl<USART, TX, RX> Pins<USART> for (TX, RX)
+where
+    TX: PinTx<USART>,
+    RX: PinRx<USART>,
+{
+}
+
+/// A `(TX, RX)` pin pair in any mode, that [`Serial::new`] configures
+pub trait IntoPins<USART> {
+    /// The pins in the modes the USART needs
+    type Pins: Pins<USART>;
+
+    #[doc(hidden)]
+    fn into_pins(self) -> Self::Pins;
+}
+
+impl<USART, const TP: char, const TN: u8, TM, const RP: char, const RN: u8, RM> IntoPins<USART>
+    for (Pin<TP, TN, TM>, Pin<RP, RN, RM>)
+where
+    TM: PinMode,
+    RM: PinMode,
+    Pin<TP, TN, Alternate<PushPull>>: PinTx<USART>,
+    Pin<RP, RN, Input<Floating>>: PinRx<USART>,
+{
+    type Pins = (
+        Pin<TP, TN, Alternate<PushPull>>,
+        Pin<RP, RN, Input<Floating>>,
+    );
+
+    fn into_pins(self) -> Self::Pins {
+        (self.0.into_alternate(), self.1.into_floating_input())
+    }
+}
+
+macro_rules! usart {
+    ($($USARTX:ident: ($Rec:ident, $pclkX:ident) => {
+        tx: [$(($TxP:literal, $TxN:literal)),*],
+        rx: [$(($RxP:literal, $RxN:literal)),*],
+    })+) => {
+        $(
+            impl crate::Sealed for $USARTX {}
+
+            impl Instance for $USARTX {
+                type Rec = rec::$Rec;
+
+                #[inline(always)]
+                fn ptr() -> *const usart1::RegisterBlock {
+                    $USARTX::ptr()
+                }
+
+                #[inline(always)]
+                fn clock(clocks: &CoreClocks) -> Hertz {
+                    clocks.$pclkX()
+                }
+            }
+
+            $(
+                impl PinTx<$USARTX> for Pin<$TxP, $TxN, Alternate<PushPull>> {}
+            )*
+            $(
+                impl<MODE> PinRx<$USARTX> for Pin<$RxP, $RxN, Input<MODE>> {}
+            )*
+        )+
+    };
+}
+
+usart! {
+    USART1: (Usart1, pclk2) => {
+        tx: [('A', 9), ('B', 6), ('B', 15), ('A', 6)],
+        rx: [('A', 10), ('B', 7), ('A', 8), ('A', 7)],
+    }
+    USART2: (Usart2, pclk1) => {
+        tx: [('A', 2), ('D', 5)],
+        rx: [('A', 3), ('D', 6)],
+    }
+    USART3: (Usart3, pclk1) => {
+        tx: [('B', 10), ('C', 10), ('D', 8)],
+        rx: [('B', 11), ('C', 11), ('D', 9)],
+    }
+    UART4: (Uart4, pclk1) => {
+        tx: [('C', 10)],
+        rx: [('C', 11)],
+    }
+    UART5: (Uart5, pclk1) => {
+        tx: [('C', 12)],
+        rx: [('D', 2)],
+    }
+    UART6: (Uart6, pclk1) => {
+        tx: [('C', 0)],
+        rx: [('C', 1)],
+    }
+    UART7: (Uart7, pclk1) => {
+        tx: [('C', 2)],
+        rx: [('C', 3)],
+    }
+    UART8: (Uart8, pclk1) => {
+        tx: [('C', 4)],
+        rx: [('C', 5)],
+    }
+}
+
+macro_rules! usart_dma {
+    ($($USARTX:ident: (tx: ($TxD:literal, $TxN:literal), rx: ($RxD:literal, $RxN:literal)),)+) => {
+        $(
+            unsafe impl DmaPeripheral<$TxD, $TxN> for Tx<$USARTX> {
+                type Word = u8;
+
+                fn address(&self) -> u32 {
+                    // NOTE(unsafe) address only
+                    unsafe { &(*$USARTX::ptr()).datar as *const _ as u32 }
+                }
+
+                fn start_dma(&mut self) {
+                    interrupt::free(|_| unsafe {
+                        (*$USARTX::ptr()).ctlr3.modify(|_, w| w.dmat().set_bit())
+                    });
+                }
+
+                fn stop_dma(&mut self) {
+                    interrupt::free(|_| unsafe {
+                        (*$USARTX::ptr()).ctlr3.modify(|_, w| w.dmat().clear_bit())
+                    });
+                }
+            }
+
+            unsafe impl DmaPeripheral<$RxD, $RxN> for Rx<$USARTX> {
+                type Word = u8;
+
+                fn address(&self) -> u32 {
+                    // NOTE(unsafe) address only
+                    unsafe { &(*$USARTX::ptr()).datar as *const _ as u32 }
+                }
+
+                fn start_dma(&mut self) {
+                    interrupt::free(|_| unsafe {
+                        (*$USARTX::ptr()).ctlr3.modify(|_, w| w.dmar().set_bit())
+                    });
+                }
+
+                fn stop_dma(&mut self) {
+                    interrupt::free(|_| unsafe {
+                        (*$USARTX::ptr()).ctlr3.modify(|_, w| w.dmar().clear_bit())
+                    });
+                }
+            }
+        )+
+    };
+}
+
+usart_dma! {
+    USART1: (tx: (1, 4), rx: (1, 5)),
+    USART2: (tx: (1, 7), rx: (1, 6)),
+    USART3: (tx: (1, 2), rx: (1, 3)),
+    UART4: (tx: (2, 5), rx: (2, 3)),
+}
+
+/// Extension trait to build a [`Serial`] from a USART peripheral
+pub trait SerialExt: Instance + Sized {
+    /// See [`Serial::new`]
+    fn serial<PINS: IntoPins<Self>>(
+        self,
+        pins: PINS,
+        config: Config,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> Serial<Self, PINS::Pins>;
+}
+
+impl<USART: Instance> SerialExt for USART {
+    fn serial<PINS: IntoPins<Self>>(
+        self,
+        pins: PINS,
+        config: Config,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> Serial<Self, PINS::Pins> {
+        Serial::new(self, pins, config, prec, clocks)
+    }
+}
+
+/// Serial abstraction
+pub struct Serial<USART: Instance, PINS> {
+    usart: USART,
+    pins: PINS,
+    prec: USART::Rec,
+}
+
+/// Serial receiver
+pub struct Rx<USART> {
+    _usart: PhantomData<USART>,
+}
+
+/// Serial transmitter
+pub struct Tx<USART> {
+    _usart: PhantomData<USART>,
+}
+
+impl<USART: Instance, PINS: Pins<USART>> Serial<USART, PINS> {
+    /// Puts TX into alternate push-pull and RX into floating input mode,
+    /// then configures the USART as [`new_from_configured`](Serial::new_from_configured).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the baud rate is out of range for the bus clock of the
+    /// USART, or if 9 data bits are combined with parity.
+    pub fn new<RAW>(
+        usart: USART,
+        pins: RAW,
+        config: Config,
+        prec: USART::Rec,
+        clocks: &CoreClocks,
+    ) -> Self
+    where
+        RAW: IntoPins<USART, Pins = PINS>,
+    {
+        Self::new_from_configured(usart, pins.into_pins(), config, prec, clocks)
+    }
+
+    /// Configures the USART and enables its transmitter and receiver,
+    /// without touching the pins.
+    ///
+    /// TX must already be in `Alternate<PushPull>` mode and RX in an
+    /// `Input` mode, with any pull.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the baud rate is out of range for the bus clock of the
+    /// USART, or if 9 data bits are combined with parity.
+    pub fn new_from_configured(
+        usart: USART,
+        pins: PINS,
+        config: Config,
+        prec: USART::Rec,
+        clocks: &CoreClocks,
+    ) -> Self {
+        use config::{Parity, WordLength};
+
+        let prec = prec.enable().reset();
+
+        // NOTE(unsafe) we own the USART
+        let rb = unsafe { &*USART::ptr() };
+
+        // USARTDIV = pclk / (16 * baud), BRR holds it as 12.4 fixed point
+        let Bps(baud) = config.baudrate;
+        let pclk = USART::clock(clocks).raw();
+        let div = (pclk + baud / 2) / baud;
+        assert!((16..=0xFFFF).contains(&div), "baud rate out of range");
+        rb.brr.write(|w| unsafe { w.bits(div) });
+
+        let parity = config.parity != Parity::ParityNone;
+        // M counts the parity bit
+        let m = match (config.wordlength, parity) {
+            (WordLength::DataBits8, false) => false,
+            (WordLength::DataBits8, true) | (WordLength::DataBits9, false) => true,
+            (WordLength::DataBits9, true) => panic!("9 data bits with parity"),
+        };
+
+        rb.ctlr2
+            .modify(|_, w| unsafe { w.stop().bits(config.stopbits as u8) });
+        rb.ctlr3.reset();
+        rb.ctlr1.write(|w| {
+            w.ue()
+                .set_bit()
+                .m()
+                .bit(m)
+                .pce()
+                .bit(parity)
+                .ps()
+                .bit(config.parity == Parity::ParityOdd)
+                .te()
+                .set_bit()
+                .re()
+                .set_bit()
+        });
+
+        Serial { usart, pins, prec }
+    }
+
+    /// Splits the serial into its transmitter and receiver halves
+    pub fn split(self) -> (Tx<USART>, Rx<USART>) {
+        (
+            Tx {
+                _usart: PhantomData,
+            },
+            Rx {
+                _usart: PhantomData,
+            },
+        )
+    }
+
+    /// Disables the USART and its clock, and releases the peripheral, the
+    /// pins and the REC token for a later [`new`](Serial::new)
+    pub fn release(self) -> (USART, PINS, USART::Rec) {
+        // NOTE(unsafe) we own the USART
+        unsafe { (*USART::ptr()).ctlr1.reset() };
+        (self.usart, self.pins, self.prec.disable())
+    }
+}
+
+impl<USART: Instance> Rx<USART> {
+    /// Enables the interrupt for `event`
+    pub fn listen(&mut self, event: Event) {
+        // CTLR1 is shared with Tx
+        interrupt::free(|_| {
+            let rb = unsafe { &*USART::ptr() };
+            rb.ctlr1.modify(|_, w| match event {
+                Event::Rxne => w.rxneie().set_bit(),
+                Event::Idle => w.idleie().set_bit(),
+            })
+        });
+    }
+
+    /// Disables the interrupt for `event`
+    pub fn unlisten(&mut self, event: Event) {
+        interrupt::free(|_| {
+            let rb = unsafe { &*USART::ptr() };
+            rb.ctlr1.modify(|_, w| match event {
+                Event::Rxne => w.rxneie().clear_bit(),
+                Event::Idle => w.idleie().clear_bit(),
+            })
+        });
+    }
+
+    /// Whether a received word is waiting in DATAR
+    pub fn is_rx_not_empty(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*USART::ptr()).statr.read().rxne().bit_is_set() }
+    }
+
+    /// Whether the line went idle after the last received word
+    pub fn is_idle(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*USART::ptr()).statr.read().idle().bit_is_set() }
+    }
+
+    /// Clears the idle flag, which is required to leave the `Idle`
+    /// interrupt handler.
+    ///
+    /// The flag is cleared by reading STATR then DATAR, so a word that
+    /// arrives in between is dropped.
+    pub fn clear_idle(&mut self) {
+        // NOTE(unsafe) reads only, clearing IDLE is the intended side effect
+        let rb = unsafe { &*USART::ptr() };
+        rb.statr.read();
+        rb.datar.read();
+    }
+
+    /// Sets the 4-bit node address matched in [`WakeUp::AddressMark`] mode
+    pub fn set_address(&mut self, address: u8) {
+        // CTLR2 is shared with Tx
+        interrupt::free(|_| {
+            let rb = unsafe { &*USART::ptr() };
+            rb.ctlr2
+                .modify(|_, w| unsafe { w.add().bits(address & 0xf) })
+        });
+    }
+
+    /// Selects how the receiver leaves mute mode
+    pub fn set_wakeup(&mut self, wakeup: WakeUp) {
+        interrupt::free(|_| {
+            let rb = unsafe { &*USART::ptr() };
+            rb.ctlr1
+                .modify(|_, w| w.wake().bit(wakeup == WakeUp::AddressMark))
+        });
+    }
+
+    /// Enters mute mode, for multidrop (e.g. RS-485) networks.
+    ///
+    /// A muted receiver sets no flags and raises no interrupts until the
+    /// wake-up condition from [`set_wakeup`](Rx::set_wakeup) occurs, so a
+    /// node only processes the messages addressed to it. In
+    /// [`WakeUp::AddressMark`] mode, a non-matching address word mutes the
+    /// receiver again by itself.
+    ///
+    /// ```
+    /// rx.set_address(0x3);
+    /// rx.set_wakeup(WakeUp::AddressMark);
+    /// rx.mute();
+    /// ```
+    ///
+    /// The address mark is the MSB of the frame, so use
+    /// [`wordlength_9`](Config::wordlength_9) to keep 8 bits of payload.
+    /// [`read_u16`](Rx::read_u16) then returns the mark as bit 8, which
+    /// tells address words from data, and the master sends it with
+    /// [`Tx::write_u16`].
+    pub fn mute(&mut self) {
+        interrupt::free(|_| {
+            let rb = unsafe { &*USART::ptr() };
+            rb.ctlr1.modify(|_, w| w.rwu().set_bit())
+        });
+    }
+
+    /// Leaves mute mode without waiting for the wake-up condition
+    pub fn unmute(&mut self) {
+        interrupt::free(|_| {
+            let rb = unsafe { &*USART::ptr() };
+            rb.ctlr1.modify(|_, w| w.rwu().clear_bit())
+        });
+    }
+
+    /// Whether the receiver is muted, it unmutes itself on wake-up
+    pub fn is_muted(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*USART::ptr()).ctlr1.read().rwu().bit_is_set() }
+    }
+
+    /// Lets DMA `channel` continuously receive into `buf`, which is used as
+    /// a ring buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is empty or longer than 65535 bytes.
+    pub fn with_dma_ring<const D: u8, const N: u8>(
+        mut self,
+        mut channel: Channel<D, N>,
+        buf: &'static mut [u8],
+    ) -> RxRing<USART, D, N>
+    where
+        Self: DmaPeripheral<D, N, Word = u8>,
+    {
+        assert!(!buf.is_empty() && buf.len() <= usize::from(u16::MAX));
+
+        channel.stop();
+        channel.set_peripheral_address(self.address(), false);
+        channel.set_memory_address(buf.as_mut_ptr() as u32, true);
+        channel.set_transfer_length(buf.len() as u16);
+        channel.set_word_size(WordSize::Bits8);
+        channel.set_direction(Direction::PeripheralToMemory);
+        channel.set_circular(true);
+        // The buffer is handed over to the DMA from here on
+        compiler_fence(Ordering::Release);
+        channel.start();
+        self.start_dma();
+
+        RxRing {
+            rx: self,
+            channel,
+            buf,
+            read: 0,
+        }
+    }
+
+    /// Receives exactly `buf.len()` bytes through DMA `channel` and
+    /// returns right away, [`Transfer::wait`] hands the filled buffer back.
+    ///
+    /// A byte already waiting in DATAR is the first one stored. Receive
+    /// errors don't stop the transfer, the byte in error is stored as is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is longer than 65535 bytes.
+    pub fn read_dma<const D: u8, const N: u8>(
+        self,
+        channel: Channel<D, N>,
+        buf: &'static mut [u8],
+    ) -> Transfer<&'static mut [u8], Channel<D, N>, Self>
+    where
+        Self: DmaPeripheral<D, N, Word = u8>,
+    {
+        Transfer::read(channel, self, buf)
+    }
+
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        self.read_u16().map(|word| word as u8)
+    }
+
+    /// Reads a whole word, with bit 8 of 9-bit words, e.g. the address mark
+    /// in [`WakeUp::AddressMark`] mode. With parity, bit 7 or 8 holds the
+    /// parity bit.
+    pub fn read_u16(&mut self) -> nb::Result<u16, Error> {
+        // NOTE(unsafe) atomic read with no side effects
+        let rb = unsafe { &*USART::ptr() };
+        let statr = rb.statr.read();
+
+        // Reading STATR then DATAR clears the error flags
+        let err = if statr.pe().bit_is_set() {
+            Some(Error::Parity)
+        } else if statr.fe().bit_is_set() {
+            Some(Error::Framing)
+        } else if statr.ne().bit_is_set() {
+            Some(Error::Noise)
+        } else if statr.ore().bit_is_set() {
+            Some(Error::Overrun)
+        } else {
+            None
+        };
+
+        if let Some(err) = err {
+            rb.datar.read();
+            Err(nb::Error::Other(err))
+        } else if statr.rxne().bit_is_set() {
+            Ok(rb.datar.read().bits() as u16 & 0x1FF)
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<USART: Instance> Tx<USART> {
+    /// Sends a break character, a whole frame of low level, after the
+    /// current word
+    pub fn send_break(&mut self) {
+        // CTLR1 is shared with Rx, SBK clears itself once the break is sent
+        interrupt::free(|_| {
+            let rb = unsafe { &*USART::ptr() };
+            rb.ctlr1.modify(|_, w| w.sbk().set_bit())
+        });
+    }
+
+    /// Sends `buf` through DMA `channel` and returns right away.
+    ///
+    /// [`Transfer::wait`] returns the buffer once the DMA has handed the
+    /// last byte to the USART, which then still shifts out up to two
+    /// bytes. Wait for [`flush`](serial::Write::flush) as well before
+    /// turning an RS-485 driver around or disabling the USART:
+    ///
+    /// ```
+    /// let (buf, ch4, mut tx) = tx.write_dma(dma1.ch4, &PACKET).wait().unwrap();
+    /// block!(tx.flush())?;
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is longer than 65535 bytes.
+    pub fn write_dma<const D: u8, const N: u8>(
+        self,
+        channel: Channel<D, N>,
+        buf: &'static [u8],
+    ) -> Transfer<&'static [u8], Channel<D, N>, Self>
+    where
+        Self: DmaPeripheral<D, N, Word = u8>,
+    {
+        // TC is still set from the last transmission, clear it so that
+        // flush waits for this one. Flags are cleared by writing 0 and
+        // writing 1 has no effect, so RXNE, LBD or CTS setting meanwhile
+        // are kept, which a read-modify-write could lose.
+        const TC: u32 = 1 << 6;
+        // NOTE(unsafe) TC is only cleared from Tx
+        unsafe { (*USART::ptr()).statr.write(|w| w.bits(!TC)) };
+        Transfer::write(channel, self, buf)
+    }
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Error> {
+        self.write_u16(u16::from(word))
+    }
+
+    /// Sends a whole word, bit 8 included with 9-bit words, e.g. to set the
+    /// address mark of an address word
+    pub fn write_u16(&mut self, word: u16) -> nb::Result<(), Error> {
+        // NOTE(unsafe) atomic read/write, DATAR is only written from Tx
+        let rb = unsafe { &*USART::ptr() };
+        if rb.statr.read().txe().bit_is_set() {
+            rb.datar
+                .write(|w| unsafe { w.bits(u32::from(word & 0x1FF)) });
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Error> {
+        // NOTE(unsafe) atomic read with no side effects
+        let rb = unsafe { &*USART::ptr() };
+        if rb.statr.read().tc().bit_is_set() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<USART: Instance> serial::Read<u8> for Rx<USART> {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        Rx::read(self)
+    }
+}
+
+impl<USART: Instance> serial::Write<u8> for Tx<USART> {
+    type Error = Error;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Error> {
+        Tx::write(self, word)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Error> {
+        Tx::flush(self)
+    }
+}
+
+impl<USART: Instance> blocking::serial::write::Default<u8> for Tx<USART> {}
+
+impl<USART: Instance> fmt::Write for Tx<USART> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.bytes()
+            .try_for_each(|c| nb::block!(Tx::write(self, c)))
+            .map_err(|_| fmt::Error)
+    }
+}
+
+impl<USART: Instance, PINS> serial::Read<u8> for Serial<USART, PINS> {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        Rx::<USART> {
+            _usart: PhantomData,
+        }
+        .read()
+    }
+}
+
+impl<USART: Instance, PINS> serial::Write<u8> for Serial<USART, PINS> {
+    type Error = Error;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Error> {
+        Tx::<USART> {
+            _usart: PhantomData,
+        }
+        .write(word)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Error> {
+        Tx::<USART> {
+            _usart: PhantomData,
+        }
+        .flush()
+    }
+}
+
+impl<USART: Instance, PINS> blocking::serial::write::Default<u8> for Serial<USART, PINS> {}
+
+/// Continuous serial reception into a ring buffer, see
+/// [`Rx::with_dma_ring`]
+///
+/// Only the bytes received since the last [`read`](RxRing::read) are kept
+/// track of. If more than the buffer length arrives in between, the oldest
+/// bytes are overwritten without notice, so poll at least once per buffer
+/// length worth of characters, e.g. from the `Idle` interrupt.
+pub struct RxRing<USART, const D: u8, const N: u8> {
+    rx: Rx<USART>,
+    channel: Channel<D, N>,
+    buf: &'static mut [u8],
+    read: usize,
+}
+
+impl<USART: Instance, const D: u8, const N: u8> RxRing<USART, D, N>
+where
+    Rx<USART>: DmaPeripheral<D, N>,
+{
+    fn write_position(&self) -> usize {
+        let remaining = usize::from(self.channel.get_transfer_length());
+        (self.buf.len() - remaining) % self.buf.len()
+    }
+
+    /// Number of bytes received since the last read
+    pub fn available(&self) -> usize {
+        let len = self.buf.len();
+        (self.write_position() + len - self.read) % len
+    }
+
+    /// Moves received bytes into `out` and returns how many were copied
+    pub fn read(&mut self, out: &mut [u8]) -> usize {
+        let count = self.available().min(out.len());
+        compiler_fence(Ordering::Acquire);
+        for byte in &mut out[..count] {
+            // NOTE(unsafe) in bounds, volatile as the DMA writes the buffer
+            *byte = unsafe { ptr::read_volatile(self.buf.as_ptr().add(self.read)) };
+            self.read = (self.read + 1) % self.buf.len();
+        }
+        count
+    }
+
+    /// The receiver, for interrupt and idle flag control
+    pub fn rx(&mut self) -> &mut Rx<USART> {
+        &mut self.rx
+    }
+
+    /// Stops the reception and releases the receiver, the DMA channel and
+    /// the buffer
+    pub fn release(mut self) -> (Rx<USART>, Channel<D, N>, &'static mut [u8]) {
+        self.rx.stop_dma();
+        self.channel.stop();
+        compiler_fence(Ordering::Acquire);
+        (self.rx, self.channel, self.buf)
+    }
+}
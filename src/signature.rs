@@ -0,0 +1,28 @@
+//! Device electronic signature
+//!
+//! Factory programmed values in system memory: the flash size and a 96-bit
+//! unique ID, e.g. to derive a USB serial number.
+//!
+//! ```
+//! let uid = signature::unique_id();
+//! let kib = signature::flash_size_kb();
+//! ```
+
+use core::ptr;
+
+// ESIG_FLACAP
+const FLASH_SIZE: *const u16 = 0x1FFF_F7E0 as *const u16;
+// ESIG_UNIID1-3
+const UNIQUE_ID: *const [u8; 12] = 0x1FFF_F7E8 as *const [u8; 12];
+
+/// Size of the code flash, in KiB
+pub fn flash_size_kb() -> u16 {
+    // NOTE(unsafe) read-only system memory
+    unsafe { ptr::read_volatile(FLASH_SIZE) }
+}
+
+/// The 96-bit unique device ID, lowest address first
+pub fn unique_id() -> [u8; 12] {
+    // NOTE(unsafe) read-only system memory
+    unsafe { ptr::read_volatile(UNIQUE_ID) }
+}
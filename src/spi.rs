@@ -0,0 +1,426 @@
+//! Serial Peripheral Interface (SPI) bus, master mode
+//!
+//! ```
+//! let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
+//!
+//! let mut spi = Spi::new(
+//!     dp.SPI1,
+//!     (gpioa.pa5, gpioa.pa6, gpioa.pa7),
+//!     spi::MODE_0,
+//!     8.MHz(),
+//!     ccdr.peripheral.SPI1,
+//!     &ccdr.clocks,
+//! );
+//!
+//! let mut buf = [0x9F, 0, 0, 0];
+//! spi.transfer(&mut buf)?;
+//! ```
+//!
+//! Frames are 8 bits wide by default, [`frame_size_16bit`](Spi::frame_size_16bit)
+//! switches to 16-bit frames and `u16` words:
+//!
+//! ```
+//! let mut spi = spi.frame_size_16bit();
+//! spi.write(&[0x1234, 0x5678])?;
+//! ```
+//!
+//! NSS is not managed by the driver, drive the chip select with any output
+//! pin. SPI1 runs from PCLK2, SPI2 and SPI3 from PCLK1. The remapped SPI1
+//! pins require [`Afio::remap_spi1`](crate::afio::Afio::remap_spi1); SPI2
+//! and SPI3 only accept their default pins.
+//!
+//! [`Spi::new`] puts SCK and MOSI into alternate push-pull and MISO into
+//! floating input mode. [`Spi::new_from_configured`] takes pins that are
+//! already set up and leaves them alone.
+
+use core::marker::PhantomData;
+
+use crate::gpio::{Alternate, Floating, Input, Pin, PinMode, PushPull};
+use crate::hal::{blocking, spi};
+use crate::pac::{spi1, SPI1, SPI2, SPI3};
+use crate::rcc::rec::{self, ResetEnable};
+use crate::rcc::CoreClocks;
+use crate::time::Hertz;
+
+pub use crate::hal::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
+
+/// SPI error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// A received word was not read before the next one arrived
+    Overrun,
+    /// Another master drove NSS low
+    ModeFault,
+    /// CRC check failed
+    Crc,
+}
+
+/// An SPI peripheral
+pub trait Instance: crate::Sealed {
+    /// The Reset and Enable control block for this SPI
+    type Rec: ResetEnable;
+
+    /// Pointer to the register block
+    fn ptr() -> *const spi1::RegisterBlock;
+
+    /// The bus clock the SCK frequency is derived from
+    fn clock(clocks: &CoreClocks) -> Hertz;
+}
+
+/// A pin that can be used as the SCK line of `SPI`
+pub trait PinSck<SPI> {}
+
+/// A pin that can be used as the MISO line of `SPI`
+pub trait PinMiso<SPI> {}
+
+/// A pin that can be used as the MOSI line of `SPI`
+pub trait PinMosi<SPI> {}
+
+/// A `(SCK, MISO, MOSI)` pin set
+pub trait Pins<SPI> {}
+
+impl<SPI, SCK, MISO, MOSI> Pins<SPI> for (SCK, MISO, MOSI)
+where
+    SCK: PinSck<SPI>,
+    MISO: PinMiso<SPI>,
+    MOSI: PinMosi<SPI>,
+{
+}
+
+/// A `(SCK, MISO, MOSI)` pin set in any mode, that [`Spi::new`] configures
+pub trait IntoPins<SPI> {
+    /// The pins in the modes the SPI needs
+    type Pins: Pins<SPI>;
+
+    #[doc(hidden)]
+    fn into_pins(self) -> Self::Pins;
+}
+
+impl<
+        SPI,
+        const SP: char,
+        const SN: u8,
+        SM,
+        const IP: char,
+        const IN: u8,
+        IM,
+        const OP: char,
+        const ON: u8,
+        OM,
+    > IntoPins<SPI> for (Pin<SP, SN, SM>, Pin<IP, IN, IM>, Pin<OP, ON, OM>)
+where
+    SM: PinMode,
+    IM: PinMode,
+    OM: PinMode,
+    Pin<SP, SN, Alternate<PushPull>>: PinSck<SPI>,
+    Pin<IP, IN, Input<Floating>>: PinMiso<SPI>,
+    Pin<OP, ON, Alternate<PushPull>>: PinMosi<SPI>,
+{
+    type Pins = (
+        Pin<SP, SN, Alternate<PushPull>>,
+        Pin<IP, IN, Input<Floating>>,
+        Pin<OP, ON, Alternate<PushPull>>,
+    );
+
+    fn into_pins(self) -> Self::Pins {
+        (
+            self.0.into_alternate(),
+            self.1.into_floating_input(),
+            self.2.into_alternate(),
+        )
+    }
+}
+
+macro_rules! spi {
+    ($($SPIX:ident: ($Rec:ident, $pclkX:ident) => {
+        sck: [$(($SckP:literal, $SckN:literal)),*],
+        miso: [$(($MisoP:literal, $MisoN:literal)),*],
+        mosi: [$(($MosiP:literal, $MosiN:literal)),*],
+    })+) => {
+        $(
+            impl crate::Sealed for $SPIX {}
+
+            impl Instance for $SPIX {
+                type Rec = rec::$Rec;
+
+                #[inline(always)]
+                fn ptr() -> *const spi1::RegisterBlock {
+                    $SPIX::ptr()
+                }
+
+                #[inline(always)]
+                fn clock(clocks: &CoreClocks) -> Hertz {
+                    clocks.$pclkX()
+                }
+            }
+
+            $(
+                impl PinSck<$SPIX> for Pin<$SckP, $SckN, Alternate<PushPull>> {}
+            )*
+            $(
+                impl<MODE> PinMiso<$SPIX> for Pin<$MisoP, $MisoN, Input<MODE>> {}
+            )*
+            $(
+                impl PinMosi<$SPIX> for Pin<$MosiP, $MosiN, Alternate<PushPull>> {}
+            )*
+        )+
+    };
+}
+
+spi! {
+    SPI1: (Spi1, pclk2) => {
+        sck: [('A', 5), ('B', 3)],
+        miso: [('A', 6), ('B', 4)],
+        mosi: [('A', 7), ('B', 5)],
+    }
+    SPI2: (Spi2, pclk1) => {
+        sck: [('B', 13)],
+        miso: [('B', 14)],
+        mosi: [('B', 15)],
+    }
+    SPI3: (Spi3, pclk1) => {
+        sck: [('B', 3)],
+        miso: [('B', 4)],
+        mosi: [('B', 5)],
+    }
+}
+
+/// Data frame word, `u8` or `u16`
+pub trait FrameSize: Copy + crate::Sealed {
+    /// CTLR1.DFF
+    #[doc(hidden)]
+    const DFF: bool;
+    #[doc(hidden)]
+    fn from_datar(bits: u32) -> Self;
+    #[doc(hidden)]
+    fn into_datar(self) -> u32;
+}
+
+impl FrameSize for u8 {
+    const DFF: bool = false;
+
+    fn from_datar(bits: u32) -> Self {
+        bits as u8
+    }
+
+    fn into_datar(self) -> u32 {
+        u32::from(self)
+    }
+}
+
+impl FrameSize for u16 {
+    const DFF: bool = true;
+
+    fn from_datar(bits: u32) -> Self {
+        bits as u16
+    }
+
+    fn into_datar(self) -> u32 {
+        u32::from(self)
+    }
+}
+
+/// BR value of the fastest PCLK / 2^n (n = 1..=8) not above `freq`, and
+/// the resulting SCK frequency
+fn baud_rate(pclk: Hertz, freq: Hertz) -> (u8, Hertz) {
+    let pclk = pclk.raw();
+    let br = (0..7u8)
+        .find(|br| pclk / (2 << br) <= freq.raw())
+        .unwrap_or(7);
+    (br, Hertz::from_raw(pclk / (2 << br)))
+}
+
+/// SPI master exchanging `WORD`s
+pub struct Spi<SPI: Instance, PINS, WORD = u8> {
+    spi: SPI,
+    pins: PINS,
+    prec: SPI::Rec,
+    _word: PhantomData<WORD>,
+}
+
+impl<SPI: Instance, PINS: Pins<SPI>> Spi<SPI, PINS> {
+    /// Puts SCK and MOSI into alternate push-pull and MISO into floating
+    /// input mode, then configures the SPI as
+    /// [`new_from_configured`](Spi::new_from_configured).
+    pub fn new<RAW>(
+        spi: SPI,
+        pins: RAW,
+        mode: Mode,
+        freq: Hertz,
+        prec: SPI::Rec,
+        clocks: &CoreClocks,
+    ) -> Self
+    where
+        RAW: IntoPins<SPI, Pins = PINS>,
+    {
+        Self::new_from_configured(spi, pins.into_pins(), mode, freq, prec, clocks)
+    }
+
+    /// Configures the SPI as a master sending the most significant bit
+    /// first, with software NSS management, without touching the pins.
+    ///
+    /// SCK and MOSI must already be in `Alternate<PushPull>` mode and MISO
+    /// in an `Input` mode, with any pull.
+    ///
+    /// The SCK frequency is the fastest PCLK / 2^n (n = 1..=8) not above
+    /// `freq`, or PCLK / 256 if `freq` is lower than that.
+    pub fn new_from_configured(
+        spi: SPI,
+        pins: PINS,
+        mode: Mode,
+        freq: Hertz,
+        prec: SPI::Rec,
+        clocks: &CoreClocks,
+    ) -> Self {
+        let prec = prec.enable().reset();
+
+        // NOTE(unsafe) we own the SPI
+        let rb = unsafe { &*SPI::ptr() };
+
+        let (br, _) = baud_rate(SPI::clock(clocks), freq);
+
+        rb.ctlr2.reset();
+        rb.ctlr1.write(|w| unsafe {
+            w.cpha()
+                .bit(mode.phase == Phase::CaptureOnSecondTransition)
+                .cpol()
+                .bit(mode.polarity == Polarity::IdleHigh)
+                .mstr()
+                .set_bit()
+                .br()
+                .bits(br)
+                .lsbfirst()
+                .clear_bit()
+                .ssm()
+                .set_bit()
+                .ssi()
+                .set_bit()
+                .spe()
+                .set_bit()
+        });
+
+        Spi {
+            spi,
+            pins,
+            prec,
+            _word: PhantomData,
+        }
+    }
+}
+
+impl<SPI: Instance, PINS, WORD: FrameSize> Spi<SPI, PINS, WORD> {
+    fn into_frame_size<W: FrameSize>(self) -> Spi<SPI, PINS, W> {
+        // NOTE(unsafe) we own the SPI
+        let rb = unsafe { &*SPI::ptr() };
+
+        // DFF may only be changed while the SPI is disabled
+        while self.is_busy() {}
+        rb.ctlr1.modify(|_, w| w.spe().clear_bit());
+        rb.ctlr1.modify(|_, w| w.dff().bit(W::DFF));
+        rb.ctlr1.modify(|_, w| w.spe().set_bit());
+
+        Spi {
+            spi: self.spi,
+            pins: self.pins,
+            prec: self.prec,
+            _word: PhantomData,
+        }
+    }
+
+    /// Switches to 8-bit data frames
+    pub fn frame_size_8bit(self) -> Spi<SPI, PINS, u8> {
+        self.into_frame_size()
+    }
+
+    /// Switches to 16-bit data frames
+    pub fn frame_size_16bit(self) -> Spi<SPI, PINS, u16> {
+        self.into_frame_size()
+    }
+
+    /// Changes the SCK frequency, chosen as in [`new`](Spi::new), and
+    /// returns the frequency reached. Lets devices of different speeds
+    /// share the bus.
+    ///
+    /// Waits for the current frame to complete, any transfer still in
+    /// flight must be finished by then.
+    pub fn set_frequency(&mut self, freq: Hertz, clocks: &CoreClocks) -> Hertz {
+        // NOTE(unsafe) we own the SPI
+        let rb = unsafe { &*SPI::ptr() };
+        let (br, sck) = baud_rate(SPI::clock(clocks), freq);
+
+        // BR may only be changed while the SPI is disabled
+        while self.is_busy() {}
+        rb.ctlr1.modify(|_, w| w.spe().clear_bit());
+        rb.ctlr1.modify(|_, w| unsafe { w.br().bits(br) });
+        rb.ctlr1.modify(|_, w| w.spe().set_bit());
+        sck
+    }
+
+    /// Disables the SPI and its clock, and releases the peripheral, the
+    /// pins and the REC token for a later [`new`](Spi::new)
+    pub fn release(self) -> (SPI, PINS, SPI::Rec) {
+        // NOTE(unsafe) we own the SPI
+        unsafe { (*SPI::ptr()).ctlr1.reset() };
+        (self.spi, self.pins, self.prec.disable())
+    }
+
+    /// Whether the SPI is busy transferring or the TX buffer is not empty
+    pub fn is_busy(&self) -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*SPI::ptr()).statr.read().bsy().bit_is_set() }
+    }
+}
+
+impl<SPI: Instance, PINS, WORD: FrameSize> spi::FullDuplex<WORD> for Spi<SPI, PINS, WORD> {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<WORD, Error> {
+        // NOTE(unsafe) we own the SPI
+        let rb = unsafe { &*SPI::ptr() };
+        let statr = rb.statr.read();
+
+        if statr.ovr().bit_is_set() {
+            // Reading DATAR then STATR clears OVR
+            rb.datar.read();
+            rb.statr.read();
+            Err(nb::Error::Other(Error::Overrun))
+        } else if statr.modf().bit_is_set() {
+            Err(nb::Error::Other(Error::ModeFault))
+        } else if statr.crcerr().bit_is_set() {
+            Err(nb::Error::Other(Error::Crc))
+        } else if statr.rxne().bit_is_set() {
+            Ok(WORD::from_datar(rb.datar.read().bits()))
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn send(&mut self, word: WORD) -> nb::Result<(), Error> {
+        // NOTE(unsafe) we own the SPI
+        let rb = unsafe { &*SPI::ptr() };
+        let statr = rb.statr.read();
+
+        if statr.modf().bit_is_set() {
+            Err(nb::Error::Other(Error::ModeFault))
+        } else if statr.crcerr().bit_is_set() {
+            Err(nb::Error::Other(Error::Crc))
+        } else if statr.txe().bit_is_set() {
+            rb.datar.write(|w| unsafe { w.bits(word.into_datar()) });
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<SPI: Instance, PINS, WORD: FrameSize> blocking::spi::transfer::Default<WORD>
+    for Spi<SPI, PINS, WORD>
+{
+}
+
+impl<SPI: Instance, PINS, WORD: FrameSize> blocking::spi::write::Default<WORD>
+    for Spi<SPI, PINS, WORD>
+{
+}
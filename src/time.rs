@@ -1,7 +1,82 @@
 //! Time units
+//!
+//! Rates and durations are [`fugit`] types. With the [prelude](crate::prelude)
+//! in scope, integer literals convert into them directly:
+//!
+//! ```
+//! use ch32v30x_hal::prelude::*;
+//! use ch32v30x_hal::time::{Bps, Hertz, MicroSeconds, MilliSeconds};
+//!
+//! let sysclk: Hertz = 144.MHz();
+//! let i2c: Hertz = 400.kHz();
+//! let baud: Bps = 115_200.bps();
+//! let timeout: MilliSeconds = 10.millis();
+//! let pulse: MicroSeconds = 500.micros();
+//! ```
 
 pub use fugit::{
     HertzU32 as Hertz, KilohertzU32 as KiloHertz, MegahertzU32 as MegaHertz,
     MicrosDurationU32 as MicroSeconds, MillisDurationU32 as MilliSeconds,
     NanosDurationU32 as NanoSeconds,
 };
+
+/// A period, given either as a duration or as a frequency
+///
+/// Lets timers take `500.micros()` as well as `2.kHz()`. For period math on
+/// plain values, fugit converts between the two with
+/// [`Hertz::into_duration`] and [`MicroSeconds::into_rate`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Period {
+    /// Time between two events
+    Duration(MicroSeconds),
+    /// Events per second
+    Rate(Hertz),
+}
+
+impl Period {
+    /// Number of `clock` cycles in the period, rounded down. A rate of 0 Hz
+    /// is an endless period, `u64::MAX` cycles.
+    pub fn cycles(self, clock: Hertz) -> u64 {
+        match self {
+            Period::Duration(d) => u64::from(d.ticks()) * u64::from(clock.raw()) / 1_000_000,
+            Period::Rate(f) => clock.raw().checked_div(f.raw()).map_or(u64::MAX, u64::from),
+        }
+    }
+}
+
+impl From<MicroSeconds> for Period {
+    fn from(d: MicroSeconds) -> Self {
+        Period::Duration(d)
+    }
+}
+
+/// Saturates at `u32::MAX` µs, about 71 minutes
+impl From<MilliSeconds> for Period {
+    fn from(d: MilliSeconds) -> Self {
+        Period::Duration(MicroSeconds::from_ticks(d.ticks().saturating_mul(1000)))
+    }
+}
+
+impl From<Hertz> for Period {
+    fn from(f: Hertz) -> Self {
+        Period::Rate(f)
+    }
+}
+
+/// Bits per second
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Bps(pub u32);
+
+/// Extension trait that adds convenience methods to the `u32` type
+pub trait U32Ext {
+    /// Wrap in `Bps`
+    fn bps(self) -> Bps;
+}
+
+impl U32Ext for u32 {
+    fn bps(self) -> Bps {
+        Bps(self)
+    }
+}
@@ -0,0 +1,203 @@
+//! Input capture of a PWM signal
+//!
+//! Measures the period and the high time of a signal on the channel 1 or
+//! channel 2 pin of a timer. Each rising edge restarts the counter, channel
+//! 1 and 2 capture it on the rising and the falling edge.
+//!
+//! ```
+//! let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
+//! let echo = gpioa.pa6.into_floating_input();
+//!
+//! // Signals down to 10 Hz can be measured
+//! let capture = dp.TIM3.capture(echo, 10.Hz(), ccdr.peripheral.TIM3, &ccdr.clocks);
+//!
+//! let freq = capture.read_frequency()?;
+//! let width = capture.read_pulse_width()?;
+//! ```
+//!
+//! The resolution is the timer clock divided by the prescaler chosen for the
+//! lowest frequency, pick it as high as the signal allows.
+
+use super::{
+    compute_psc_period, regs, CapturePin, ChannelNum, Instance, C1, C2, CEN, UG, UIF, URS,
+};
+use crate::rcc::CoreClocks;
+use crate::time::{Hertz, MicroSeconds};
+
+use core::cell::Cell;
+
+// SMCFGR bits
+const SMS_RESET: u32 = 0b100;
+const TS_OFFSET: u32 = 4;
+// CHCTLR1 input selection, CC1S and CC2S
+const CCS_DIRECT: u32 = 0b01;
+const CCS_INDIRECT: u32 = 0b10;
+// CCER bits
+const CC1E: u32 = 1 << 0;
+const CC1P: u32 = 1 << 1;
+const CC2E: u32 = 1 << 4;
+const CC2P: u32 = 1 << 5;
+// INTFR overcapture flag of channel 1, channel 2 is the next bit
+const CC1OF: u32 = 1 << 9;
+
+/// Capture error
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// No edge in a full counter period: the signal stopped, or is slower
+    /// than the lowest frequency given to [`CaptureExt::capture`]
+    NoSignal,
+}
+
+/// Channel 1 or 2, the channels that can measure a signal
+pub trait InputChannel: ChannelNum {
+    /// SMCFGR.TS selecting the filtered input of the channel
+    #[doc(hidden)]
+    const TS: u32;
+}
+
+impl InputChannel for C1 {
+    const TS: u32 = 0b101;
+}
+
+impl InputChannel for C2 {
+    const TS: u32 = 0b110;
+}
+
+/// Extension trait to measure a signal with a timer
+pub trait CaptureExt: Instance + Sized {
+    /// Starts measuring the signal on `pin`, slowest at `min_freq`
+    fn capture<PIN>(
+        self,
+        pin: PIN,
+        min_freq: Hertz,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> Capture<Self, PIN>
+    where
+        PIN: CapturePin<Self>,
+        PIN::Channel: InputChannel;
+}
+
+impl<TIM: Instance> CaptureExt for TIM {
+    fn capture<PIN>(
+        self,
+        pin: PIN,
+        min_freq: Hertz,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> Capture<Self, PIN>
+    where
+        PIN: CapturePin<Self>,
+        PIN::Channel: InputChannel,
+    {
+        prec.enable().reset();
+        let tim = regs::<TIM>();
+
+        let clock = TIM::clock(clocks);
+        let (psc, _) = compute_psc_period(clock, min_freq);
+        tim.PSC.set(u32::from(psc));
+        tim.ATRLR.set(0xFFFF);
+
+        // The pin's channel captures the rising edges, the other one the
+        // falling edges of the same input
+        let (cc1s, cc2s, ccer) = if <PIN::Channel as ChannelNum>::N == 0 {
+            (CCS_DIRECT, CCS_INDIRECT, CC1E | CC2E | CC2P)
+        } else {
+            (CCS_INDIRECT, CCS_DIRECT, CC1E | CC2E | CC1P)
+        };
+        tim.CHCTLR[0].set(cc1s | cc2s << 8);
+        tim.CCER.set(ccer);
+        tim.SMCFGR
+            .set(<PIN::Channel as InputChannel>::TS << TS_OFFSET | SMS_RESET);
+
+        // Only overflows set UIF, not the restarts on each rising edge
+        tim.CTLR1.set(URS);
+        tim.SWEVGR.set(UG);
+        tim.INTFR.set(0);
+        tim.CTLR1.set(URS | CEN);
+
+        Capture {
+            tim: self,
+            pin,
+            tick: clock.raw() / (u32::from(psc) + 1),
+            stale: Cell::new(true),
+        }
+    }
+}
+
+/// A signal measured by a timer
+pub struct Capture<TIM, PIN> {
+    tim: TIM,
+    pin: PIN,
+    tick: u32,
+    // The capture registers still hold a cycle from before a timeout
+    stale: Cell<bool>,
+}
+
+impl<TIM: Instance, PIN> Capture<TIM, PIN>
+where
+    PIN: CapturePin<TIM>,
+    PIN::Channel: InputChannel,
+{
+    /// Returns the high time and the period of the last cycle, in timer ticks
+    ///
+    /// After a timeout, this keeps returning [`Error::NoSignal`] until two
+    /// rising edges have measured a new cycle.
+    pub fn read_duty(&self) -> Result<(u16, u16), Error> {
+        let tim = regs::<TIM>();
+        let n = usize::from(<PIN::Channel as ChannelNum>::N);
+        let of = CC1OF << n;
+        if tim.INTFR.get() & UIF != 0 {
+            // The capture registers keep the cycle before the signal
+            // stopped, wait for a second capture overwriting a first one
+            tim.INTFR.set(!(UIF | of));
+            self.stale.set(true);
+            return Err(Error::NoSignal);
+        }
+        if self.stale.get() {
+            if tim.INTFR.get() & of == 0 {
+                return Err(Error::NoSignal);
+            }
+            tim.INTFR.set(!of);
+            self.stale.set(false);
+        }
+
+        let period = tim.CHCVR[n].get() as u16;
+        let high = tim.CHCVR[n ^ 1].get() as u16;
+        if period == 0 {
+            Err(Error::NoSignal)
+        } else {
+            Ok((high, period))
+        }
+    }
+
+    /// Returns the frequency of the signal
+    pub fn read_frequency(&self) -> Result<Hertz, Error> {
+        let (_, period) = self.read_duty()?;
+        Ok(Hertz::from_raw(self.tick / u32::from(period)))
+    }
+
+    /// Returns how long the signal was high in the last cycle
+    pub fn read_pulse_width(&self) -> Result<MicroSeconds, Error> {
+        let (high, _) = self.read_duty()?;
+        let us = u64::from(high) * 1_000_000 / u64::from(self.tick);
+        Ok(MicroSeconds::from_ticks(us as u32))
+    }
+
+    /// Returns the frequency the timer counts at, the resolution of the
+    /// measurements
+    pub fn tick(&self) -> Hertz {
+        Hertz::from_raw(self.tick)
+    }
+
+    /// Stops the timer and releases it and the pin
+    pub fn release(self) -> (TIM, PIN) {
+        let tim = regs::<TIM>();
+        tim.CTLR1.set(0);
+        tim.SMCFGR.set(0);
+        tim.CCER.set(0);
+        (self.tim, self.pin)
+    }
+}
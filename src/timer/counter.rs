@@ -0,0 +1,149 @@
+//! Periodic count down timer
+//!
+//! ```
+//! let mut timer = dp.TIM2.timer(ccdr.peripheral.TIM2, &ccdr.clocks);
+//! timer.start(10.millis());
+//!
+//! loop {
+//!     block!(timer.wait()).unwrap();
+//!     // every 10 ms
+//! }
+//! ```
+//!
+//! The period can also be given as a frequency, `timer.start(100.Hz())` is
+//! the same as above.
+//!
+//! With [`listen`](Timer::listen) the timer raises its update interrupt
+//! instead, the handler must call [`clear_interrupt`](Timer::clear_interrupt).
+
+use super::{event_bits, regs, Instance, CEN, UG, UIF, URS};
+use crate::hal::timer::{CountDown, Periodic};
+use crate::rcc::CoreClocks;
+use crate::time::{Hertz, Period};
+
+// CTLR2 bits
+const MMS_UPDATE: u32 = 0b010 << 4;
+
+/// Timer event
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The timer reached the end of the period
+    Update,
+    /// The break input of an advanced-control timer disabled the outputs,
+    /// see [`Break`](super::pwm::Break)
+    Break,
+}
+
+/// Extension trait to use a timer as a count down timer
+pub trait TimerExt: Instance + Sized {
+    /// Takes the timer, which stays stopped until [`Timer::start`]
+    fn timer(self, prec: Self::Rec, clocks: &CoreClocks) -> Timer<Self>;
+}
+
+impl<TIM: Instance> TimerExt for TIM {
+    fn timer(self, prec: Self::Rec, clocks: &CoreClocks) -> Timer<Self> {
+        prec.enable().reset();
+
+        // Update events from UG don't set UIF
+        regs::<TIM>().CTLR1.set(URS);
+
+        Timer {
+            tim: self,
+            clock: TIM::clock(clocks).raw(),
+        }
+    }
+}
+
+/// A periodic count down timer
+pub struct Timer<TIM> {
+    tim: TIM,
+    clock: u32,
+}
+
+impl<TIM: Instance> Timer<TIM> {
+    /// Starts raising the interrupt of `event`, the update interrupt at
+    /// the end of each period.
+    ///
+    /// # Panics
+    ///
+    /// Panics on [`Event::Break`] if the timer has no break input.
+    pub fn listen(&mut self, event: Event) {
+        let (ie, _) = event_bits::<TIM>(event);
+        let tim = regs::<TIM>();
+        tim.DMAINTENR.set(tim.DMAINTENR.get() | ie);
+    }
+
+    /// Stops raising the interrupt of `event`
+    pub fn unlisten(&mut self, event: Event) {
+        let (ie, _) = event_bits::<TIM>(event);
+        let tim = regs::<TIM>();
+        tim.DMAINTENR.set(tim.DMAINTENR.get() & !ie);
+    }
+
+    /// Clears the pending flag of `event`
+    pub fn clear_interrupt(&mut self, event: Event) {
+        let (_, flag) = event_bits::<TIM>(event);
+        // Flags are cleared by writing 0
+        regs::<TIM>().INTFR.set(!flag);
+    }
+
+    /// Outputs the update event on TRGO, to trigger the DAC at the end of
+    /// each period
+    pub fn trigger_on_update(&mut self) {
+        regs::<TIM>().CTLR2.set(MMS_UPDATE);
+    }
+
+    /// Stops the timer
+    pub fn stop(&mut self) {
+        let tim = regs::<TIM>();
+        tim.CTLR1.set(tim.CTLR1.get() & !CEN);
+    }
+
+    /// Stops the timer and releases it
+    pub fn release(mut self) -> TIM {
+        self.stop();
+        regs::<TIM>().DMAINTENR.set(0);
+        self.tim
+    }
+}
+
+impl<TIM: Instance> CountDown for Timer<TIM> {
+    type Time = Period;
+
+    /// Starts periods of `timeout`, restarting the running one
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout` is longer than 2^32 timer ticks.
+    fn start<T>(&mut self, timeout: T)
+    where
+        T: Into<Period>,
+    {
+        let ticks = timeout.into().cycles(Hertz::from_raw(self.clock)).max(1);
+        let psc = (ticks - 1) / 0x1_0000;
+        assert!(psc <= 0xFFFF, "period too long");
+        let arr = ticks / (psc + 1) - 1;
+
+        let tim = regs::<TIM>();
+        self.stop();
+        tim.PSC.set(psc as u32);
+        tim.ATRLR.set(arr as u32);
+        tim.CNT.set(0);
+        // Load PSC
+        tim.SWEVGR.set(UG);
+        self.clear_interrupt(Event::Update);
+        tim.CTLR1.set(tim.CTLR1.get() | CEN);
+    }
+
+    fn wait(&mut self) -> nb::Result<(), void::Void> {
+        if regs::<TIM>().INTFR.get() & UIF == 0 {
+            Err(nb::Error::WouldBlock)
+        } else {
+            self.clear_interrupt(Event::Update);
+            Ok(())
+        }
+    }
+}
+
+impl<TIM: Instance> Periodic for Timer<TIM> {}
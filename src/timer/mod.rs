@@ -0,0 +1,216 @@
+//! Timers
+//!
+//! TIM1 is an advanced-control timer on APB2, TIM2-TIM4 are general-purpose
+//! timers on APB1. They share the register layout used here, TIM1 adds the
+//! break and dead-time register (`BDTR`), whose main output enable gates
+//! all of its outputs.
+//!
+//! The timers count at [`CoreClocks::pclk2_tim`] and
+//! [`CoreClocks::pclk1_tim`] respectively, which are twice the bus clock
+//! when the APB prescaler is not 1.
+
+use vcell::VolatileCell;
+
+use crate::gpio::{Alternate, Input, Pin, PushPull};
+use crate::pac::{TIM1, TIM2, TIM3, TIM4};
+use crate::rcc::rec::{self, ResetEnable};
+use crate::rcc::CoreClocks;
+use crate::time::Hertz;
+
+pub mod capture;
+mod counter;
+pub use counter::{Event, Timer, TimerExt};
+pub mod pwm;
+
+#[allow(non_snake_case, dead_code)]
+#[repr(C)]
+pub(crate) struct TIM {
+    pub(crate) CTLR1: VolatileCell<u32>,
+    pub(crate) CTLR2: VolatileCell<u32>,
+    pub(crate) SMCFGR: VolatileCell<u32>,
+    pub(crate) DMAINTENR: VolatileCell<u32>,
+    pub(crate) INTFR: VolatileCell<u32>,
+    pub(crate) SWEVGR: VolatileCell<u32>,
+    pub(crate) CHCTLR: [VolatileCell<u32>; 2],
+    pub(crate) CCER: VolatileCell<u32>,
+    pub(crate) CNT: VolatileCell<u32>,
+    pub(crate) PSC: VolatileCell<u32>,
+    pub(crate) ATRLR: VolatileCell<u32>,
+    pub(crate) RPTCR: VolatileCell<u32>,
+    pub(crate) CHCVR: [VolatileCell<u32>; 4],
+    pub(crate) BDTR: VolatileCell<u32>,
+    pub(crate) DMACFGR: VolatileCell<u32>,
+    pub(crate) DMAADR: VolatileCell<u32>,
+}
+
+// CTLR1 bits
+pub(crate) const CEN: u32 = 1 << 0;
+pub(crate) const URS: u32 = 1 << 2;
+pub(crate) const ARPE: u32 = 1 << 7;
+// DMAINTENR bits
+pub(crate) const UIE: u32 = 1 << 0;
+pub(crate) const BIE: u32 = 1 << 7;
+// INTFR and SWEVGR bits
+pub(crate) const UIF: u32 = 1 << 0;
+pub(crate) const BIF: u32 = 1 << 7;
+pub(crate) const UG: u32 = 1 << 0;
+// BDTR bits
+pub(crate) const BKE: u32 = 1 << 12;
+pub(crate) const BKP: u32 = 1 << 13;
+pub(crate) const AOE: u32 = 1 << 14;
+pub(crate) const MOE: u32 = 1 << 15;
+
+/// A timer
+pub trait Instance: crate::Sealed {
+    /// The Reset and Enable control block for this timer
+    type Rec: ResetEnable;
+
+    /// Whether this is an advanced-control timer, with a `BDTR` register
+    #[doc(hidden)]
+    const ADVANCED: bool;
+
+    /// Base address of the registers
+    #[doc(hidden)]
+    fn base() -> usize;
+
+    /// The clock the timer counts at, before the prescaler
+    fn clock(clocks: &CoreClocks) -> Hertz;
+}
+
+#[inline(always)]
+pub(crate) fn regs<T: Instance>() -> &'static TIM {
+    // NOTE(unsafe) all timers share this layout, BDTR only exists in TIM1
+    unsafe { &*(T::base() as *const TIM) }
+}
+
+/// DMAINTENR and INTFR bit of `event`
+pub(crate) fn event_bits<T: Instance>(event: Event) -> (u32, u32) {
+    match event {
+        Event::Update => (UIE, UIF),
+        Event::Break => {
+            assert!(T::ADVANCED, "no break input");
+            (BIE, BIF)
+        }
+    }
+}
+
+/// Splits `clock / freq` timer ticks into a prescaler and a period that
+/// fit their 16-bit registers, as `(PSC, period)`. The period is at most
+/// 65535, so `ATRLR = period - 1` leaves room for a 100% duty cycle.
+pub(crate) fn compute_psc_period(clock: Hertz, freq: Hertz) -> (u16, u16) {
+    let ticks = (clock.raw() / freq.raw()).max(1);
+    let psc = ((ticks - 1) / 0xFFFF).min(0xFFFF);
+    let period = (ticks / (psc + 1)).clamp(1, 0xFFFF);
+    (psc as u16, period as u16)
+}
+
+/// Channel 1 (type state)
+pub struct C1;
+/// Channel 2 (type state)
+pub struct C2;
+/// Channel 3 (type state)
+pub struct C3;
+/// Channel 4 (type state)
+pub struct C4;
+
+/// A timer channel
+pub trait ChannelNum: crate::Sealed {
+    /// Channel index, from 0
+    #[doc(hidden)]
+    const N: u8;
+}
+
+macro_rules! channel_num {
+    ($($C:ident: $N:literal,)+) => {
+        $(
+            impl crate::Sealed for $C {}
+            impl ChannelNum for $C {
+                const N: u8 = $N;
+            }
+        )+
+    };
+}
+
+channel_num! {
+    C1: 0,
+    C2: 1,
+    C3: 2,
+    C4: 3,
+}
+
+/// A pin that can be driven by a channel of `TIM`
+pub trait CPin<TIM> {
+    /// The channel driving the pin
+    type Channel: ChannelNum;
+}
+
+/// A pin that can be sampled by a channel of `TIM`
+pub trait CapturePin<TIM> {
+    /// The channel sampling the pin
+    type Channel: ChannelNum;
+}
+
+macro_rules! timer {
+    ($($TIMX:ident: ($Rec:ident, $timclk:ident, $advanced:literal) => {
+        $($C:ident: [$(($P:literal, $N:literal)),*],)+
+    })+) => {
+        $(
+            impl crate::Sealed for $TIMX {}
+
+            impl Instance for $TIMX {
+                type Rec = rec::$Rec;
+                const ADVANCED: bool = $advanced;
+
+                #[inline(always)]
+                fn base() -> usize {
+                    $TIMX::ptr() as usize
+                }
+
+                #[inline(always)]
+                fn clock(clocks: &CoreClocks) -> Hertz {
+                    clocks.$timclk()
+                }
+            }
+
+            $(
+                $(
+                    impl CPin<$TIMX> for Pin<$P, $N, Alternate<PushPull>> {
+                        type Channel = $C;
+                    }
+
+                    impl<MODE> CapturePin<$TIMX> for Pin<$P, $N, Input<MODE>> {
+                        type Channel = $C;
+                    }
+                )*
+            )+
+        )+
+    };
+}
+
+// Default pins first, then the remapped ones, see `afio::Tim*Remap`
+timer! {
+    TIM1: (Tim1, pclk2_tim, true) => {
+        C1: [('A', 8), ('E', 9)],
+        C2: [('A', 9), ('E', 11)],
+        C3: [('A', 10), ('E', 13)],
+        C4: [('A', 11), ('E', 14)],
+    }
+    TIM2: (Tim2, pclk1_tim, false) => {
+        C1: [('A', 0), ('A', 15)],
+        C2: [('A', 1), ('B', 3)],
+        C3: [('A', 2), ('B', 10)],
+        C4: [('A', 3), ('B', 11)],
+    }
+    TIM3: (Tim3, pclk1_tim, false) => {
+        C1: [('A', 6), ('B', 4), ('C', 6)],
+        C2: [('A', 7), ('B', 5), ('C', 7)],
+        C3: [('B', 0), ('C', 8)],
+        C4: [('B', 1), ('C', 9)],
+    }
+    TIM4: (Tim4, pclk1_tim, false) => {
+        C1: [('B', 6), ('D', 12)],
+        C2: [('B', 7), ('D', 13)],
+        C3: [('B', 8), ('D', 14)],
+        C4: [('B', 9), ('D', 15)],
+    }
+}
@@ -0,0 +1,379 @@
+//! Pulse Width Modulation (PWM) output
+//!
+//! ```
+//! let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
+//! let pins = (gpioa.pa6.into_alternate(), gpioa.pa7.into_alternate());
+//!
+//! let (mut ch1, mut ch2) = dp.TIM3.pwm_hz(pins, 1.kHz(), ccdr.peripheral.TIM3, &ccdr.clocks);
+//!
+//! let max = ch1.get_max_duty();
+//! ch1.set_duty(max / 2);
+//! ch1.enable();
+//! ```
+//!
+//! Channels run in PWM mode 1, high while the counter is below the duty.
+//! The duty ranges from 0 (always low) to [`get_max_duty`](PwmChannel::get_max_duty)
+//! (always high). The remapped pins require the matching
+//! [`Afio`](crate::afio::Afio) remap.
+//!
+//! A DMA burst can update several duties at once on every period, see
+//! [`PwmChannel::dma_burst`].
+//!
+//! The break input of TIM1 shuts all its outputs off in hardware, e.g. on
+//! an over-current or driver fault signal, see [`Break`]:
+//!
+//! ```
+//! let pins = (gpioa.pa8.into_alternate(),);
+//! let (mut ch1,) = dp.TIM1.pwm_hz(pins, 20.kHz(), ccdr.peripheral.TIM1, &ccdr.clocks);
+//!
+//! // The gate driver pulls its fault pin low
+//! let fault_pin = gpiob.pb12.into_pull_up_input();
+//! let mut fault = Break::new(fault_pin, BreakPolarity::ActiveLow, false);
+//! fault.listen(Event::Break);
+//! ```
+
+use core::marker::PhantomData;
+
+use super::{
+    compute_psc_period, event_bits, regs, CPin, ChannelNum, Event, Instance, AOE, ARPE, BIF, BKE,
+    BKP, CEN, MOE, UG,
+};
+use crate::dma::DmaPeripheral;
+use crate::gpio::{Input, Pin};
+use crate::hal::PwmPin;
+use crate::pac::{TIM1, TIM2, TIM3, TIM4};
+use crate::rcc::CoreClocks;
+use crate::time::Hertz;
+
+// CHCTLR output compare bits, per 8-bit channel field
+const OCPE: u32 = 1 << 3;
+const OCM_PWM1: u32 = 0b110 << 4;
+// DMAINTENR bits
+const UDE: u32 = 1 << 8;
+// Word offset of CHCVR1 from CTLR1, for DMACFGR.DBA
+const CHCVR1_OFFSET: u32 = 13;
+
+/// One or more pins of the channels of `TIM`, as a tuple
+pub trait Pins<TIM> {
+    /// One [`PwmChannel`] per pin, in the same order
+    type Channels;
+
+    #[doc(hidden)]
+    const MASK: u8;
+
+    #[doc(hidden)]
+    const COUNT: u32;
+
+    #[doc(hidden)]
+    fn channels() -> Self::Channels;
+}
+
+macro_rules! pins_tuple {
+    ($($P:ident),+) => {
+        impl<TIM, $($P),+> Pins<TIM> for ($($P,)+)
+        where
+            $($P: CPin<TIM>,)+
+        {
+            type Channels = ($(PwmChannel<TIM, $P::Channel>,)+);
+
+            const MASK: u8 = $((1 << <$P::Channel as ChannelNum>::N))|+;
+
+            const COUNT: u32 = [$(stringify!($P)),+].len() as u32;
+
+            fn channels() -> Self::Channels {
+                ($(PwmChannel::<TIM, $P::Channel> { _tim: PhantomData, _ch: PhantomData },)+)
+            }
+        }
+    };
+}
+
+pins_tuple!(P1);
+pins_tuple!(P1, P2);
+pins_tuple!(P1, P2, P3);
+pins_tuple!(P1, P2, P3, P4);
+
+/// Extension trait to drive the pins of a timer with PWM
+pub trait PwmExt: Instance + Sized {
+    /// Starts the timer at `freq` and returns one stopped channel per pin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel appears twice in `pins`.
+    fn pwm_hz<PINS: Pins<Self>>(
+        self,
+        pins: PINS,
+        freq: Hertz,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> PINS::Channels;
+}
+
+impl<TIM: Instance> PwmExt for TIM {
+    fn pwm_hz<PINS: Pins<Self>>(
+        self,
+        _pins: PINS,
+        freq: Hertz,
+        prec: Self::Rec,
+        clocks: &CoreClocks,
+    ) -> PINS::Channels {
+        assert_eq!(PINS::MASK.count_ones(), PINS::COUNT, "duplicate channel");
+
+        prec.enable().reset();
+        let tim = regs::<TIM>();
+
+        let (psc, period) = compute_psc_period(TIM::clock(clocks), freq);
+        tim.PSC.set(u32::from(psc));
+        tim.ATRLR.set(u32::from(period) - 1);
+
+        for n in 0..4 {
+            if PINS::MASK & (1 << n) != 0 {
+                let chctlr = &tim.CHCTLR[n / 2];
+                let offset = 8 * (n % 2);
+                chctlr.set((chctlr.get() & !(0xFF << offset)) | ((OCM_PWM1 | OCPE) << offset));
+            }
+        }
+
+        if TIM::ADVANCED {
+            tim.BDTR.set(tim.BDTR.get() | MOE);
+        }
+        tim.CTLR1.set(ARPE);
+        // Load PSC and ATRLR before counting
+        tim.SWEVGR.set(UG);
+        tim.CTLR1.set(ARPE | CEN);
+
+        PINS::channels()
+    }
+}
+
+/// A PWM output channel `CH` of timer `TIM`
+pub struct PwmChannel<TIM, CH> {
+    _tim: PhantomData<TIM>,
+    _ch: PhantomData<CH>,
+}
+
+impl<TIM: Instance, CH: ChannelNum> PwmChannel<TIM, CH> {
+    /// Connects the channel to its pin
+    pub fn enable(&mut self) {
+        // CCER is shared with the other channels
+        riscv::interrupt::free(|_| {
+            let ccer = &regs::<TIM>().CCER;
+            ccer.set(ccer.get() | 1 << (4 * CH::N));
+        });
+    }
+
+    /// Disconnects the channel from its pin, which floats
+    pub fn disable(&mut self) {
+        riscv::interrupt::free(|_| {
+            let ccer = &regs::<TIM>().CCER;
+            ccer.set(ccer.get() & !(1 << (4 * CH::N)));
+        });
+    }
+
+    /// Returns the duty, in timer ticks
+    pub fn get_duty(&self) -> u16 {
+        regs::<TIM>().CHCVR[usize::from(CH::N)].get() as u16
+    }
+
+    /// Returns the duty for a 100% duty cycle, the period in timer ticks
+    pub fn get_max_duty(&self) -> u16 {
+        regs::<TIM>().ATRLR.get() as u16 + 1
+    }
+
+    /// Sets the duty, in timer ticks, which takes effect on the next period
+    pub fn set_duty(&mut self, duty: u16) {
+        regs::<TIM>().CHCVR[usize::from(CH::N)].set(u32::from(duty));
+    }
+
+    /// Sets up a DMA burst writing the duties of `count` consecutive
+    /// channels, starting with this one, at every update event.
+    ///
+    /// The timer's `DMACFGR` gets this channel's compare register as base
+    /// address (`DBA`, in words from `CTLR1`) and `count - 1` as burst length
+    /// (`DBL`). Each update request then takes `count` words from the DMA
+    /// and spreads them over the compare registers through `DMAADR`, so all
+    /// duties change together, at the start of the next period:
+    ///
+    /// ```
+    /// // C1..C4 duties for 2 periods
+    /// static DUTIES: [u16; 8] = [100, 200, 300, 400, 400, 300, 200, 100];
+    ///
+    /// let burst = ch1.dma_burst(4);
+    /// // TIM3 update requests are served by DMA1 channel 3
+    /// let transfer = Transfer::write(dma1.ch3, burst, &DUTIES);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is 0 or the burst goes past channel 4.
+    pub fn dma_burst(&mut self, count: u8) -> DmaBurst<TIM> {
+        assert!(count > 0 && CH::N + count <= 4, "burst past channel 4");
+        let dba = CHCVR1_OFFSET + u32::from(CH::N);
+        regs::<TIM>().DMACFGR.set((u32::from(count - 1) << 8) | dba);
+        DmaBurst { _tim: PhantomData }
+    }
+}
+
+/// Duty updates of timer `TIM` through its DMA burst register, see
+/// [`PwmChannel::dma_burst`]
+pub struct DmaBurst<TIM> {
+    _tim: PhantomData<TIM>,
+}
+
+macro_rules! dma_burst {
+    ($($TIMX:ident: ($D:literal, $N:literal),)+) => {
+        $(
+            unsafe impl DmaPeripheral<$D, $N> for DmaBurst<$TIMX> {
+                type Word = u16;
+
+                fn address(&self) -> u32 {
+                    &regs::<$TIMX>().DMAADR as *const _ as u32
+                }
+
+                fn start_dma(&mut self) {
+                    let dmaintenr = &regs::<$TIMX>().DMAINTENR;
+                    riscv::interrupt::free(|_| dmaintenr.set(dmaintenr.get() | UDE));
+                }
+
+                fn stop_dma(&mut self) {
+                    let dmaintenr = &regs::<$TIMX>().DMAINTENR;
+                    riscv::interrupt::free(|_| dmaintenr.set(dmaintenr.get() & !UDE));
+                }
+            }
+        )+
+    };
+}
+
+// Update request channels
+dma_burst! {
+    TIM1: (1, 5),
+    TIM2: (1, 2),
+    TIM3: (1, 3),
+    TIM4: (1, 7),
+}
+
+/// Active level of the break input
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BreakPolarity {
+    /// Breaks while the input is low
+    ActiveLow,
+    /// Breaks while the input is high
+    ActiveHigh,
+}
+
+/// A pin that can be used as the break input (BKIN) of `TIM`
+pub trait BkinPin<TIM> {}
+
+// Default, partial and full remap, see `afio::Tim1Remap`
+impl<MODE> BkinPin<TIM1> for Pin<'B', 12, Input<MODE>> {}
+impl<MODE> BkinPin<TIM1> for Pin<'A', 6, Input<MODE>> {}
+impl<MODE> BkinPin<TIM1> for Pin<'E', 15, Input<MODE>> {}
+
+/// Break input of TIM1, which disables all its PWM outputs in hardware.
+///
+/// The active level clears the main output enable (MOE) asynchronously,
+/// without waiting for a timer clock edge, so the outputs are off within
+/// the input path delay, well below a microsecond. The [`Event::Break`]
+/// interrupt follows on the next timer clock. With the outputs off, the
+/// pins are no longer driven, an external pull resistor must hold the
+/// power stage in its safe state.
+///
+/// There is no input filter, glitches on the line trip the break as well.
+/// Filter the fault signal with an RC network if it is noisy.
+pub struct Break<PIN> {
+    pin: PIN,
+}
+
+impl<PIN: BkinPin<TIM1>> Break<PIN> {
+    /// Enables the break input on `pin`.
+    ///
+    /// With `automatic_output_enable`, the outputs come back on at the
+    /// first update event after the input turned inactive, otherwise only
+    /// on [`resume`](Break::resume). Call this after
+    /// [`pwm_hz`](PwmExt::pwm_hz), which resets the timer.
+    pub fn new(pin: PIN, polarity: BreakPolarity, automatic_output_enable: bool) -> Self {
+        let tim = regs::<TIM1>();
+        let mut bdtr = tim.BDTR.get() & !(BKP | AOE);
+        if polarity == BreakPolarity::ActiveHigh {
+            bdtr |= BKP;
+        }
+        if automatic_output_enable {
+            bdtr |= AOE;
+        }
+        tim.BDTR.set(bdtr | BKE);
+        // Flags are cleared by writing 0
+        tim.INTFR.set(!BIF);
+        Break { pin }
+    }
+
+    /// Whether the break input tripped since the flag was last cleared
+    pub fn is_tripped(&self) -> bool {
+        regs::<TIM1>().INTFR.get() & BIF != 0
+    }
+
+    /// Whether the outputs are enabled, i.e. no break is in effect
+    pub fn is_output_enabled(&self) -> bool {
+        regs::<TIM1>().BDTR.get() & MOE != 0
+    }
+
+    /// Turns the outputs back on after a break. Has no effect while the
+    /// break input is still active.
+    pub fn resume(&mut self) {
+        let tim = regs::<TIM1>();
+        tim.BDTR.set(tim.BDTR.get() | MOE);
+    }
+
+    /// Starts raising the interrupt of `event`
+    pub fn listen(&mut self, event: Event) {
+        let (ie, _) = event_bits::<TIM1>(event);
+        let dmaintenr = &regs::<TIM1>().DMAINTENR;
+        riscv::interrupt::free(|_| dmaintenr.set(dmaintenr.get() | ie));
+    }
+
+    /// Stops raising the interrupt of `event`
+    pub fn unlisten(&mut self, event: Event) {
+        let (ie, _) = event_bits::<TIM1>(event);
+        let dmaintenr = &regs::<TIM1>().DMAINTENR;
+        riscv::interrupt::free(|_| dmaintenr.set(dmaintenr.get() & !ie));
+    }
+
+    /// Clears the pending flag of `event`
+    pub fn clear_interrupt(&mut self, event: Event) {
+        let (_, flag) = event_bits::<TIM1>(event);
+        // Flags are cleared by writing 0
+        regs::<TIM1>().INTFR.set(!flag);
+    }
+
+    /// Disables the break input and releases the pin. The outputs stay as
+    /// they are, call [`resume`](Break::resume) first after a break.
+    pub fn release(mut self) -> PIN {
+        self.unlisten(Event::Break);
+        let tim = regs::<TIM1>();
+        tim.BDTR.set(tim.BDTR.get() & !BKE);
+        self.pin
+    }
+}
+
+impl<TIM: Instance, CH: ChannelNum> PwmPin for PwmChannel<TIM, CH> {
+    type Duty = u16;
+
+    fn disable(&mut self) {
+        PwmChannel::disable(self)
+    }
+
+    fn enable(&mut self) {
+        PwmChannel::enable(self)
+    }
+
+    fn get_duty(&self) -> u16 {
+        PwmChannel::get_duty(self)
+    }
+
+    fn get_max_duty(&self) -> u16 {
+        PwmChannel::get_max_duty(self)
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        PwmChannel::set_duty(self, duty)
+    }
+}
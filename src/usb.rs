@@ -0,0 +1,89 @@
+//! USB full-speed device (USBD), for [usb-device](https://docs.rs/usb-device)
+//!
+//! The USBD is compatible with the STM32F1 USB peripheral, the
+//! [`UsbBus`] is the one of the `stm32-usbd` crate. It needs a 48MHz USB
+//! clock, so a PLLCLK of 48, 96 or 144MHz.
+//!
+//! ```
+//! let ccdr = dp.RCC.constrain().sysclk(144.MHz()).require_usbclk().freeze();
+//! let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
+//!
+//! let usb = Peripheral::new(
+//!     dp.USBD,
+//!     (gpioa.pa12.into_floating_input(), gpioa.pa11.into_floating_input()),
+//!     ccdr.peripheral.USBD,
+//!     &ccdr.clocks,
+//! );
+//! let usb_bus = UsbBus::new(usb);
+//!
+//! // The internal pull-up on D+ signals the device to the host
+//! dp.EXTEND.constrain().set_usbd_pull_up(true);
+//! ```
+//!
+//! The USBD packet memory is shared with CAN, the two can't be used at the
+//! same time.
+
+use crate::delay::CycleDelay;
+use crate::gpio::{Floating, Input, Pin};
+use crate::pac::USBD;
+use crate::rcc::rec::{self, ResetEnable};
+use crate::rcc::CoreClocks;
+use crate::time::Hertz;
+
+pub use stm32_usbd::UsbBus;
+use stm32_usbd::UsbPeripheral;
+
+/// D+ pin
+pub type PinDp = Pin<'A', 12, Input<Floating>>;
+/// D- pin
+pub type PinDm = Pin<'A', 11, Input<Floating>>;
+
+/// The USBD peripheral and its pins, to build a [`UsbBus`] from
+pub struct Peripheral {
+    /// The USBD peripheral
+    pub usb: USBD,
+    /// The `(D+, D-)` pins
+    pub pins: (PinDp, PinDm),
+}
+
+// NOTE(unsafe) the bus only accesses the registers through a critical section
+unsafe impl Sync for Peripheral {}
+
+impl Peripheral {
+    /// Enables and resets the USBD.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the USB clock isn't 48MHz.
+    pub fn new(usb: USBD, pins: (PinDp, PinDm), prec: rec::Usbd, clocks: &CoreClocks) -> Self {
+        assert_eq!(
+            clocks.usbclk(),
+            Some(Hertz::MHz(48)),
+            "USBD needs a 48, 96 or 144MHz PLLCLK"
+        );
+        prec.enable().reset();
+        Peripheral { usb, pins }
+    }
+}
+
+unsafe impl UsbPeripheral for Peripheral {
+    const REGISTERS: *const () = USBD::ptr() as *const ();
+    // The pull-up is in EXTEND, see the module docs
+    const DP_PULL_UP_FEATURE: bool = false;
+    const EP_MEMORY: *const () = 0x4000_6000 as *const ();
+    const EP_MEMORY_SIZE: usize = 512;
+    // 16-bit words at 32-bit strides, like the STM32F1
+    const EP_MEMORY_ACCESS_2X16: bool = false;
+
+    fn enable() {
+        // Clocked and reset by `Peripheral::new`
+    }
+
+    fn startup_delay() {
+        // tSTARTUP is 1us, SYSCLK is at most 144MHz
+        CycleDelay::new(Hertz::MHz(144)).delay_us(1);
+    }
+}
+
+/// The [`UsbBus`] of the USBD
+pub type UsbBusType = UsbBus<Peripheral>;
@@ -0,0 +1,108 @@
+//! WS2812 / SK6812 addressable LEDs over SPI
+//!
+//! The one-wire waveform is produced on MOSI, each data bit of the LEDs
+//! being encoded into 4 SPI bits: `1000` for a 0 and `1100` for a 1. SCK and
+//! MISO are not needed by the LEDs.
+//!
+//! The SPI clock must be between 2.2 and 3 MHz, so that a 0 stays high for
+//! 0.33 to 0.45 µs and a 1 for 0.67 to 0.9 µs, within 1.85 µs per bit.
+//! [`Spi::new`](crate::spi::Spi::new) only reaches PCLK / 2^n, asking for
+//! 3 MHz gives 2.25 MHz from a 72 or 144 MHz PCLK and 3 MHz from 96 MHz.
+//!
+//! ```
+//! let spi = Spi::new(
+//!     dp.SPI1,
+//!     (sck, miso, mosi),
+//!     spi::MODE_0,
+//!     3.MHz(),
+//!     ccdr.peripheral.SPI1,
+//!     &ccdr.clocks,
+//! );
+//! let mut leds = Ws2812::new(spi);
+//!
+//! let colors = [RGB8::new(255, 0, 0), RGB8::new(0, 255, 0)];
+//! leds.write(colors.iter().cloned())?;
+//! ```
+//!
+//! **NOTE**: Each SPI byte holds two whole LED bits, and MOSI keeps the last
+//! bit of a byte, a 0, until the next one. A pause between bytes therefore
+//! only stretches the low part of a bit. A pause longer than about 5µs,
+//! e.g. an interrupt taken in the middle of a frame, latches the frame
+//! early on older WS2812. Write the LEDs inside `riscv::interrupt::free`
+//! when interrupts are in use.
+
+use crate::hal::spi::FullDuplex;
+
+use nb::block;
+use smart_leds_trait::{SmartLedsWrite, RGB8};
+
+/// Low time latching the frame into the LEDs, 280µs for recent WS2812B,
+/// in bytes at 3 MHz
+const RESET_BYTES: usize = 105;
+
+/// A chain of WS2812 LEDs driven from the MOSI line of `SPI`
+pub struct Ws2812<SPI> {
+    spi: SPI,
+}
+
+impl<SPI, E> Ws2812<SPI>
+where
+    SPI: FullDuplex<u8, Error = E>,
+{
+    /// Drives the LEDs with `spi`, which must run in mode 0 at 2.2 to 3 MHz
+    pub fn new(spi: SPI) -> Self {
+        Ws2812 { spi }
+    }
+
+    /// Releases the SPI
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Sends one byte of LED data, MSB first, as 4 SPI bytes
+    fn write_byte(&mut self, data: u8) -> Result<(), E> {
+        let bits = (0..8).rev().fold(0u32, |bits, i| match data & (1 << i) {
+            0 => bits << 4 | 0b1000,
+            _ => bits << 4 | 0b1100,
+        });
+        for byte in &bits.to_be_bytes() {
+            self.send(*byte)?;
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, byte: u8) -> Result<(), E> {
+        block!(self.spi.send(byte))?;
+        // Drain RX, so that it never overruns
+        block!(self.spi.read())?;
+        Ok(())
+    }
+}
+
+impl<SPI, E> SmartLedsWrite for Ws2812<SPI>
+where
+    SPI: FullDuplex<u8, Error = E>,
+{
+    type Error = E;
+    type Color = RGB8;
+
+    /// Sends the colors of the LEDs, starting with the one closest to the
+    /// MCU, then latches them
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), E>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        for color in iterator {
+            let color = color.into();
+            // The LEDs take the channels in GRB order
+            self.write_byte(color.g)?;
+            self.write_byte(color.r)?;
+            self.write_byte(color.b)?;
+        }
+        for _ in 0..RESET_BYTES {
+            self.send(0)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+//! Window watchdog (WWDG)
+//!
+//! ```
+//! let mut wwdg = Wwdg::new(dp.WWDG, ccdr.peripheral.WWDG, &ccdr.clocks);
+//!
+//! // Reset unless fed in the last 4 ms before the 10 ms deadline
+//! wwdg.set_window(4_000.micros());
+//! wwdg.start(10_000.micros());
+//!
+//! loop {
+//!     control_step();
+//!     wwdg.feed();
+//! }
+//! ```
+//!
+//! The watchdog counts PCLK1 / 4096 / 2^WDGTB ticks down from its counter
+//! value to 0x3F, where it resets the chip, so a timeout spans 1 to 64
+//! ticks: up to ~58 ms at PCLK1 = 36 MHz. Feeding it while the counter is
+//! still above the window value resets the chip too.
+//!
+//! Once started, the watchdog can only be stopped by a reset.
+
+use crate::hal::watchdog::{Watchdog, WatchdogEnable};
+use crate::pac::WWDG;
+use crate::rcc::rec::{self, ResetEnable};
+use crate::rcc::CoreClocks;
+use crate::time::MicroSeconds;
+
+// The chip resets when the counter goes from 0x40 to 0x3F
+const RESET_AT: u32 = 0x3F;
+const MAX_TICKS: u64 = 64;
+
+// CTLR bits
+const WDGA: u32 = 1 << 7;
+// CFGR bits
+const WDGTB_OFFSET: u32 = 7;
+const EWI: u32 = 1 << 9;
+// STATR bits
+const EWIF: u32 = 1 << 0;
+
+/// Window watchdog
+pub struct Wwdg {
+    wwdg: WWDG,
+    pclk1: u32,
+    window: Option<MicroSeconds>,
+    counter: u32,
+    wdgtb: u32,
+}
+
+impl Wwdg {
+    /// Enables the WWDG clock. The watchdog stays stopped until
+    /// [`start`](Wwdg::start).
+    pub fn new(wwdg: WWDG, prec: rec::Wwdg, clocks: &CoreClocks) -> Self {
+        prec.enable().reset();
+
+        Wwdg {
+            wwdg,
+            pclk1: clocks.pclk1().raw(),
+            window: None,
+            counter: RESET_AT + 1,
+            wdgtb: 0,
+        }
+    }
+
+    /// Watchdog ticks in `duration` with the prescaler `wdgtb`, rounded up
+    fn ticks(&self, wdgtb: u32, duration: MicroSeconds) -> u64 {
+        let tick_hz = u64::from(self.pclk1 / 4096 >> wdgtb);
+        (u64::from(duration.ticks()) * tick_hz).div_ceil(1_000_000)
+    }
+
+    /// Only allows feeding in the last `window` before the timeout, earlier
+    /// feeds reset the chip. Applies from the next [`start`](Wwdg::start).
+    ///
+    /// By default feeding is allowed during the whole timeout.
+    pub fn set_window(&mut self, window: MicroSeconds) {
+        self.window = Some(window);
+    }
+
+    /// Starts the watchdog, or restarts it with a new timeout.
+    ///
+    /// The timeout is rounded up to a whole watchdog tick, see
+    /// [`timeout`](Wwdg::timeout) for the actual value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout` is longer than 64 ticks at PCLK1 / 32768.
+    pub fn start(&mut self, timeout: MicroSeconds) {
+        // The finest prescaler that fits the timeout
+        let (wdgtb, ticks) = (0..4)
+            .map(|wdgtb| (wdgtb, self.ticks(wdgtb, timeout)))
+            .find(|&(_, ticks)| ticks <= MAX_TICKS)
+            .expect("WWDG timeout too long");
+        let ticks = ticks.max(1) as u32;
+
+        let window = match self.window {
+            Some(window) => (self.ticks(wdgtb, window) as u32).clamp(1, ticks),
+            None => ticks,
+        };
+
+        self.wdgtb = wdgtb;
+        self.counter = RESET_AT + ticks;
+
+        let ewi = self.wwdg.cfgr.read().bits() & EWI;
+        self.wwdg
+            .cfgr
+            .write(|w| unsafe { w.bits(ewi | wdgtb << WDGTB_OFFSET | (RESET_AT + window)) });
+        self.wwdg
+            .ctlr
+            .write(|w| unsafe { w.bits(WDGA | self.counter) });
+    }
+
+    /// Reloads the counter. Must be called within the window, see
+    /// [`set_window`](Wwdg::set_window).
+    pub fn feed(&mut self) {
+        self.wwdg
+            .ctlr
+            .write(|w| unsafe { w.bits(WDGA | self.counter) });
+    }
+
+    /// The timeout set by the last [`start`](Wwdg::start), after rounding
+    pub fn timeout(&self) -> MicroSeconds {
+        let tick_hz = u64::from(self.pclk1 / 4096 >> self.wdgtb);
+        let ticks = u64::from(self.counter - RESET_AT);
+        MicroSeconds::from_ticks((ticks * 1_000_000 / tick_hz) as u32)
+    }
+
+    /// Raises the WWDG interrupt one tick before the reset, the last chance
+    /// to feed the watchdog.
+    ///
+    /// Only a reset disables the interrupt again.
+    pub fn listen(&mut self) {
+        self.wwdg
+            .cfgr
+            .modify(|r, w| unsafe { w.bits(r.bits() | EWI) });
+    }
+
+    /// Whether the early wakeup interrupt is pending
+    pub fn is_pending(&self) -> bool {
+        self.wwdg.statr.read().bits() & EWIF != 0
+    }
+
+    /// Clears the pending early wakeup interrupt
+    pub fn clear_interrupt(&mut self) {
+        self.wwdg.statr.write(|w| unsafe { w.bits(0) });
+    }
+}
+
+impl Watchdog for Wwdg {
+    fn feed(&mut self) {
+        Wwdg::feed(self)
+    }
+}
+
+impl WatchdogEnable for Wwdg {
+    type Time = MicroSeconds;
+
+    fn start<T>(&mut self, period: T)
+    where
+        T: Into<MicroSeconds>,
+    {
+        Wwdg::start(self, period.into())
+    }
+}